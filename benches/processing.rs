@@ -5,7 +5,6 @@ use enterprise_data_processor::{
     ProcessorConfig,
 };
 use serde_json::json;
-use std::time::Duration;
 
 fn bench_single_record_processing(c: &mut Criterion) {
     let rt = tokio::runtime::Runtime::new().unwrap();
@@ -162,7 +161,8 @@ fn bench_storage_operations(c: &mut Criterion) {
                 black_box(json!({"value": "test"}))
             );
             
-            async {
+            let storage = &storage;
+            async move {
                 storage.store(black_box(&record)).await.unwrap()
             }
         });
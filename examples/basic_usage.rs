@@ -4,7 +4,7 @@ use enterprise_data_processor::{
     pipeline::PipelineBuilder,
     processor::Processor,
     record::Record,
-    storage::{CachedStorage, InMemoryStorage},
+    storage::{CachedStorage, InMemoryStorage, Storage},
     transform::{EnrichTransform, NormalizeTransform},
     validation::{NonEmptyStringRule, NumericRangeRule, RequiredFieldRule, Validator},
     ProcessorConfig,
@@ -165,11 +165,13 @@ async fn example_complete_pipeline() -> Result<(), Box<dyn std::error::Error>> {
 
     for record in test_records {
         match pipeline.execute(record).await {
-            Ok(result) => {
-                info!("Pipeline success for {}", result.key);
-                info!("  Normalized name: {}", result.value["name"]);
-                info!("  Normalized email: {}", result.value["email"]);
-                info!("  Metadata added: {}", result.value["metadata"]);
+            Ok(outcome) => {
+                for result in outcome.into_records() {
+                    info!("Pipeline success for {}", result.key);
+                    info!("  Normalized name: {}", result.value["name"]);
+                    info!("  Normalized email: {}", result.value["email"]);
+                    info!("  Metadata added: {}", result.value["metadata"]);
+                }
             }
             Err(e) => {
                 info!("Pipeline failed: {}", e);
@@ -0,0 +1,443 @@
+//! Compression of persisted record data
+//!
+//! [`FileStorage`](crate::storage::FileStorage) entries and the files written
+//! by [`JsonLinesSink`](crate::sink::JsonLinesSink) and
+//! [`CsvSink`](crate::sink::CsvSink) can be compressed with gzip or zstd.
+//! Readers never need to be told: compressed data is recognised by its magic
+//! number and anything else is read as-is, so compressed and uncompressed
+//! data can be mixed freely.
+//!
+//! The codecs are only built with the `compression` feature. Without it the
+//! types here still parse from configuration, but compressing, or reading
+//! compressed data, fails with [`Error::Config`].
+
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    fmt,
+    io::SeekFrom,
+    path::Path,
+    pin::Pin,
+};
+use tokio::{
+    fs::File,
+    io::{
+        AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt,
+        BufReader, BufWriter,
+    },
+};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression algorithm
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    /// gzip (DEFLATE); levels 0 to 9
+    #[default]
+    Gzip,
+
+    /// Zstandard; levels 1 to 22
+    Zstd,
+}
+
+impl Codec {
+    /// Recognise compressed data by its magic number
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(GZIP_MAGIC) {
+            Some(Codec::Gzip)
+        } else if data.starts_with(ZSTD_MAGIC) {
+            Some(Codec::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// Name used in metrics and messages
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::Gzip => "gzip",
+            Codec::Zstd => "zstd",
+        }
+    }
+
+    /// File extension appended to compressed files
+    pub fn extension(self) -> &'static str {
+        match self {
+            Codec::Gzip => "gz",
+            Codec::Zstd => "zst",
+        }
+    }
+
+    /// Codec whose [`extension`](Self::extension) this is
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "gz" => Some(Codec::Gzip),
+            "zst" => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Level used when none is configured
+    pub fn default_level(self) -> i32 {
+        match self {
+            Codec::Gzip => 6,
+            Codec::Zstd => 3,
+        }
+    }
+
+    fn levels(self) -> std::ops::RangeInclusive<i32> {
+        match self {
+            Codec::Gzip => 0..=9,
+            Codec::Zstd => 1..=22,
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Codec and level used to compress data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Compression {
+    /// Compression algorithm
+    pub codec: Codec,
+
+    /// Compression level; the codec's default when unset
+    pub level: Option<i32>,
+}
+
+impl Compression {
+    /// Compress with the given codec at its default level
+    pub fn new(codec: Codec) -> Self {
+        Self { codec, level: None }
+    }
+
+    /// Compress with gzip at its default level
+    pub fn gzip() -> Self {
+        Self::new(Codec::Gzip)
+    }
+
+    /// Compress with zstd at its default level
+    pub fn zstd() -> Self {
+        Self::new(Codec::Zstd)
+    }
+
+    /// Set the compression level
+    pub fn level(mut self, level: i32) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Level in effect, falling back to the codec's default
+    pub fn effective_level(&self) -> i32 {
+        self.level.unwrap_or_else(|| self.codec.default_level())
+    }
+
+    /// Check the level is valid for the codec and the codec is built in
+    pub fn validate(&self) -> Result<()> {
+        let levels = self.codec.levels();
+        if !levels.contains(&self.effective_level()) {
+            return Err(Error::config(format!(
+                "{} compression level must be between {} and {}",
+                self.codec,
+                levels.start(),
+                levels.end()
+            )));
+        }
+        if !cfg!(feature = "compression") {
+            return Err(unsupported(self.codec));
+        }
+        Ok(())
+    }
+}
+
+/// Compress data in memory
+pub fn compress(data: &[u8], compression: Compression) -> Result<Vec<u8>> {
+    compression.validate()?;
+    codecs::compress(data, compression.codec, compression.effective_level())
+}
+
+/// Decompress data in memory, returning data that is not compressed unchanged
+pub fn decompress(data: &[u8]) -> Result<Cow<'_, [u8]>> {
+    match Codec::detect(data) {
+        Some(codec) => codecs::decompress(data, codec).map(Cow::Owned),
+        None => Ok(Cow::Borrowed(data)),
+    }
+}
+
+fn unsupported(codec: Codec) -> Error {
+    Error::config(format!("{} support requires the `compression` feature", codec))
+}
+
+#[cfg(feature = "compression")]
+mod codecs {
+    use super::Codec;
+    use crate::{Error, Result};
+    use async_compression::{tokio as codec_io, Level};
+    use std::{
+        io::{Read, Write},
+        pin::Pin,
+    };
+    use tokio::io::{AsyncBufRead, AsyncWrite, BufReader};
+
+    pub(super) fn compress(data: &[u8], codec: Codec, level: i32) -> Result<Vec<u8>> {
+        match codec {
+            Codec::Gzip => {
+                let level = flate2::Compression::new(level as u32);
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), level);
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            Codec::Zstd => Ok(zstd::encode_all(data, level)?),
+        }
+    }
+
+    pub(super) fn decompress(data: &[u8], codec: Codec) -> Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        let result = match codec {
+            Codec::Gzip => flate2::read::MultiGzDecoder::new(data).read_to_end(&mut decoded),
+            Codec::Zstd => zstd::Decoder::new(data)?.read_to_end(&mut decoded),
+        };
+        result.map_err(|e| Error::storage(format!("Invalid {} data: {}", codec, e)))?;
+        Ok(decoded)
+    }
+
+    pub(super) fn encoder<W>(
+        writer: W,
+        codec: Codec,
+        level: i32,
+    ) -> Result<Pin<Box<dyn AsyncWrite + Send>>>
+    where
+        W: AsyncWrite + Send + 'static,
+    {
+        let level = Level::Precise(level);
+        Ok(match codec {
+            Codec::Gzip => Box::pin(codec_io::write::GzipEncoder::with_quality(writer, level)),
+            Codec::Zstd => Box::pin(codec_io::write::ZstdEncoder::with_quality(writer, level)),
+        })
+    }
+
+    pub(super) fn decoder<R>(
+        reader: R,
+        codec: Codec,
+    ) -> Result<Pin<Box<dyn AsyncBufRead + Send>>>
+    where
+        R: AsyncBufRead + Send + 'static,
+    {
+        Ok(match codec {
+            Codec::Gzip => {
+                let mut decoder = codec_io::bufread::GzipDecoder::new(reader);
+                decoder.multiple_members(true);
+                Box::pin(BufReader::new(decoder))
+            }
+            Codec::Zstd => {
+                let mut decoder = codec_io::bufread::ZstdDecoder::new(reader);
+                decoder.multiple_members(true);
+                Box::pin(BufReader::new(decoder))
+            }
+        })
+    }
+}
+
+#[cfg(not(feature = "compression"))]
+mod codecs {
+    use super::{unsupported, Codec};
+    use crate::Result;
+    use std::pin::Pin;
+    use tokio::io::{AsyncBufRead, AsyncWrite};
+
+    pub(super) fn compress(_data: &[u8], codec: Codec, _level: i32) -> Result<Vec<u8>> {
+        Err(unsupported(codec))
+    }
+
+    pub(super) fn decompress(_data: &[u8], codec: Codec) -> Result<Vec<u8>> {
+        Err(unsupported(codec))
+    }
+
+    pub(super) fn encoder<W>(
+        _writer: W,
+        codec: Codec,
+        _level: i32,
+    ) -> Result<Pin<Box<dyn AsyncWrite + Send>>>
+    where
+        W: AsyncWrite + Send + 'static,
+    {
+        Err(unsupported(codec))
+    }
+
+    pub(super) fn decoder<R>(
+        _reader: R,
+        codec: Codec,
+    ) -> Result<Pin<Box<dyn AsyncBufRead + Send>>>
+    where
+        R: AsyncBufRead + Send + 'static,
+    {
+        Err(unsupported(codec))
+    }
+}
+
+/// File being written, compressed on the fly if configured
+pub(crate) struct FileWriter {
+    writer: Pin<Box<dyn AsyncWrite + Send>>,
+    file: File,
+    codec: Option<Codec>,
+}
+
+impl fmt::Debug for FileWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileWriter").field("codec", &self.codec).finish_non_exhaustive()
+    }
+}
+
+impl FileWriter {
+    /// Create or truncate a file
+    pub(crate) async fn create(path: &Path, compression: Option<Compression>) -> Result<Self> {
+        let file = File::create(path).await?;
+        // A second handle to the same file, for syncing underneath the buffers
+        let sync_handle = file.try_clone().await?;
+        let buffered = BufWriter::new(file);
+
+        let writer = match compression {
+            Some(compression) => {
+                compression.validate()?;
+                codecs::encoder(buffered, compression.codec, compression.effective_level())?
+            }
+            None => Box::pin(buffered),
+        };
+        Ok(Self {
+            writer,
+            file: sync_handle,
+            codec: compression.map(|c| c.codec),
+        })
+    }
+
+    /// Codec the file is compressed with, if any
+    pub(crate) fn codec(&self) -> Option<Codec> {
+        self.codec
+    }
+
+    pub(crate) async fn write_all(&mut self, data: &[u8]) -> Result<()> {
+        Ok(self.writer.write_all(data).await?)
+    }
+
+    /// Flush buffered and pending compressed output and sync it to disk
+    pub(crate) async fn flush(&mut self) -> Result<()> {
+        self.writer.flush().await?;
+        self.file.sync_data().await?;
+        Ok(())
+    }
+
+    /// Finish the compressed stream, sync the file and return its size on disk
+    pub(crate) async fn finish(mut self) -> Result<u64> {
+        self.writer.shutdown().await?;
+        self.file.sync_all().await?;
+        Ok(self.file.metadata().await?.len())
+    }
+}
+
+/// File being read, decompressed on the fly if it is compressed
+pub(crate) struct FileReader {
+    reader: Pin<Box<dyn AsyncBufRead + Send>>,
+    codec: Option<Codec>,
+}
+
+impl fmt::Debug for FileReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileReader").field("codec", &self.codec).finish_non_exhaustive()
+    }
+}
+
+impl FileReader {
+    /// Open a file, skipping the first `skip` bytes of its decompressed content
+    ///
+    /// Uncompressed files are seeked; compressed ones have to be decompressed
+    /// up to that point.
+    pub(crate) async fn open(path: &Path, skip: u64) -> Result<Self> {
+        let mut file = BufReader::new(File::open(path).await?);
+        let codec = Codec::detect(file.fill_buf().await?);
+
+        let mut reader = match codec {
+            Some(codec) => codecs::decoder(file, codec)?,
+            None => {
+                if skip > 0 {
+                    file.seek(SeekFrom::Start(skip)).await?;
+                }
+                Box::pin(file)
+            }
+        };
+        if codec.is_some() && skip > 0 {
+            tokio::io::copy(&mut (&mut reader).take(skip), &mut tokio::io::sink()).await?;
+        }
+        Ok(Self { reader, codec })
+    }
+
+    /// Codec the file is compressed with, if any
+    pub(crate) fn codec(&self) -> Option<Codec> {
+        self.codec
+    }
+
+    /// Append one line, including its terminator, to `line`
+    pub(crate) async fn read_line(&mut self, line: &mut String) -> Result<usize> {
+        Ok(self.reader.read_line(line).await?)
+    }
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use super::*;
+
+    const DATA: &[u8] = br#"{"key":"value","items":[1,2,3,4,5,6,7,8,9,10]}"#;
+
+    #[test]
+    fn test_round_trip_and_detection() {
+        for compression in [Compression::gzip(), Compression::zstd().level(19)] {
+            let compressed = compress(&DATA.repeat(20), compression).unwrap();
+            assert_eq!(Codec::detect(&compressed), Some(compression.codec));
+            assert!(compressed.len() < DATA.len() * 20);
+            assert_eq!(decompress(&compressed).unwrap(), DATA.repeat(20));
+        }
+
+        assert_eq!(Codec::detect(DATA), None);
+        assert!(matches!(decompress(DATA).unwrap(), Cow::Borrowed(_)));
+        assert!(decompress(&[0x1f, 0x8b, 0, 1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_levels_are_validated() {
+        assert!(Compression::gzip().level(9).validate().is_ok());
+        assert!(Compression::gzip().level(10).validate().is_err());
+        assert!(Compression::zstd().level(0).validate().is_err());
+        assert_eq!(Compression::zstd().effective_level(), 3);
+
+        let parsed: Compression = serde_json::from_str(r#"{"codec": "zstd"}"#).unwrap();
+        assert_eq!(parsed, Compression::zstd());
+    }
+
+    #[tokio::test]
+    async fn test_file_writer_and_reader() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, compression) in [("plain", None), ("packed", Some(Compression::zstd()))] {
+            let path = dir.path().join(name);
+            let mut writer = FileWriter::create(&path, compression).await.unwrap();
+            writer.write_all(b"first\nsecond\n").await.unwrap();
+            writer.flush().await.unwrap();
+            writer.write_all(b"third\n").await.unwrap();
+            let size = writer.finish().await.unwrap();
+            assert_eq!(size, std::fs::metadata(&path).unwrap().len());
+
+            let mut reader = FileReader::open(&path, 6).await.unwrap();
+            assert_eq!(reader.codec(), compression.map(|c| c.codec));
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            reader.read_line(&mut line).await.unwrap();
+            assert_eq!(line, "second\nthird\n");
+        }
+    }
+}
@@ -1,7 +1,16 @@
 //! Configuration management for the processor
 
+use crate::compression::Compression;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Prefix of environment variables read by [`ConfigLoader`] by default
+pub const ENV_PREFIX: &str = "EDP";
 
 /// Main configuration for the data processor
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +22,7 @@ pub struct ProcessorConfig {
     pub max_workers: usize,
     
     /// Timeout for processing operations
+    #[serde(with = "duration_format")]
     pub operation_timeout: Duration,
     
     /// Enable metrics collection
@@ -28,7 +38,55 @@ pub struct ProcessorConfig {
     pub buffer_size: usize,
     
     /// Enable compression for data storage
+    ///
+    /// When set, [`active_compression`](Self::active_compression) returns the
+    /// [`compression`](Self::compression) settings for storage and sinks.
     pub enable_compression: bool,
+
+    /// Codec and level used when compression is enabled
+    #[serde(default)]
+    pub compression: Compression,
+
+    /// Prometheus exporter started by [`init_with_config`](crate::init_with_config)
+    #[serde(default)]
+    pub metrics_exporter: Option<MetricsExporterConfig>,
+
+    /// Limits on the record state kept by the processor
+    #[serde(default)]
+    pub record_retention: RetentionConfig,
+}
+
+/// Limits on the record state a [`Processor`](crate::Processor) keeps
+///
+/// The default keeps every record until
+/// [`clear_records`](crate::Processor::clear_records) is called.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Maximum number of records kept; the least recently updated are evicted first
+    pub max_records: Option<usize>,
+
+    /// Evict records that have not been updated for this long
+    #[serde(with = "duration_format::option")]
+    pub max_age: Option<Duration>,
+
+    /// Forget records as soon as they complete or are filtered out
+    pub drop_completed: bool,
+}
+
+/// Configuration for the built-in Prometheus exporter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricsExporterConfig {
+    /// Address the `/metrics` endpoint listens on
+    pub listen_address: SocketAddr,
+
+    /// Bucket bounds for histograms measured in milliseconds (names ending in `_ms`)
+    #[serde(default = "default_duration_buckets")]
+    pub duration_buckets: Vec<f64>,
+
+    /// Bucket bounds for histograms of counts and sizes (names ending in `_size` or `_bytes`)
+    #[serde(default = "default_size_buckets")]
+    pub size_buckets: Vec<f64>,
 }
 
 /// Retry configuration for failed operations
@@ -38,13 +96,19 @@ pub struct RetryConfig {
     pub max_attempts: u32,
     
     /// Initial backoff duration
+    #[serde(with = "duration_format")]
     pub initial_backoff: Duration,
     
     /// Maximum backoff duration
+    #[serde(with = "duration_format")]
     pub max_backoff: Duration,
     
     /// Backoff multiplier
     pub backoff_multiplier: f64,
+
+    /// Fraction of each backoff that is randomized (0.0 to 1.0)
+    #[serde(default = "default_jitter")]
+    pub jitter: f64,
 }
 
 impl Default for ProcessorConfig {
@@ -58,6 +122,19 @@ impl Default for ProcessorConfig {
             retry_config: RetryConfig::default(),
             buffer_size: 1000,
             enable_compression: false,
+            compression: Compression::default(),
+            metrics_exporter: None,
+            record_retention: RetentionConfig::default(),
+        }
+    }
+}
+
+impl Default for MetricsExporterConfig {
+    fn default() -> Self {
+        Self {
+            listen_address: SocketAddr::from(([127, 0, 0, 1], 9000)),
+            duration_buckets: default_duration_buckets(),
+            size_buckets: default_size_buckets(),
         }
     }
 }
@@ -69,6 +146,7 @@ impl Default for RetryConfig {
             initial_backoff: Duration::from_millis(100),
             max_backoff: Duration::from_secs(10),
             backoff_multiplier: 2.0,
+            jitter: default_jitter(),
         }
     }
 }
@@ -79,6 +157,31 @@ impl ProcessorConfig {
         ProcessorConfigBuilder::new()
     }
 
+    /// Create a loader layering files and environment variables over the defaults
+    pub fn loader() -> ConfigLoader {
+        ConfigLoader::new()
+    }
+
+    /// Load the configuration from a file, with `EDP_` environment overrides
+    pub fn from_file(path: impl AsRef<Path>) -> crate::Result<Self> {
+        ConfigLoader::new().file(path).load()
+    }
+
+    /// Load the defaults with `EDP_` environment overrides
+    pub fn from_env() -> crate::Result<Self> {
+        ConfigLoader::new().load()
+    }
+
+    /// Compression settings to use, or `None` if compression is disabled
+    ///
+    /// Pass these to [`FileStorageConfig`](crate::storage::FileStorageConfig) or
+    /// a sink's `compression` builder.
+    /// [`PipelineRegistry::with_config`](crate::pipeline::PipelineRegistry::with_config)
+    /// applies them to `file` storage in pipeline definitions.
+    pub fn active_compression(&self) -> Option<Compression> {
+        self.enable_compression.then_some(self.compression)
+    }
+
     /// Validate the configuration
     pub fn validate(&self) -> crate::Result<()> {
         if self.max_batch_size == 0 {
@@ -93,12 +196,104 @@ impl ProcessorConfig {
             return Err(crate::Error::config("buffer_size must be greater than 0"));
         }
         
+        if self.operation_timeout.is_zero() {
+            return Err(crate::Error::config("operation_timeout must be greater than 0"));
+        }
+        
         self.retry_config.validate()?;
+        self.record_retention.validate()?;
+        if self.enable_compression {
+            self.compression.validate()?;
+        }
+        
+        if let Some(exporter) = &self.metrics_exporter {
+            exporter.validate()?;
+        }
+        
+        Ok(())
+    }
+}
+
+impl MetricsExporterConfig {
+    /// Create an exporter configuration listening on the given address
+    pub fn new(listen_address: SocketAddr) -> Self {
+        Self {
+            listen_address,
+            ..Self::default()
+        }
+    }
+
+    /// Set the bucket bounds for millisecond duration histograms
+    pub fn duration_buckets(mut self, buckets: Vec<f64>) -> Self {
+        self.duration_buckets = buckets;
+        self
+    }
+
+    /// Set the bucket bounds for count and size histograms
+    pub fn size_buckets(mut self, buckets: Vec<f64>) -> Self {
+        self.size_buckets = buckets;
+        self
+    }
+
+    /// Validate the exporter configuration
+    pub fn validate(&self) -> crate::Result<()> {
+        for (name, buckets) in [
+            ("duration_buckets", &self.duration_buckets),
+            ("size_buckets", &self.size_buckets),
+        ] {
+            if buckets.is_empty() {
+                return Err(crate::Error::config(format!("{} must not be empty", name)));
+            }
+            if buckets.windows(2).any(|pair| pair[0] >= pair[1]) {
+                return Err(crate::Error::config(format!(
+                    "{} must be strictly increasing",
+                    name
+                )));
+            }
+        }
         
         Ok(())
     }
 }
 
+impl RetentionConfig {
+    /// Keep at most this many records
+    pub fn max_records(mut self, max_records: usize) -> Self {
+        self.max_records = Some(max_records);
+        self
+    }
+
+    /// Evict records not updated within this duration
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Forget records once they complete or are filtered out
+    pub fn drop_completed(mut self, drop_completed: bool) -> Self {
+        self.drop_completed = drop_completed;
+        self
+    }
+
+    /// Whether records are evicted by count or age
+    pub fn is_bounded(&self) -> bool {
+        self.max_records.is_some() || self.max_age.is_some()
+    }
+
+    /// Validate the retention configuration
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.max_records == Some(0) {
+            return Err(crate::Error::config("max_records must be greater than 0"));
+        }
+
+        if self.max_age.is_some_and(|age| age.is_zero()) {
+            return Err(crate::Error::config("max_age must be greater than 0"));
+        }
+
+        Ok(())
+    }
+}
+
 impl RetryConfig {
     /// Validate the retry configuration
     pub fn validate(&self) -> crate::Result<()> {
@@ -115,6 +310,10 @@ impl RetryConfig {
                 "initial_backoff cannot be greater than max_backoff",
             ));
         }
+
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err(crate::Error::config("jitter must be between 0.0 and 1.0"));
+        }
         
         Ok(())
     }
@@ -127,6 +326,21 @@ impl RetryConfig {
         let backoff = backoff.min(self.max_backoff.as_millis() as f64);
         Duration::from_millis(backoff as u64)
     }
+
+    /// Calculate a randomized backoff duration for a given attempt
+    ///
+    /// The exponential backoff from [`calculate_backoff`](Self::calculate_backoff)
+    /// is scaled by a random factor in `[1 - jitter, 1 + jitter]` and capped at
+    /// `max_backoff`, so that concurrent retries do not fire in lockstep.
+    pub fn calculate_backoff_with_jitter(&self, attempt: u32) -> Duration {
+        let backoff = self.calculate_backoff(attempt);
+        if self.jitter <= 0.0 {
+            return backoff;
+        }
+
+        let factor = 1.0 + rand::thread_rng().gen_range(-self.jitter..=self.jitter);
+        backoff.mul_f64(factor).min(self.max_backoff)
+    }
 }
 
 /// Builder for ProcessorConfig
@@ -191,6 +405,25 @@ impl ProcessorConfigBuilder {
         self
     }
 
+    /// Enable compression with the given codec and level
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.config.enable_compression = true;
+        self.config.compression = compression;
+        self
+    }
+
+    /// Set the record retention limits
+    pub fn record_retention(mut self, retention: RetentionConfig) -> Self {
+        self.config.record_retention = retention;
+        self
+    }
+
+    /// Serve Prometheus metrics when the library is initialized
+    pub fn metrics_exporter(mut self, exporter: MetricsExporterConfig) -> Self {
+        self.config.metrics_exporter = Some(exporter);
+        self
+    }
+
     /// Build the configuration
     pub fn build(self) -> ProcessorConfig {
         self.config
@@ -209,6 +442,271 @@ impl Default for ProcessorConfigBuilder {
     }
 }
 
+/// Loads a [`ProcessorConfig`] from layered sources
+///
+/// Later layers override earlier ones: the defaults first, then each file in
+/// the order added, then environment variables. Environment variables use the
+/// prefix (default `EDP`) followed by the field name, with `__` separating
+/// nested fields, e.g. `EDP_MAX_WORKERS=8` or
+/// `EDP_RETRY_CONFIG__INITIAL_BACKOFF=250ms`. Files may be TOML, YAML or JSON,
+/// and durations accept strings such as `"30s"`. The result is checked with
+/// [`ProcessorConfig::validate`].
+#[derive(Debug, Clone)]
+pub struct ConfigLoader {
+    files: Vec<(PathBuf, bool)>,
+    env_prefix: Option<String>,
+}
+
+impl ConfigLoader {
+    /// Create a loader reading `EDP_` environment variables
+    pub fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            env_prefix: Some(ENV_PREFIX.to_string()),
+        }
+    }
+
+    /// Add a file that must exist
+    pub fn file(mut self, path: impl AsRef<Path>) -> Self {
+        self.files.push((path.as_ref().to_path_buf(), true));
+        self
+    }
+
+    /// Add a file that is skipped if it does not exist
+    pub fn optional_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.files.push((path.as_ref().to_path_buf(), false));
+        self
+    }
+
+    /// Read environment variables with a different prefix
+    pub fn env_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.env_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Ignore environment variables
+    pub fn no_env(mut self) -> Self {
+        self.env_prefix = None;
+        self
+    }
+
+    /// Files layered by this loader, in order
+    pub fn paths(&self) -> impl Iterator<Item = &Path> + '_ {
+        self.files.iter().map(|(path, _)| path.as_path())
+    }
+
+    /// Load, merge and validate the configuration
+    pub fn load(&self) -> crate::Result<ProcessorConfig> {
+        let config_error = |e: ::config::ConfigError| crate::Error::config(e.to_string());
+
+        let defaults =
+            ::config::Config::try_from(&ProcessorConfig::default()).map_err(config_error)?;
+        let mut builder = ::config::Config::builder().add_source(defaults);
+        for (path, required) in &self.files {
+            builder = builder.add_source(::config::File::from(path.as_path()).required(*required));
+        }
+        if let Some(prefix) = &self.env_prefix {
+            builder = builder.add_source(
+                ::config::Environment::with_prefix(prefix)
+                    .prefix_separator("_")
+                    .separator("__")
+                    .try_parsing(true),
+            );
+        }
+
+        let settings = builder.build().map_err(config_error)?;
+        // Serde errors from the duration fields don't carry the key, so check them up front
+        for key in DURATION_KEYS {
+            if let Ok(value) = settings.get_string(key) {
+                parse_duration(&value)
+                    .map_err(|e| crate::Error::config(format!("{}: {}", key, e)))?;
+            }
+        }
+        let config: ProcessorConfig = settings.try_deserialize().map_err(config_error)?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keys holding durations, checked by [`ConfigLoader::load`]
+const DURATION_KEYS: [&str; 4] = [
+    "operation_timeout",
+    "retry_config.initial_backoff",
+    "retry_config.max_backoff",
+    "record_retention.max_age",
+];
+
+/// Serde format for durations
+///
+/// Durations are written as strings such as `"30s"` or `"250ms"`. Reading also
+/// accepts a number of seconds and the `{ secs, nanos }` form used by earlier
+/// versions.
+mod duration_format {
+    use super::parse_duration;
+    use serde::{de, Deserializer, Serializer};
+    use std::{fmt, time::Duration};
+
+    pub(super) fn serialize<S: Serializer>(
+        duration: &Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let formatted = if duration.subsec_nanos() == 0 {
+            format!("{}s", duration.as_secs())
+        } else if duration.subsec_nanos() % 1_000_000 == 0 {
+            format!("{}ms", duration.as_millis())
+        } else {
+            format!("{}s", duration.as_secs_f64())
+        };
+        serializer.serialize_str(&formatted)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        deserializer.deserialize_any(DurationVisitor)
+    }
+
+    struct DurationVisitor;
+
+    impl<'de> de::Visitor<'de> for DurationVisitor {
+        type Value = Duration;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a duration such as \"30s\" or a number of seconds")
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Duration, E> {
+            parse_duration(value).map_err(|_| {
+                E::invalid_value(de::Unexpected::Str(value), &self)
+            })
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<Duration, E> {
+            Ok(Duration::from_secs(value))
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<Duration, E> {
+            u64::try_from(value)
+                .map(Duration::from_secs)
+                .map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+        }
+
+        fn visit_f64<E: de::Error>(self, value: f64) -> Result<Duration, E> {
+            Duration::try_from_secs_f64(value)
+                .map_err(|_| E::invalid_value(de::Unexpected::Float(value), &self))
+        }
+
+        fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Duration, A::Error> {
+            let (mut secs, mut nanos) = (None, None);
+            while let Some(key) = map.next_key::<String>()? {
+                match key.as_str() {
+                    "secs" => secs = Some(map.next_value::<u64>()?),
+                    "nanos" => nanos = Some(map.next_value::<u32>()?),
+                    _ => return Err(de::Error::unknown_field(&key, &["secs", "nanos"])),
+                }
+            }
+            let secs = secs.ok_or_else(|| de::Error::missing_field("secs"))?;
+            Ok(Duration::new(secs, nanos.unwrap_or(0)))
+        }
+    }
+
+    /// The same format for optional durations
+    pub(super) mod option {
+        use serde::{de, Deserializer, Serializer};
+        use std::{fmt, time::Duration};
+
+        pub(in super::super) fn serialize<S: Serializer>(
+            duration: &Option<Duration>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match duration {
+                Some(duration) => super::serialize(duration, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub(in super::super) fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Duration>, D::Error> {
+            deserializer.deserialize_option(OptionVisitor)
+        }
+
+        struct OptionVisitor;
+
+        impl<'de> de::Visitor<'de> for OptionVisitor {
+            type Value = Option<Duration>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an optional duration")
+            }
+
+            fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+                Ok(None)
+            }
+
+            fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+                Ok(None)
+            }
+
+            fn visit_some<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<Self::Value, D::Error> {
+                super::deserialize(deserializer).map(Some)
+            }
+        }
+    }
+}
+
+fn default_jitter() -> f64 {
+    0.1
+}
+
+fn default_duration_buckets() -> Vec<f64> {
+    vec![
+        1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0,
+        30000.0,
+    ]
+}
+
+fn default_size_buckets() -> Vec<f64> {
+    vec![1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 5000.0, 10000.0]
+}
+
+/// Parse a human-readable duration such as `"30s"`, `"250ms"`, `"5m"` or `"1.5h"`
+///
+/// Supported units are `ms`, `s`, `m` and `h`; a bare number is read as seconds.
+pub fn parse_duration(input: &str) -> crate::Result<Duration> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+
+    let invalid = || {
+        crate::Error::config(format!(
+            "Invalid duration '{}': expected a number followed by ms, s, m or h",
+            input
+        ))
+    };
+    let value: f64 = number.parse().map_err(|_| invalid())?;
+    let seconds = match unit.trim() {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => return Err(invalid()),
+    };
+
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
 /// Get the number of logical CPU cores
 fn num_cpus() -> usize {
     std::thread::available_parallelism()
@@ -253,4 +751,166 @@ mod tests {
         assert!(backoff > retry_config.initial_backoff);
         assert!(backoff <= retry_config.max_backoff);
     }
+
+    #[test]
+    fn test_backoff_jitter_bounds() {
+        let retry_config = RetryConfig {
+            jitter: 0.5,
+            ..RetryConfig::default()
+        };
+
+        for _ in 0..100 {
+            let backoff = retry_config.calculate_backoff_with_jitter(1);
+            assert!(backoff >= Duration::from_millis(100));
+            assert!(backoff <= Duration::from_millis(300));
+        }
+
+        let invalid = RetryConfig {
+            jitter: 1.5,
+            ..RetryConfig::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_metrics_exporter_config_validation() {
+        let config = ProcessorConfig::builder()
+            .metrics_exporter(MetricsExporterConfig::default())
+            .build();
+        assert!(config.validate().is_ok());
+
+        let config = ProcessorConfig::builder()
+            .metrics_exporter(MetricsExporterConfig::default().duration_buckets(vec![10.0, 5.0]))
+            .build();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("duration_buckets"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("1.5h").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("10").unwrap(), Duration::from_secs(10));
+
+        for invalid in ["", "s", "10 days", "-5s", "1.2.3s"] {
+            assert!(parse_duration(invalid).is_err(), "{} should be rejected", invalid);
+        }
+    }
+
+    #[test]
+    fn test_durations_round_trip() {
+        let config = ProcessorConfig::builder()
+            .operation_timeout(Duration::from_millis(1500))
+            .build();
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["operation_timeout"], serde_json::json!("1500ms"));
+        assert_eq!(json["retry_config"]["max_backoff"], serde_json::json!("10s"));
+
+        let parsed: ProcessorConfig = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.operation_timeout, Duration::from_millis(1500));
+
+        let legacy = serde_json::json!({"secs": 2, "nanos": 500_000_000});
+        let mut json = serde_json::to_value(&config).unwrap();
+        json["operation_timeout"] = legacy;
+        json["retry_config"]["initial_backoff"] = serde_json::json!(1);
+        let parsed: ProcessorConfig = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.operation_timeout, Duration::from_millis(2500));
+        assert_eq!(parsed.retry_config.initial_backoff, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_loader_layers_files_and_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("edp.toml");
+        std::fs::write(
+            &base,
+            "max_batch_size = 250\noperation_timeout = \"45s\"\n\n\
+             [retry_config]\nmax_attempts = 5\n",
+        )
+        .unwrap();
+        let local = dir.path().join("edp.local.yaml");
+        std::fs::write(&local, "max_batch_size: 500\nbuffer_size: 64\n").unwrap();
+
+        std::env::set_var("EDP_LOADER_TEST_MAX_WORKERS", "3");
+        std::env::set_var("EDP_LOADER_TEST_RETRY_CONFIG__INITIAL_BACKOFF", "250ms");
+        let config = ProcessorConfig::loader()
+            .file(&base)
+            .optional_file(&local)
+            .optional_file(dir.path().join("missing.json"))
+            .env_prefix("EDP_LOADER_TEST")
+            .load()
+            .unwrap();
+        std::env::remove_var("EDP_LOADER_TEST_MAX_WORKERS");
+        std::env::remove_var("EDP_LOADER_TEST_RETRY_CONFIG__INITIAL_BACKOFF");
+
+        assert_eq!(config.max_batch_size, 500);
+        assert_eq!(config.buffer_size, 64);
+        assert_eq!(config.max_workers, 3);
+        assert_eq!(config.operation_timeout, Duration::from_secs(45));
+        assert_eq!(config.retry_config.max_attempts, 5);
+        assert_eq!(config.retry_config.initial_backoff, Duration::from_millis(250));
+        assert_eq!(config.retry_config.max_backoff, Duration::from_secs(10));
+        assert!(config.enable_metrics);
+        assert_eq!(config.record_retention, RetentionConfig::default());
+
+        std::fs::write(
+            &local,
+            "record_retention:\n  max_records: 10000\n  max_age: 15m\n  drop_completed: true\n",
+        )
+        .unwrap();
+        let config = ProcessorConfig::loader().file(&local).no_env().load().unwrap();
+        let retention = RetentionConfig::default()
+            .max_records(10_000)
+            .max_age(Duration::from_secs(900))
+            .drop_completed(true);
+        assert_eq!(config.record_retention, retention);
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["record_retention"]["max_age"], serde_json::json!("900s"));
+    }
+
+    #[test]
+    fn test_loader_reports_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let loader = |contents: &str| {
+            let path = dir.path().join("edp.json");
+            std::fs::write(&path, contents).unwrap();
+            ProcessorConfig::loader().file(&path).no_env().load().unwrap_err().to_string()
+        };
+
+        let err = loader(r#"{"operation_timeout": "soon"}"#);
+        assert!(err.contains("operation_timeout"), "{}", err);
+        let err = loader(r#"{"max_workers": "many"}"#);
+        assert!(err.contains("max_workers"), "{}", err);
+        let err = loader(r#"{"max_workers": 0}"#);
+        assert!(err.contains("max_workers must be greater than 0"), "{}", err);
+        let err = loader(r#"{"retry_config": {"initial_backoff": "1m", "max_backoff": "1s"}}"#);
+        assert!(err.contains("initial_backoff cannot be greater than max_backoff"), "{}", err);
+
+        let missing = ProcessorConfig::loader().file(dir.path().join("none.toml")).load();
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn test_compression_settings() {
+        let config = ProcessorConfig::default();
+        assert_eq!(config.active_compression(), None);
+
+        std::env::set_var("EDP_COMPRESSION_TEST_ENABLE_COMPRESSION", "true");
+        std::env::set_var("EDP_COMPRESSION_TEST_COMPRESSION__CODEC", "zstd");
+        let config = ProcessorConfig::loader().env_prefix("EDP_COMPRESSION_TEST").load();
+        std::env::remove_var("EDP_COMPRESSION_TEST_ENABLE_COMPRESSION");
+        std::env::remove_var("EDP_COMPRESSION_TEST_COMPRESSION__CODEC");
+        assert_eq!(config.unwrap().active_compression(), Some(Compression::zstd()));
+
+        let invalid = ProcessorConfig::builder().compression(Compression::gzip().level(12)).build();
+        assert!(invalid.validate().is_err());
+        let disabled = ProcessorConfig {
+            enable_compression: false,
+            ..invalid
+        };
+        assert!(disabled.validate().is_ok());
+    }
 }
@@ -0,0 +1,217 @@
+//! Dead-letter queue for records that failed processing
+
+use crate::{
+    record::Record,
+    retry::FailedAttempt,
+    storage::{InMemoryStorage, Storage},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::debug;
+use uuid::Uuid;
+
+/// A record that failed processing, with the reason it failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    /// The record to replay
+    ///
+    /// A [`Processor`](crate::Processor) stores its input record; a pipeline
+    /// stores the record as it entered the failing stage.
+    pub record: Record,
+
+    /// Error code of the final failure, from [`Error::code`]
+    pub code: String,
+
+    /// Error message of the final failure
+    pub error: String,
+
+    /// Name of the transform or stage that failed
+    pub stage: String,
+
+    /// Failed attempts at the failing stage, oldest first
+    pub attempts: Vec<FailedAttempt>,
+
+    /// When the record was dead-lettered
+    pub failed_at: DateTime<Utc>,
+}
+
+impl DeadLetter {
+    /// Describe a record that failed at `stage` with `error`
+    pub fn new(
+        record: Record,
+        stage: impl Into<String>,
+        error: &Error,
+        attempts: Vec<FailedAttempt>,
+    ) -> Self {
+        Self {
+            record,
+            code: error.code().to_string(),
+            error: error.to_string(),
+            stage: stage.into(),
+            attempts,
+            failed_at: Utc::now(),
+        }
+    }
+
+    /// ID of the dead-lettered record, which also identifies the entry
+    pub fn id(&self) -> Uuid {
+        self.record.id
+    }
+
+    /// Wrap the entry in a record for storage
+    fn to_storage_record(&self) -> Result<Record> {
+        let mut entry = Record::new(self.record.key.clone(), serde_json::to_value(self)?);
+        entry.id = self.id();
+        entry.metadata.source = "dead_letter".to_string();
+        entry.add_tag("stage", self.stage.clone());
+        entry.add_tag("code", self.code.clone());
+        Ok(entry)
+    }
+
+    fn from_storage_record(entry: Record) -> Result<Self> {
+        Ok(serde_json::from_value(entry.value)?)
+    }
+}
+
+/// Queue of [`DeadLetter`]s kept in a [`Storage`] backend
+///
+/// Entries are keyed by record ID, so a record that fails again replaces its
+/// earlier entry. Use [`in_memory`](Self::in_memory) for a transient queue or
+/// pass a [`FileStorage`](crate::storage::FileStorage) to keep entries across
+/// restarts. Cloning the queue shares the underlying storage.
+#[derive(Debug, Clone)]
+pub struct DeadLetterQueue {
+    storage: Arc<dyn Storage>,
+}
+
+impl DeadLetterQueue {
+    /// Create a queue backed by the given storage
+    pub fn new(storage: Arc<dyn Storage>) -> Self {
+        Self { storage }
+    }
+
+    /// Create a queue held in memory
+    pub fn in_memory() -> Self {
+        Self::new(Arc::new(InMemoryStorage::new()))
+    }
+
+    /// Add an entry, replacing any earlier entry for the same record
+    pub async fn push(&self, letter: DeadLetter) -> Result<()> {
+        debug!(
+            record_id = %letter.id(),
+            stage = %letter.stage,
+            code = %letter.code,
+            "Dead-lettering record"
+        );
+        self.storage.store(&letter.to_storage_record()?).await
+    }
+
+    /// Get the entry for a record
+    pub async fn get(&self, id: &Uuid) -> Result<Option<DeadLetter>> {
+        self.storage
+            .get(id)
+            .await?
+            .map(DeadLetter::from_storage_record)
+            .transpose()
+    }
+
+    /// Remove and return the entry for a record
+    pub async fn remove(&self, id: &Uuid) -> Result<Option<DeadLetter>> {
+        let letter = self.get(id).await?;
+        if letter.is_some() {
+            self.storage.delete(id).await?;
+        }
+        Ok(letter)
+    }
+
+    /// List all entries, oldest first
+    pub async fn list(&self) -> Result<Vec<DeadLetter>> {
+        let mut letters = Vec::new();
+        for id in self.storage.list().await? {
+            if let Some(letter) = self.get(&id).await? {
+                letters.push(letter);
+            }
+        }
+        letters.sort_by_key(|letter| letter.failed_at);
+        Ok(letters)
+    }
+
+    /// Number of entries
+    pub async fn len(&self) -> Result<usize> {
+        self.storage.count().await
+    }
+
+    /// Whether the queue is empty
+    pub async fn is_empty(&self) -> Result<bool> {
+        Ok(self.len().await? == 0)
+    }
+
+    /// Remove all entries
+    pub async fn clear(&self) -> Result<()> {
+        self.storage.clear().await
+    }
+
+    /// Make pushed entries durable
+    pub async fn flush(&self) -> Result<()> {
+        self.storage.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FileStorage;
+
+    fn letter(key: &str, stage: &str) -> DeadLetter {
+        let error = Error::timeout("too slow");
+        let attempts = vec![FailedAttempt::new(1, &error), FailedAttempt::new(2, &error)];
+        DeadLetter::new(Record::new(key, 1), stage, &error, attempts)
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_queue() {
+        let queue = DeadLetterQueue::in_memory();
+        let first = letter("a", "enrich");
+        let second = letter("b", "normalize");
+        queue.push(first.clone()).await.unwrap();
+        queue.push(second.clone()).await.unwrap();
+        assert_eq!(queue.len().await.unwrap(), 2);
+
+        let listed = queue.list().await.unwrap();
+        let ids: Vec<_> = listed.iter().map(DeadLetter::id).collect();
+        assert_eq!(ids, vec![first.id(), second.id()]);
+        assert_eq!(listed[0].code, "TIMEOUT");
+        assert_eq!(listed[0].attempts, first.attempts);
+
+        // A second failure replaces the entry
+        let mut again = first.clone();
+        again.stage = "validate".to_string();
+        queue.push(again).await.unwrap();
+        assert_eq!(queue.len().await.unwrap(), 2);
+        assert_eq!(queue.get(&first.id()).await.unwrap().unwrap().stage, "validate");
+
+        assert!(queue.remove(&first.id()).await.unwrap().is_some());
+        assert!(queue.remove(&first.id()).await.unwrap().is_none());
+        queue.clear().await.unwrap();
+        assert!(queue.is_empty().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_file_backed_queue_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry = letter("order-1", "enrich");
+
+        let queue = DeadLetterQueue::new(Arc::new(FileStorage::open(dir.path()).await.unwrap()));
+        queue.push(entry.clone()).await.unwrap();
+        drop(queue);
+
+        let queue = DeadLetterQueue::new(Arc::new(FileStorage::open(dir.path()).await.unwrap()));
+        let listed = queue.list().await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].record.key, "order-1");
+        assert_eq!(listed[0].stage, "enrich");
+        assert_eq!(listed[0].attempts, entry.attempts);
+    }
+}
@@ -0,0 +1,93 @@
+//! Deadlines and timeouts for processing operations
+
+use crate::{Error, Result};
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+/// Run a future with a timeout, mapping expiry to [`Error::Timeout`]
+///
+/// `operation` names the transform, stage or storage call in the error
+/// message.
+pub async fn with_timeout<T, F>(operation: &str, timeout: Duration, future: F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    match tokio::time::timeout(timeout, future).await {
+        Ok(result) => result,
+        Err(_) => Err(Error::timeout(format!(
+            "{} exceeded {}ms",
+            operation,
+            timeout.as_millis()
+        ))),
+    }
+}
+
+/// A point in time by which all work on a record must finish
+///
+/// A deadline is created once per record and carried across stages, so each
+/// stage only gets whatever budget the previous stages left over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline {
+    expires_at: Instant,
+}
+
+impl Deadline {
+    /// Create a deadline that expires after the given budget
+    pub fn after(budget: Duration) -> Self {
+        Self {
+            expires_at: Instant::now() + budget,
+        }
+    }
+
+    /// Time left before the deadline expires
+    pub fn remaining(&self) -> Duration {
+        self.expires_at.saturating_duration_since(Instant::now())
+    }
+
+    /// Check whether the deadline has passed
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// Clamp a per-operation timeout to the remaining budget
+    pub fn bound(&self, timeout: Option<Duration>) -> Duration {
+        match timeout {
+            Some(timeout) => timeout.min(self.remaining()),
+            None => self.remaining(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_with_timeout() {
+        let ok = with_timeout("fast", Duration::from_secs(1), async { Ok(1) }).await;
+        assert_eq!(ok.unwrap(), 1);
+
+        let err = with_timeout("slow", Duration::from_millis(10), async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        })
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Error::Timeout(_)));
+        assert!(err.to_string().contains("slow"));
+    }
+
+    #[test]
+    fn test_deadline_bounds_timeout() {
+        let deadline = Deadline::after(Duration::from_secs(5));
+        assert!(!deadline.is_expired());
+        assert!(deadline.bound(Some(Duration::from_secs(1))) <= Duration::from_secs(1));
+        assert!(deadline.bound(Some(Duration::from_secs(60))) <= Duration::from_secs(5));
+
+        let expired = Deadline::after(Duration::ZERO);
+        assert!(expired.is_expired());
+        assert_eq!(expired.bound(None), Duration::ZERO);
+    }
+}
@@ -0,0 +1,477 @@
+//! Field-level encryption with AES-256-GCM
+//!
+//! [`EncryptTransform`](crate::transform::EncryptTransform) replaces selected
+//! values in `Record.value` with envelopes, strings of the form
+//! `enc:v1:<key id>:<base64 nonce and ciphertext>`, and
+//! [`DecryptTransform`](crate::transform::DecryptTransform) restores them.
+//! Any JSON value can be encrypted; it is serialized first, so its type
+//! survives the round trip. Each envelope is bound to the field path it was
+//! encrypted at, and optionally to a caller-supplied context such as the
+//! record key, so it cannot be copied elsewhere and still decrypt.
+//!
+//! Keys come from a [`KeyProvider`]. New values are always encrypted with the
+//! provider's current key, and the key ID embedded in each envelope selects
+//! the key to decrypt it with. To rotate, make a new key current and keep the
+//! old one available until nothing encrypted with it remains.
+
+use crate::{Error, Result};
+use aes_gcm::{
+    aead::{Aead, KeyInit, OsRng, Payload},
+    AeadCore, Aes256Gcm, Key, Nonce,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use parking_lot::RwLock;
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+use zeroize::Zeroizing;
+
+/// Tag added to records with encrypted fields, naming the key most recently used
+pub const ENCRYPTED_TAG: &str = "encrypted";
+
+/// Length of an AES-256 key in bytes
+pub const KEY_LEN: usize = 32;
+
+const ENVELOPE_PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+
+/// AES-256 key with the ID that envelopes refer to it by
+///
+/// The key material is wiped from memory when the key is dropped and never
+/// shown by `Debug`.
+#[derive(Clone)]
+pub struct EncryptionKey {
+    id: String,
+    material: Zeroizing<[u8; KEY_LEN]>,
+}
+
+impl EncryptionKey {
+    /// Create a key from 32 bytes of key material
+    ///
+    /// IDs may contain letters, digits, `-`, `_` and `.`.
+    pub fn new(id: impl Into<String>, material: &[u8]) -> Result<Self> {
+        let id = id.into();
+        let valid_id = !id.is_empty()
+            && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid_id {
+            return Err(Error::config(format!("Invalid encryption key ID '{}'", id)));
+        }
+
+        let material: [u8; KEY_LEN] = material.try_into().map_err(|_| {
+            Error::config(format!(
+                "Encryption key '{}' must be {} bytes, not {}",
+                id,
+                KEY_LEN,
+                material.len()
+            ))
+        })?;
+        Ok(Self {
+            id,
+            material: Zeroizing::new(material),
+        })
+    }
+
+    /// Create a key from base64-encoded key material
+    pub fn from_base64(id: impl Into<String>, encoded: &str) -> Result<Self> {
+        let id = id.into();
+        let material = Zeroizing::new(STANDARD.decode(encoded.trim()).map_err(|e| {
+            Error::config(format!("Encryption key '{}' is not valid base64: {}", id, e))
+        })?);
+        Self::new(id, &material)
+    }
+
+    /// Generate a random key
+    pub fn generate(id: impl Into<String>) -> Result<Self> {
+        Self::new(id, &Aes256Gcm::generate_key(&mut OsRng))
+    }
+
+    /// ID of the key
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Key material encoded as base64, as used in key files
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.material.as_slice())
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(self.material.as_slice()))
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionKey")
+            .field("id", &self.id)
+            .field("material", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Source of encryption keys
+#[async_trait]
+pub trait KeyProvider: Send + Sync + fmt::Debug {
+    /// Key that new values are encrypted with
+    async fn current_key(&self) -> Result<EncryptionKey>;
+
+    /// Key with the given ID, or `None` if the provider does not know it
+    async fn key(&self, id: &str) -> Result<Option<EncryptionKey>>;
+}
+
+/// Provider holding a fixed set of keys in memory
+#[derive(Debug, Clone)]
+pub struct StaticKeyProvider {
+    current: EncryptionKey,
+    retired: HashMap<String, EncryptionKey>,
+}
+
+impl StaticKeyProvider {
+    /// Create a provider that encrypts with `current`
+    pub fn new(current: EncryptionKey) -> Self {
+        Self {
+            current,
+            retired: HashMap::new(),
+        }
+    }
+
+    /// Keep a retired key so values encrypted with it can still be decrypted
+    pub fn with_key(mut self, key: EncryptionKey) -> Self {
+        self.retired.insert(key.id.clone(), key);
+        self
+    }
+}
+
+#[async_trait]
+impl KeyProvider for StaticKeyProvider {
+    async fn current_key(&self) -> Result<EncryptionKey> {
+        Ok(self.current.clone())
+    }
+
+    async fn key(&self, id: &str) -> Result<Option<EncryptionKey>> {
+        if id == self.current.id {
+            return Ok(Some(self.current.clone()));
+        }
+        Ok(self.retired.get(id).cloned())
+    }
+}
+
+/// Contents of a key file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyFile {
+    current: String,
+    keys: HashMap<String, String>,
+}
+
+/// Keys loaded from a key file
+#[derive(Debug)]
+struct KeySet {
+    current: String,
+    keys: HashMap<String, EncryptionKey>,
+}
+
+/// Provider reading keys from a JSON key file
+///
+/// The file names the current key and lists every key by ID, with base64
+/// key material:
+///
+/// ```json
+/// {
+///   "current": "2024-06",
+///   "keys": {
+///     "2024-06": "q2P0b5cW6tDk3hUu0Jr4mXo8m2y3nQmZ0b9x1YbqR3E=",
+///     "2024-01": "mJ8C1s3zq9YwXyU0Kf4R2pTn7vLh6aEd5iBo0gWcN1M="
+///   }
+/// }
+/// ```
+///
+/// Call [`reload`](Self::reload) after rotating keys in the file.
+#[derive(Debug)]
+pub struct KeyFileProvider {
+    path: PathBuf,
+    keys: RwLock<KeySet>,
+}
+
+impl KeyFileProvider {
+    /// Load keys from a file
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let keys = load_key_file(&path)?;
+        Ok(Self {
+            path,
+            keys: RwLock::new(keys),
+        })
+    }
+
+    /// Path of the key file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Re-read the key file
+    ///
+    /// On error the keys loaded before stay in place.
+    pub fn reload(&self) -> Result<()> {
+        let keys = load_key_file(&self.path)?;
+        info!(
+            path = %self.path.display(),
+            current = %keys.current,
+            keys = keys.keys.len(),
+            "Reloaded encryption keys"
+        );
+        *self.keys.write() = keys;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl KeyProvider for KeyFileProvider {
+    async fn current_key(&self) -> Result<EncryptionKey> {
+        let keys = self.keys.read();
+        Ok(keys.keys[&keys.current].clone())
+    }
+
+    async fn key(&self, id: &str) -> Result<Option<EncryptionKey>> {
+        Ok(self.keys.read().keys.get(id).cloned())
+    }
+}
+
+fn load_key_file(path: &Path) -> Result<KeySet> {
+    let contents = Zeroizing::new(std::fs::read_to_string(path)?);
+    let file: KeyFile = serde_json::from_str(&contents).map_err(|e| {
+        Error::config(format!("Invalid key file {}: {}", path.display(), e))
+    })?;
+
+    let mut keys = HashMap::with_capacity(file.keys.len());
+    for (id, encoded) in file.keys {
+        let key = EncryptionKey::from_base64(id.clone(), &Zeroizing::new(encoded))?;
+        keys.insert(id, key);
+    }
+    if !keys.contains_key(&file.current) {
+        return Err(Error::config(format!(
+            "Key file {} has no key for current key ID '{}'",
+            path.display(),
+            file.current
+        )));
+    }
+    Ok(KeySet {
+        current: file.current,
+        keys,
+    })
+}
+
+/// Encrypt a value found at `field` into an envelope string
+///
+/// `field` is the concrete path of the value, such as `cards[0].number`
+/// (see [`PathMatch::path`](crate::path::PathMatch::path)). The envelope only
+/// decrypts for the same field path and `context`.
+pub fn encrypt_value(
+    value: &Value,
+    key: &EncryptionKey,
+    field: &str,
+    context: Option<&str>,
+) -> Result<Value> {
+    let plaintext = Zeroizing::new(serde_json::to_vec(value)?);
+    let header = format!("{}{}:", ENVELOPE_PREFIX, key.id);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let aad = associated_data(&header, field, context);
+    let payload = Payload {
+        msg: &plaintext,
+        aad: &aad,
+    };
+    let ciphertext = key
+        .cipher()
+        .encrypt(&nonce, payload)
+        .map_err(|_| Error::processing("Encryption failed"))?;
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(Value::String(format!("{}{}", header, STANDARD.encode(sealed))))
+}
+
+/// Decrypt an envelope produced by [`encrypt_value`]
+///
+/// Fails if the envelope was encrypted with a different key, for a different
+/// field or context, or has been tampered with.
+pub fn decrypt_value(
+    envelope: &str,
+    key: &EncryptionKey,
+    field: &str,
+    context: Option<&str>,
+) -> Result<Value> {
+    let (key_id, encoded) = parse_envelope(envelope)
+        .ok_or_else(|| Error::processing("Value is not an encryption envelope"))?;
+    if key_id != key.id {
+        return Err(Error::processing(format!(
+            "Value was encrypted with key '{}', not '{}'",
+            key_id, key.id
+        )));
+    }
+
+    let sealed = STANDARD
+        .decode(encoded)
+        .map_err(|_| Error::processing("Encrypted value is not valid base64"))?;
+    if sealed.len() < NONCE_LEN {
+        return Err(Error::processing("Encrypted value is truncated"));
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let header = &envelope[..envelope.len() - encoded.len()];
+    let aad = associated_data(header, field, context);
+    let payload = Payload {
+        msg: ciphertext,
+        aad: &aad,
+    };
+    let plaintext = Zeroizing::new(
+        key.cipher()
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| Error::processing("Decryption failed: wrong key or tampered value"))?,
+    );
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+/// ID of the key an envelope was encrypted with, or `None` if the value is not an envelope
+pub fn envelope_key_id(value: &Value) -> Option<&str> {
+    match value {
+        Value::String(s) => parse_envelope(s).map(|(key_id, _)| key_id),
+        _ => None,
+    }
+}
+
+/// Whether `value` contains an envelope anywhere inside it
+pub fn contains_envelope(value: &Value) -> bool {
+    match value {
+        Value::String(_) => envelope_key_id(value).is_some(),
+        Value::Array(items) => items.iter().any(contains_envelope),
+        Value::Object(object) => object.values().any(contains_envelope),
+        _ => false,
+    }
+}
+
+/// Authenticated data binding an envelope to its header, field and context
+///
+/// The field path is length-prefixed so the context can follow unescaped.
+fn associated_data(header: &str, field: &str, context: Option<&str>) -> Vec<u8> {
+    let mut aad = format!("{}{}:{}", header, field.len(), field);
+    if let Some(context) = context {
+        aad.push(':');
+        aad.push_str(context);
+    }
+    aad.into_bytes()
+}
+
+/// Split an envelope into key ID and encoded payload
+fn parse_envelope(s: &str) -> Option<(&str, &str)> {
+    let (key_id, encoded) = s.strip_prefix(ENVELOPE_PREFIX)?.split_once(':')?;
+    (!key_id.is_empty()).then_some((key_id, encoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_value_round_trip() {
+        let key = EncryptionKey::generate("k1").unwrap();
+        let field = "ssn";
+        for value in [json!("123-45-6789"), json!(42), json!({"street": "Main St", "no": [1, 2]})] {
+            let envelope = encrypt_value(&value, &key, field, None).unwrap();
+            assert_eq!(envelope_key_id(&envelope), Some("k1"));
+            let again = encrypt_value(&value, &key, field, None).unwrap();
+            assert_ne!(envelope, again, "nonces must differ");
+            let envelope = envelope.as_str().unwrap();
+            assert_eq!(decrypt_value(envelope, &key, field, None).unwrap(), value);
+        }
+
+        assert_eq!(envelope_key_id(&json!("plain text")), None);
+        let envelope = encrypt_value(&json!(1), &key, field, None).unwrap();
+        assert!(contains_envelope(&json!({"a": [1, envelope]})));
+    }
+
+    #[test]
+    fn test_decrypt_rejects_wrong_key_and_tampering() {
+        let key = EncryptionKey::generate("k1").unwrap();
+        let field = "ssn";
+        let envelope = encrypt_value(&json!("secret"), &key, field, None).unwrap();
+        let envelope = envelope.as_str().unwrap();
+
+        let other = EncryptionKey::generate("k1").unwrap();
+        assert!(decrypt_value(envelope, &other, field, None).is_err());
+        let renamed = EncryptionKey::new("k2", &key.material[..]).unwrap();
+        assert!(decrypt_value(envelope, &renamed, field, None).is_err());
+
+        // Claiming a different key ID breaks the authenticated header
+        let relabelled = envelope.replacen("k1", "k2", 1);
+        assert!(decrypt_value(&relabelled, &renamed, field, None).is_err());
+
+        let mut sealed = STANDARD.decode(&envelope["enc:v1:k1:".len()..]).unwrap();
+        *sealed.last_mut().unwrap() ^= 1;
+        let tampered = format!("enc:v1:k1:{}", STANDARD.encode(sealed));
+        assert!(decrypt_value(&tampered, &key, field, None).is_err());
+    }
+
+    #[test]
+    fn test_envelopes_are_bound_to_field_and_context() {
+        let key = EncryptionKey::generate("k1").unwrap();
+        let envelope = encrypt_value(&json!("secret"), &key, "ssn", None).unwrap();
+        let envelope = envelope.as_str().unwrap();
+
+        // Moved to another field, the envelope no longer decrypts
+        assert!(decrypt_value(envelope, &key, "name", None).is_err());
+        assert!(decrypt_value(envelope, &key, "ssn", Some("")).is_err());
+        assert_eq!(decrypt_value(envelope, &key, "ssn", None).unwrap(), json!("secret"));
+
+        let bound = encrypt_value(&json!("secret"), &key, "ssn", Some("c1")).unwrap();
+        let bound = bound.as_str().unwrap();
+        assert!(decrypt_value(bound, &key, "ssn", Some("c2")).is_err());
+        assert!(decrypt_value(bound, &key, "ssn", None).is_err());
+        assert_eq!(decrypt_value(bound, &key, "ssn", Some("c1")).unwrap(), json!("secret"));
+    }
+
+    #[test]
+    fn test_key_validation_and_debug() {
+        assert!(EncryptionKey::new("k1", &[0; 16]).is_err());
+        assert!(EncryptionKey::new("bad:id", &[0; KEY_LEN]).is_err());
+        assert!(EncryptionKey::new("", &[0; KEY_LEN]).is_err());
+
+        let key = EncryptionKey::new("k1", &[7; KEY_LEN]).unwrap();
+        let restored = EncryptionKey::from_base64("k1", &key.to_base64()).unwrap();
+        assert_eq!(restored.material, key.material);
+        assert!(!format!("{:?}", key).contains('7'));
+    }
+
+    #[tokio::test]
+    async fn test_key_file_provider_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.json");
+        let old = EncryptionKey::generate("2024-01").unwrap();
+        let new = EncryptionKey::generate("2024-06").unwrap();
+
+        let write = |current: &str, keys: &[&EncryptionKey]| {
+            let keys: serde_json::Map<_, _> =
+                keys.iter().map(|k| (k.id().to_string(), json!(k.to_base64()))).collect();
+            std::fs::write(&path, json!({"current": current, "keys": keys}).to_string()).unwrap();
+        };
+
+        write("2024-01", &[&old]);
+        let provider = KeyFileProvider::open(&path).unwrap();
+        assert_eq!(provider.current_key().await.unwrap().id(), "2024-01");
+        assert!(provider.key("2024-06").await.unwrap().is_none());
+
+        write("2024-06", &[&old, &new]);
+        provider.reload().unwrap();
+        assert_eq!(provider.current_key().await.unwrap().id(), "2024-06");
+        assert!(provider.key("2024-01").await.unwrap().is_some());
+
+        write("missing", &[&new]);
+        assert!(provider.reload().is_err());
+        assert_eq!(provider.current_key().await.unwrap().id(), "2024-06");
+    }
+}
@@ -1,5 +1,6 @@
 //! Error types and result aliases for the library
 
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use thiserror::Error;
 
@@ -17,6 +18,10 @@ pub enum Error {
     #[error("Validation error: {0}")]
     Validation(#[from] ValidationError),
 
+    /// Record failed one or more validation rules
+    #[error("Validation failed: {0}")]
+    ValidationFailed(ValidationReport),
+
     /// Processing error
     #[error("Processing error: {0}")]
     Processing(String),
@@ -49,13 +54,37 @@ pub enum Error {
     #[error("Concurrent access error: {0}")]
     Concurrency(String),
 
+    /// Stored record version did not match the expected one
+    #[error("Version conflict: {0}")]
+    Conflict(String),
+
     /// Generic internal error
     #[error("Internal error: {0}")]
     Internal(String),
 }
 
+/// Severity of a validation failure
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Reported, but the record still passes validation
+    Warning,
+    /// The record fails validation
+    #[default]
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => f.write_str("warning"),
+            Severity::Error => f.write_str("error"),
+        }
+    }
+}
+
 /// Validation error details
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ValidationError {
     /// Field that failed validation
     pub field: String,
@@ -63,6 +92,36 @@ pub struct ValidationError {
     pub rule: String,
     /// Additional context
     pub message: String,
+    /// Whether the failure rejects the record
+    #[serde(default)]
+    pub severity: Severity,
+}
+
+impl ValidationError {
+    /// Create a new validation error with [`Severity::Error`]
+    pub fn new(
+        field: impl Into<String>,
+        rule: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            field: field.into(),
+            rule: rule.into(),
+            message: message.into(),
+            severity: Severity::Error,
+        }
+    }
+
+    /// Set the severity
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    /// Check if this failure is only a warning
+    pub fn is_warning(&self) -> bool {
+        self.severity == Severity::Warning
+    }
 }
 
 impl fmt::Display for ValidationError {
@@ -77,6 +136,84 @@ impl fmt::Display for ValidationError {
 
 impl std::error::Error for ValidationError {}
 
+/// Every failure found while validating a record
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    issues: Vec<ValidationError>,
+}
+
+impl ValidationReport {
+    /// Create an empty report
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a report holding the given failures
+    pub(crate) fn from_issues(issues: Vec<ValidationError>) -> Self {
+        Self { issues }
+    }
+
+    /// Add a failure to the report
+    pub fn push(&mut self, issue: ValidationError) {
+        self.issues.push(issue);
+    }
+
+    /// Check that the report holds no errors; warnings are allowed
+    pub fn is_valid(&self) -> bool {
+        self.issues.iter().all(ValidationError::is_warning)
+    }
+
+    /// Check whether the report holds no failures at all
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// All failures in the order they were found
+    pub fn issues(&self) -> &[ValidationError] {
+        &self.issues
+    }
+
+    /// Failures with [`Severity::Error`]
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationError> {
+        self.issues.iter().filter(|issue| !issue.is_warning())
+    }
+
+    /// Failures with [`Severity::Warning`]
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationError> {
+        self.issues.iter().filter(|issue| issue.is_warning())
+    }
+
+    /// Consume the report and return its failures
+    pub fn into_issues(self) -> Vec<ValidationError> {
+        self.issues
+    }
+
+    /// Return the report if it is valid, or [`Error::ValidationFailed`] otherwise
+    pub fn into_result(self) -> Result<Self> {
+        if self.is_valid() {
+            Ok(self)
+        } else {
+            Err(Error::ValidationFailed(self))
+        }
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} error(s), {} warning(s)",
+            self.errors().count(),
+            self.warnings().count()
+        )?;
+        for (i, issue) in self.issues.iter().enumerate() {
+            let separator = if i == 0 { ": " } else { "; " };
+            write!(f, "{}[{}] {}", separator, issue.severity, issue)?;
+        }
+        Ok(())
+    }
+}
+
 impl Error {
     /// Create a new configuration error
     pub fn config(msg: impl Into<String>) -> Self {
@@ -108,7 +245,21 @@ impl Error {
         Error::Timeout(msg.into())
     }
 
+    /// Create a new concurrency error
+    pub fn concurrency(msg: impl Into<String>) -> Self {
+        Error::Concurrency(msg.into())
+    }
+
+    /// Create a new version conflict error
+    pub fn conflict(msg: impl Into<String>) -> Self {
+        Error::Conflict(msg.into())
+    }
+
     /// Check if error is retryable
+    ///
+    /// [`Error::Conflict`] is not: repeating the same write conflicts again,
+    /// so the record has to be re-read first (see
+    /// [`StorageExt::modify`](crate::storage::StorageExt::modify)).
     pub fn is_retryable(&self) -> bool {
         matches!(
             self,
@@ -120,7 +271,7 @@ impl Error {
     pub fn code(&self) -> &str {
         match self {
             Error::Config(_) => "CONFIG_ERROR",
-            Error::Validation(_) => "VALIDATION_ERROR",
+            Error::Validation(_) | Error::ValidationFailed(_) => "VALIDATION_ERROR",
             Error::Processing(_) => "PROCESSING_ERROR",
             Error::Storage(_) => "STORAGE_ERROR",
             Error::Io(_) => "IO_ERROR",
@@ -129,6 +280,7 @@ impl Error {
             Error::NotFound(_) => "NOT_FOUND",
             Error::Timeout(_) => "TIMEOUT",
             Error::Concurrency(_) => "CONCURRENCY_ERROR",
+            Error::Conflict(_) => "CONFLICT",
             Error::Internal(_) => "INTERNAL_ERROR",
         }
     }
@@ -150,4 +302,11 @@ mod tests {
         let timeout = Error::timeout("test timeout");
         assert!(timeout.is_retryable());
     }
+
+    #[test]
+    fn test_conflicts_are_not_retryable() {
+        let conflict = Error::conflict("expected version 1, found 2");
+        assert_eq!(conflict.code(), "CONFLICT");
+        assert!(!conflict.is_retryable());
+    }
 }
@@ -36,13 +36,24 @@
 )]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+pub mod compression;
 pub mod config;
+pub mod dead_letter;
+pub mod deadline;
+#[cfg(feature = "encryption")]
+#[cfg_attr(docsrs, doc(cfg(feature = "encryption")))]
+pub mod encryption;
 pub mod error;
 pub mod metrics;
+pub mod path;
 pub mod pipeline;
 pub mod processor;
 pub mod record;
+pub mod retry;
+pub mod sink;
+pub mod source;
 pub mod storage;
+pub mod stream;
 pub mod transform;
 pub mod validation;
 
@@ -74,10 +85,19 @@ pub fn init() {
 
 /// Initialize the library with custom configuration
 ///
+/// Installs the tracing subscriber (unless one is already set) and, if
+/// `config.metrics_exporter` is set, a Prometheus exporter that keeps serving
+/// for the life of the process. Use [`try_init_with_config`] to handle
+/// failures or to keep the exporter handle.
+///
 /// # Arguments
 ///
 /// * `config` - Custom processor configuration
 ///
+/// # Panics
+///
+/// Panics if the configuration is invalid or the exporter cannot be started.
+///
 /// # Examples
 ///
 /// ```rust
@@ -89,20 +109,62 @@ pub fn init() {
 /// init_with_config(config);
 /// ```
 pub fn init_with_config(config: config::ProcessorConfig) {
+    if let Err(e) = try_init_with_config(config) {
+        panic!("Failed to initialize Enterprise Data Processor: {}", e);
+    }
+}
+
+/// Initialize the library, returning the metrics exporter if one was started
+///
+/// Like [`init_with_config`], but returns configuration and exporter errors
+/// instead of panicking. If `config.metrics_exporter` is set, the returned
+/// handle can be used to render the metrics or stop serving.
+///
+/// `config.enable_metrics` becomes the default for components created
+/// without a [`MetricsRecorder`](metrics::MetricsRecorder), such as
+/// pipelines, validators, storage backends and sinks.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use enterprise_data_processor::{
+///     config::MetricsExporterConfig, try_init_with_config, ProcessorConfig,
+/// };
+///
+/// let config = ProcessorConfig::builder()
+///     .metrics_exporter(MetricsExporterConfig::new("127.0.0.1:9000".parse().unwrap()))
+///     .build();
+/// let exporter = try_init_with_config(config).unwrap();
+/// ```
+pub fn try_init_with_config(
+    config: config::ProcessorConfig,
+) -> Result<Option<metrics::MetricsExporter>> {
+    config.validate()?;
+
     // Initialize tracing subscriber
-    tracing_subscriber::fmt()
+    let _ = tracing_subscriber::fmt()
         .with_env_filter(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
         )
         .with_target(false)
-        .init();
+        .try_init();
+
+    metrics::MetricsRecorder::set_default_enabled(config.enable_metrics);
+
+    let exporter = config
+        .metrics_exporter
+        .as_ref()
+        .map(metrics::MetricsExporter::install)
+        .transpose()?;
 
     info!(
         version = VERSION,
         config = ?config,
         "Enterprise Data Processor initialized"
     );
+
+    Ok(exporter)
 }
 
 #[cfg(test)]
@@ -1,18 +1,74 @@
 //! Metrics and observability module
 
-use metrics::{counter, gauge, histogram};
-use std::time::Instant;
+use metrics::{counter, gauge, histogram, Label};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+mod exporter;
+
+pub use exporter::{MetricsExporter, METRICS_PATH};
+
+/// Whether recorders created with [`MetricsRecorder::default`] are enabled
+static DEFAULT_ENABLED: AtomicBool = AtomicBool::new(true);
 
 /// Metrics recorder for the processor
+///
+/// Labels added with [`with_label`](Self::with_label) are attached to every
+/// metric the recorder emits.
 #[derive(Debug, Clone)]
 pub struct MetricsRecorder {
     enabled: bool,
+    labels: Arc<Vec<Label>>,
+}
+
+impl Default for MetricsRecorder {
+    /// A recorder enabled according to [`set_default_enabled`](Self::set_default_enabled)
+    fn default() -> Self {
+        Self::new(DEFAULT_ENABLED.load(Ordering::Relaxed))
+    }
 }
 
 impl MetricsRecorder {
     /// Create a new metrics recorder
     pub fn new(enabled: bool) -> Self {
-        Self { enabled }
+        Self {
+            enabled,
+            labels: Arc::new(Vec::new()),
+        }
+    }
+
+    /// Set whether recorders created with [`default`](Self::default) are enabled
+    ///
+    /// Components that are not handed a recorder (pipelines, validators,
+    /// storage backends, sinks) start from the default.
+    /// [`init_with_config`](crate::init_with_config) sets it from
+    /// [`ProcessorConfig::enable_metrics`](crate::ProcessorConfig::enable_metrics).
+    pub fn set_default_enabled(enabled: bool) {
+        DEFAULT_ENABLED.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Add a label attached to every metric this recorder emits
+    pub fn with_label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.labels).push(Label::new(key.into(), value.into()));
+        self
+    }
+
+    /// Check whether metrics are recorded
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Base labels followed by metric-specific labels
+    fn labels<const N: usize>(&self, extra: [(&'static str, &str); N]) -> Vec<Label> {
+        let mut labels = Vec::with_capacity(self.labels.len() + N);
+        labels.extend(self.labels.iter().cloned());
+        labels.extend(extra.map(|(key, value)| Label::new(key, value.to_string())));
+        labels
     }
 
     /// Record a processed record
@@ -21,15 +77,50 @@ impl MetricsRecorder {
             return;
         }
 
-        counter!("records_processed_total").increment(1);
+        counter!("records_processed_total", self.labels([])).increment(1);
         
         if success {
-            counter!("records_processed_success").increment(1);
+            counter!("records_processed_success", self.labels([])).increment(1);
         } else {
-            counter!("records_processed_failed").increment(1);
+            counter!("records_processed_failed", self.labels([])).increment(1);
+        }
+
+        histogram!("record_processing_duration_ms", self.labels([]))
+            .record(duration_ms as f64);
+    }
+
+    /// Record a record that was filtered out
+    ///
+    /// Filtered records are counted separately from successes and failures.
+    pub fn record_filtered(&self, stage: &str) {
+        if !self.enabled {
+            return;
         }
 
-        histogram!("record_processing_duration_ms").record(duration_ms as f64);
+        counter!("records_filtered_total", self.labels([("stage", stage)])).increment(1);
+    }
+
+    /// Record a record sent to the dead-letter queue
+    pub fn record_dead_lettered(&self, stage: &str) {
+        if !self.enabled {
+            return;
+        }
+
+        counter!("records_dead_lettered_total", self.labels([("stage", stage)])).increment(1);
+    }
+
+    /// Record data written through a compression codec
+    ///
+    /// Counts the bytes before and after compression, so the ratio achieved
+    /// is `compression_output_bytes_total / compression_input_bytes_total`.
+    pub fn record_compression(&self, codec: &str, raw_bytes: u64, compressed_bytes: u64) {
+        if !self.enabled {
+            return;
+        }
+
+        let labels = self.labels([("codec", codec)]);
+        counter!("compression_input_bytes_total", labels.clone()).increment(raw_bytes);
+        counter!("compression_output_bytes_total", labels).increment(compressed_bytes);
     }
 
     /// Record batch processing
@@ -38,9 +129,10 @@ impl MetricsRecorder {
             return;
         }
 
-        counter!("batches_processed_total").increment(1);
-        histogram!("batch_size").record(count as f64);
-        histogram!("batch_processing_duration_ms").record(duration_ms as f64);
+        counter!("batches_processed_total", self.labels([])).increment(1);
+        histogram!("batch_size", self.labels([])).record(count as f64);
+        histogram!("batch_processing_duration_ms", self.labels([]))
+            .record(duration_ms as f64);
     }
 
     /// Update active tasks gauge
@@ -49,7 +141,7 @@ impl MetricsRecorder {
             return;
         }
 
-        gauge!("active_tasks").set(count as f64);
+        gauge!("active_tasks", self.labels([])).set(count as f64);
     }
 
     /// Record storage operation
@@ -58,22 +150,43 @@ impl MetricsRecorder {
             return;
         }
 
-        counter!("storage_operations_total", "operation" => operation.to_string())
-            .increment(1);
+        let labels = self.labels([("operation", operation)]);
+        counter!("storage_operations_total", labels.clone()).increment(1);
         
         if success {
-            counter!("storage_operations_success", "operation" => operation.to_string())
-                .increment(1);
+            counter!("storage_operations_success", labels.clone()).increment(1);
         } else {
-            counter!("storage_operations_failed", "operation" => operation.to_string())
-                .increment(1);
+            counter!("storage_operations_failed", labels.clone()).increment(1);
         }
 
-        histogram!(
-            "storage_operation_duration_ms",
-            "operation" => operation.to_string()
-        )
-        .record(duration_ms as f64);
+        histogram!("storage_operation_duration_ms", labels).record(duration_ms as f64);
+    }
+
+    /// Record a cache lookup
+    pub fn record_cache_access(&self, cache: &str, hit: bool) {
+        if !self.enabled {
+            return;
+        }
+
+        let labels = self.labels([("cache", cache)]);
+        if hit {
+            counter!("cache_hits_total", labels).increment(1);
+        } else {
+            counter!("cache_misses_total", labels).increment(1);
+        }
+    }
+
+    /// Record a cache eviction
+    ///
+    /// `reason` is `capacity` for entries evicted to make room and `expired`
+    /// for entries dropped after their TTL.
+    pub fn record_cache_eviction(&self, cache: &str, reason: &str) {
+        if !self.enabled {
+            return;
+        }
+
+        counter!("cache_evictions_total", self.labels([("cache", cache), ("reason", reason)]))
+            .increment(1);
     }
 
     /// Record validation
@@ -82,12 +195,13 @@ impl MetricsRecorder {
             return;
         }
 
-        counter!("validations_total", "rule" => rule.to_string()).increment(1);
+        let labels = self.labels([("rule", rule)]);
+        counter!("validations_total", labels.clone()).increment(1);
         
         if success {
-            counter!("validations_success", "rule" => rule.to_string()).increment(1);
+            counter!("validations_success", labels).increment(1);
         } else {
-            counter!("validations_failed", "rule" => rule.to_string()).increment(1);
+            counter!("validations_failed", labels).increment(1);
         }
     }
 
@@ -97,22 +211,45 @@ impl MetricsRecorder {
             return;
         }
 
-        counter!("transforms_applied_total", "transform" => name.to_string())
-            .increment(1);
+        let labels = self.labels([("transform", name)]);
+        counter!("transforms_applied_total", labels.clone()).increment(1);
         
         if success {
-            counter!("transforms_applied_success", "transform" => name.to_string())
-                .increment(1);
+            counter!("transforms_applied_success", labels.clone()).increment(1);
         } else {
-            counter!("transforms_applied_failed", "transform" => name.to_string())
-                .increment(1);
+            counter!("transforms_applied_failed", labels.clone()).increment(1);
+        }
+
+        histogram!("transform_duration_ms", labels).record(duration_ms as f64);
+    }
+
+    /// Record a pipeline stage execution
+    ///
+    /// `outcome` is `success`, `filtered` or `failed`.
+    pub fn record_stage(&self, stage: &str, duration_ms: u64, outcome: &str) {
+        if !self.enabled {
+            return;
         }
 
-        histogram!(
-            "transform_duration_ms",
-            "transform" => name.to_string()
+        counter!(
+            "pipeline_stage_executions_total",
+            self.labels([("stage", stage), ("outcome", outcome)])
         )
-        .record(duration_ms as f64);
+        .increment(1);
+        histogram!("pipeline_stage_duration_ms", self.labels([("stage", stage)]))
+            .record(duration_ms as f64);
+    }
+
+    /// Record a record that went through a whole pipeline
+    ///
+    /// `outcome` is `success`, `filtered` or `failed`.
+    pub fn record_pipeline(&self, duration_ms: u64, outcome: &str) {
+        if !self.enabled {
+            return;
+        }
+
+        counter!("pipeline_records_total", self.labels([("outcome", outcome)])).increment(1);
+        histogram!("pipeline_duration_ms", self.labels([])).record(duration_ms as f64);
     }
 
     /// Record error
@@ -121,11 +258,12 @@ impl MetricsRecorder {
             return;
         }
 
-        counter!("errors_total", "type" => error_type.to_string()).increment(1);
+        counter!("errors_total", self.labels([("type", error_type)])).increment(1);
     }
 }
 
 /// Timer for measuring operation duration
+#[derive(Debug)]
 pub struct Timer {
     start: Instant,
     name: String,
@@ -145,15 +283,89 @@ impl Timer {
     /// Stop the timer and record the duration
     pub fn stop(self) -> u64 {
         let duration_ms = self.start.elapsed().as_millis() as u64;
-        histogram!("operation_duration_ms", "operation" => self.name.clone())
-            .record(duration_ms as f64);
+        if self.recorder.enabled {
+            histogram!("operation_duration_ms", self.recorder.labels([("operation", &self.name)]))
+                .record(duration_ms as f64);
+        }
         duration_ms
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use metrics_util::{
+        debugging::{DebugValue, DebuggingRecorder},
+        CompositeKey, MetricKind,
+    };
+    use std::future::Future;
+
+    /// Metrics emitted while running a test body
+    #[derive(Debug)]
+    pub(crate) struct CapturedMetrics {
+        entries: Vec<(CompositeKey, DebugValue)>,
+    }
+
+    impl CapturedMetrics {
+        fn matching<'a>(
+            &'a self,
+            kind: MetricKind,
+            name: &'a str,
+            labels: &'a [(&'a str, &'a str)],
+        ) -> impl Iterator<Item = &'a DebugValue> + 'a {
+            self.entries
+                .iter()
+                .filter(move |(key, _)| {
+                    key.kind() == kind
+                        && key.key().name() == name
+                        && labels.iter().all(|(k, v)| {
+                            key.key().labels().any(|l| l.key() == *k && l.value() == *v)
+                        })
+                })
+                .map(|(_, value)| value)
+        }
+
+        /// Sum of all counters with the given name and labels
+        pub(crate) fn counter(&self, name: &str, labels: &[(&str, &str)]) -> u64 {
+            self.matching(MetricKind::Counter, name, labels)
+                .map(|value| match value {
+                    DebugValue::Counter(count) => *count,
+                    _ => 0,
+                })
+                .sum()
+        }
+
+        /// Number of samples across histograms with the given name and labels
+        pub(crate) fn histogram_samples(&self, name: &str, labels: &[(&str, &str)]) -> usize {
+            self.matching(MetricKind::Histogram, name, labels)
+                .map(|value| match value {
+                    DebugValue::Histogram(samples) => samples.len(),
+                    _ => 0,
+                })
+                .sum()
+        }
+    }
+
+    /// Run a future on a single-threaded runtime with a local debugging recorder
+    pub(crate) fn capture_metrics<F: Future>(future: F) -> (F::Output, CapturedMetrics) {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let output = metrics::with_local_recorder(&recorder, || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(future)
+        });
+
+        let entries = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| (key, value))
+            .collect();
+        (output, CapturedMetrics { entries })
+    }
 
     #[test]
     fn test_metrics_recorder() {
@@ -163,6 +375,56 @@ mod tests {
         recorder.update_active_tasks(5);
     }
 
+    #[test]
+    fn test_labels_and_disabled_recorder() {
+        let ((), metrics) = capture_metrics(async {
+            let recorder = MetricsRecorder::new(true).with_label("pipeline", "orders");
+            recorder.record_stage("validation", 3, "success");
+            recorder.record_transform("normalize", 1, false);
+
+            MetricsRecorder::new(false).record_transform("normalize", 1, true);
+        });
+
+        let stage = [("pipeline", "orders"), ("stage", "validation"), ("outcome", "success")];
+        assert_eq!(metrics.counter("pipeline_stage_executions_total", &stage), 1);
+        assert_eq!(metrics.counter("transforms_applied_total", &[("pipeline", "orders")]), 1);
+        assert_eq!(metrics.counter("transforms_applied_success", &[]), 0);
+        assert_eq!(metrics.histogram_samples("transform_duration_ms", &[]), 1);
+    }
+
+    #[test]
+    fn test_filtered_records() {
+        let ((), metrics) = capture_metrics(async {
+            let recorder = MetricsRecorder::new(true).with_label("pipeline", "orders");
+            recorder.record_filtered("filter");
+            recorder.record_filtered("filter");
+            MetricsRecorder::new(false).record_filtered("filter");
+        });
+
+        let labels = [("pipeline", "orders"), ("stage", "filter")];
+        assert_eq!(metrics.counter("records_filtered_total", &labels), 2);
+    }
+
+    #[test]
+    fn test_cache_metrics() {
+        let ((), metrics) = capture_metrics(async {
+            let recorder = MetricsRecorder::new(true);
+            recorder.record_cache_access("storage", true);
+            recorder.record_cache_access("storage", true);
+            recorder.record_cache_access("storage", false);
+            recorder.record_cache_eviction("storage", "capacity");
+            recorder.record_cache_eviction("storage", "expired");
+        });
+
+        let cache = [("cache", "storage")];
+        assert_eq!(metrics.counter("cache_hits_total", &cache), 2);
+        assert_eq!(metrics.counter("cache_misses_total", &cache), 1);
+        let capacity = [("cache", "storage"), ("reason", "capacity")];
+        assert_eq!(metrics.counter("cache_evictions_total", &capacity), 1);
+        let expired = [("cache", "storage"), ("reason", "expired")];
+        assert_eq!(metrics.counter("cache_evictions_total", &expired), 1);
+    }
+
     #[test]
     fn test_timer() {
         let recorder = MetricsRecorder::new(true);
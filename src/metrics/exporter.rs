@@ -0,0 +1,209 @@
+//! Prometheus exporter serving `/metrics` over HTTP
+
+use crate::{config::MetricsExporterConfig, Error, Result};
+use metrics_exporter_prometheus::{
+    Matcher, PrometheusBuilder, PrometheusHandle, PrometheusRecorder,
+};
+use std::{fmt, net::SocketAddr, thread::JoinHandle};
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+/// Path scrapers are pointed at
+///
+/// The exporter answers every path except `/health` with the metrics.
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Histogram name suffixes that use the configured size buckets
+const SIZE_SUFFIXES: &[&str] = &["_size", "_bytes"];
+
+/// Running Prometheus exporter
+///
+/// The exporter serves on its own thread, so it keeps running independently
+/// of the application's runtime. Dropping the handle leaves it running; call
+/// [`shutdown`](Self::shutdown) to stop it.
+pub struct MetricsExporter {
+    local_addr: SocketAddr,
+    handle: PrometheusHandle,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl fmt::Debug for MetricsExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricsExporter")
+            .field("local_addr", &self.local_addr)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MetricsExporter {
+    /// Install a Prometheus recorder as the global recorder and start serving it
+    ///
+    /// Fails if the address cannot be bound or another global metrics
+    /// recorder is already installed.
+    pub fn install(config: &MetricsExporterConfig) -> Result<Self> {
+        let (recorder, exporter) = Self::serve(config)?;
+
+        if metrics::set_global_recorder(recorder).is_err() {
+            exporter.shutdown();
+            return Err(Error::config("A global metrics recorder is already installed"));
+        }
+
+        info!(address = %exporter.local_addr, "Prometheus exporter listening");
+        Ok(exporter)
+    }
+
+    /// Build a recorder and serve its metrics with the exporter's HTTP listener
+    fn serve(config: &MetricsExporterConfig) -> Result<(PrometheusRecorder, Self)> {
+        // The listener binds on the runtime it is built in, so build it on
+        // the runtime the exporter thread will drive
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let (recorder, server) = {
+            let _runtime = runtime.enter();
+            prometheus_builder(config)?
+                .with_http_listener(config.listen_address)
+                .build()
+                .map_err(|e| Error::config(format!("Failed to start metrics exporter: {}", e)))?
+        };
+        let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+
+        let thread = std::thread::Builder::new()
+            .name("metrics-exporter".to_string())
+            .spawn(move || {
+                runtime.block_on(async move {
+                    tokio::select! {
+                        result = server => {
+                            if let Err(e) = result {
+                                warn!(error = ?e, "Metrics exporter stopped");
+                            }
+                        }
+                        // Dropping the sender also resolves the receiver, so
+                        // only an explicit shutdown stops the thread
+                        Ok(()) = shutdown_rx => {}
+                    }
+                })
+            })?;
+
+        let exporter = Self {
+            local_addr: config.listen_address,
+            handle: recorder.handle(),
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        };
+        Ok((recorder, exporter))
+    }
+
+    /// Address the exporter is listening on, as configured
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Render the current metrics in the Prometheus text format
+    pub fn render(&self) -> String {
+        self.handle.render()
+    }
+
+    /// Stop serving metrics
+    ///
+    /// The recorder stays installed, so metrics are still collected.
+    pub fn shutdown(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        info!(address = %self.local_addr, "Prometheus exporter stopped");
+    }
+}
+
+/// Prometheus builder with the configured histogram buckets
+///
+/// Duration buckets apply to histograms whose names end in `_ms` and size
+/// buckets to those ending in `_size` or `_bytes`.
+fn prometheus_builder(config: &MetricsExporterConfig) -> Result<PrometheusBuilder> {
+    config.validate()?;
+
+    let durations = Matcher::Suffix("_ms".to_string());
+    let mut builder = PrometheusBuilder::new()
+        .set_buckets_for_metric(durations, &config.duration_buckets)
+        .map_err(invalid_buckets)?;
+    for suffix in SIZE_SUFFIXES {
+        builder = builder
+            .set_buckets_for_metric(Matcher::Suffix(suffix.to_string()), &config.size_buckets)
+            .map_err(invalid_buckets)?;
+    }
+    Ok(builder)
+}
+
+fn invalid_buckets(error: impl fmt::Display) -> Error {
+    Error::config(format!("Invalid histogram buckets: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    /// A loopback address with a port that is free at the time of the call
+    fn free_address() -> SocketAddr {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    async fn scrape(address: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request =
+            format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_serves_metrics_with_buckets() {
+        let config = MetricsExporterConfig::new(free_address())
+            .duration_buckets(vec![10.0, 100.0])
+            .size_buckets(vec![5.0, 50.0]);
+        let (recorder, exporter) = MetricsExporter::serve(&config).unwrap();
+        metrics::with_local_recorder(&recorder, || {
+            let metrics = crate::metrics::MetricsRecorder::new(true);
+            metrics.record_processed(42, true);
+            metrics.record_batch_processed(20, 5);
+            metrics::histogram!("payload_bytes").record(30.0);
+        });
+
+        let response = scrape(exporter.local_addr(), METRICS_PATH).await;
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("records_processed_success 1"));
+        assert!(response.contains("record_processing_duration_ms_bucket{le=\"10\"} 0"));
+        assert!(response.contains("record_processing_duration_ms_bucket{le=\"100\"} 1"));
+        assert!(response.contains("batch_size_bucket{le=\"5\"} 0"));
+        assert!(response.contains("batch_size_bucket{le=\"50\"} 1"));
+        assert!(response.contains("payload_bytes_bucket{le=\"50\"} 1"));
+
+        let address = exporter.local_addr();
+        exporter.shutdown();
+        assert!(TcpStream::connect(address).await.is_err());
+    }
+
+    #[test]
+    fn test_serve_fails_when_address_is_taken() {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let config = MetricsExporterConfig::new(listener.local_addr().unwrap());
+
+        let err = match MetricsExporter::serve(&config) {
+            Ok(_) => panic!("expected the bind to fail"),
+            Err(e) => e,
+        };
+        assert!(err.to_string().contains("Failed to start metrics exporter"), "{}", err);
+    }
+}
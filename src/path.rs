@@ -0,0 +1,476 @@
+//! Field paths into nested record values
+//!
+//! A [`FieldPath`] addresses values inside `Record.value` using dotted keys,
+//! array indices and wildcards:
+//!
+//! - `customer.address.city` - nested object keys
+//! - `items[0].price` - an array element
+//! - `items[*].price` - every element of an array (or every value of an object)
+//! - `attributes["content-type"]` - a key containing `.`, `[` or other special characters
+
+use crate::{Error, Result};
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// One step of a [`FieldPath`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+/// Parsed path to one or more values inside a JSON value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPath {
+    raw: String,
+    segments: Vec<Segment>,
+}
+
+/// Value found, or not found, at a concrete path
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathMatch<'a> {
+    /// Concrete path with wildcards replaced by indices or keys
+    ///
+    /// For a missing value this is the path up to the first segment that
+    /// could not be resolved.
+    pub path: String,
+    /// The value, or `None` if the path does not exist
+    pub value: Option<&'a Value>,
+}
+
+impl FieldPath {
+    /// Parse a path, rejecting malformed syntax
+    pub fn parse(path: &str) -> Result<Self> {
+        let segments = parse_segments(path).map_err(|message| {
+            Error::config(format!("Invalid field path '{}': {}", path, message))
+        })?;
+        Ok(Self {
+            raw: path.to_string(),
+            segments,
+        })
+    }
+
+    /// Path to a single top-level key, with no path syntax
+    pub fn key(key: impl Into<String>) -> Self {
+        let key = key.into();
+        Self {
+            raw: key.clone(),
+            segments: vec![Segment::Key(key)],
+        }
+    }
+
+    /// The path as written
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Check whether the path contains a wildcard
+    pub fn has_wildcard(&self) -> bool {
+        self.segments.contains(&Segment::Wildcard)
+    }
+
+    /// Resolve the path, reporting every match and every missing branch
+    ///
+    /// A wildcard over an empty array or object yields no matches.
+    pub fn resolve<'a>(&self, value: &'a Value) -> Vec<PathMatch<'a>> {
+        let mut matches = Vec::new();
+        resolve_into(&self.segments, value, String::new(), &mut matches);
+        matches
+    }
+
+    /// Every value the path resolves to
+    pub fn find<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        self.resolve(value).into_iter().filter_map(|m| m.value).collect()
+    }
+
+    /// First value the path resolves to
+    pub fn get<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.resolve(value).into_iter().find_map(|m| m.value)
+    }
+
+    /// Call `f` with every existing value the path resolves to
+    pub fn for_each_mut(&self, value: &mut Value, mut f: impl FnMut(&mut Value)) {
+        for_each_mut(&self.segments, value, &mut f);
+    }
+
+    /// Call `f` with the concrete path and value of every existing match
+    ///
+    /// Concrete paths are written the same way as [`PathMatch::path`].
+    pub fn for_each_path_mut(&self, value: &mut Value, mut f: impl FnMut(&str, &mut Value)) {
+        for_each_path_mut(&self.segments, value, String::new(), &mut f);
+    }
+
+    /// Set every value the path resolves to, creating missing object keys
+    ///
+    /// Intermediate objects are created for missing keys; missing array
+    /// elements and non-object parents are left alone. Returns the number of
+    /// values set.
+    pub fn set(&self, value: &mut Value, new_value: Value) -> usize {
+        set(&self.segments, value, &new_value)
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl std::str::FromStr for FieldPath {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+/// Parse a path, treating malformed syntax as a single literal key
+impl From<&str> for FieldPath {
+    fn from(path: &str) -> Self {
+        Self::parse(path).unwrap_or_else(|_| Self::key(path))
+    }
+}
+
+/// Parse a path, treating malformed syntax as a single literal key
+impl From<String> for FieldPath {
+    fn from(path: String) -> Self {
+        Self::from(path.as_str())
+    }
+}
+
+impl From<&String> for FieldPath {
+    fn from(path: &String) -> Self {
+        Self::from(path.as_str())
+    }
+}
+
+fn parse_segments(path: &str) -> std::result::Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut chars = path.chars().peekable();
+    let mut expect_key = true;
+
+    if path.is_empty() {
+        return Err("path is empty".to_string());
+    }
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            '[' => {
+                chars.next();
+                segments.push(parse_bracket(&mut chars)?);
+                expect_key = false;
+            }
+            '.' if !expect_key => {
+                chars.next();
+                expect_key = true;
+                if chars.peek().is_none() {
+                    return Err("path ends with '.'".to_string());
+                }
+            }
+            '.' => return Err("empty key".to_string()),
+            _ if expect_key => {
+                let mut key = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == '.' || c == '[' {
+                        break;
+                    }
+                    if c == ']' {
+                        return Err("unexpected ']'".to_string());
+                    }
+                    key.push(c);
+                    chars.next();
+                }
+                segments.push(if key == "*" {
+                    Segment::Wildcard
+                } else {
+                    Segment::Key(key)
+                });
+                expect_key = false;
+            }
+            _ => return Err(format!("expected '.' or '[' before '{}'", c)),
+        }
+    }
+
+    Ok(segments)
+}
+
+fn parse_bracket(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+) -> std::result::Result<Segment, String> {
+    let segment = match chars.peek() {
+        Some(&quote @ ('"' | '\'')) => {
+            chars.next();
+            let mut key = String::new();
+            loop {
+                match chars.next() {
+                    Some('\\') => key.push(chars.next().ok_or("unterminated quoted key")?),
+                    Some(c) if c == quote => break,
+                    Some(c) => key.push(c),
+                    None => return Err("unterminated quoted key".to_string()),
+                }
+            }
+            Segment::Key(key)
+        }
+        Some('*') => {
+            chars.next();
+            Segment::Wildcard
+        }
+        _ => {
+            let mut digits = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == ']' {
+                    break;
+                }
+                digits.push(c);
+                chars.next();
+            }
+            let index = digits
+                .parse()
+                .map_err(|_| format!("'{}' is not an array index", digits))?;
+            Segment::Index(index)
+        }
+    };
+
+    match chars.next() {
+        Some(']') => Ok(segment),
+        _ => Err("expected ']'".to_string()),
+    }
+}
+
+/// Append a segment to a concrete path
+fn push_segment(path: &str, segment: &Segment) -> String {
+    match segment {
+        Segment::Index(i) => format!("{}[{}]", path, i),
+        Segment::Key(key) => push_key(path, key),
+        Segment::Wildcard => format!("{}[*]", path),
+    }
+}
+
+fn push_key(path: &str, key: &str) -> String {
+    let plain = !key.is_empty() && key != "*" && !key.contains(['.', '[', ']', '"', '\'']);
+    match (plain, path.is_empty()) {
+        (true, true) => key.to_string(),
+        (true, false) => format!("{}.{}", path, key),
+        (false, _) => format!("{}[\"{}\"]", path, key.replace('\\', "\\\\").replace('"', "\\\"")),
+    }
+}
+
+fn resolve_into<'a>(
+    segments: &[Segment],
+    value: &'a Value,
+    path: String,
+    matches: &mut Vec<PathMatch<'a>>,
+) {
+    let Some((segment, rest)) = segments.split_first() else {
+        matches.push(PathMatch {
+            path,
+            value: Some(value),
+        });
+        return;
+    };
+
+    match (segment, value) {
+        (Segment::Key(key), Value::Object(object)) if object.contains_key(key) => {
+            resolve_into(rest, &object[key], push_key(&path, key), matches)
+        }
+        (Segment::Index(i), Value::Array(items)) if *i < items.len() => {
+            resolve_into(rest, &items[*i], format!("{}[{}]", path, i), matches)
+        }
+        (Segment::Wildcard, Value::Array(items)) => {
+            for (i, item) in items.iter().enumerate() {
+                resolve_into(rest, item, format!("{}[{}]", path, i), matches);
+            }
+        }
+        (Segment::Wildcard, Value::Object(object)) => {
+            for (key, item) in object {
+                resolve_into(rest, item, push_key(&path, key), matches);
+            }
+        }
+        _ => matches.push(PathMatch {
+            path: push_segment(&path, segment),
+            value: None,
+        }),
+    }
+}
+
+fn for_each_mut(segments: &[Segment], value: &mut Value, f: &mut impl FnMut(&mut Value)) {
+    let Some((segment, rest)) = segments.split_first() else {
+        f(value);
+        return;
+    };
+
+    match (segment, value) {
+        (Segment::Key(key), Value::Object(object)) => {
+            if let Some(child) = object.get_mut(key) {
+                for_each_mut(rest, child, f);
+            }
+        }
+        (Segment::Index(i), Value::Array(items)) => {
+            if let Some(child) = items.get_mut(*i) {
+                for_each_mut(rest, child, f);
+            }
+        }
+        (Segment::Wildcard, Value::Array(items)) => {
+            items.iter_mut().for_each(|child| for_each_mut(rest, child, f));
+        }
+        (Segment::Wildcard, Value::Object(object)) => {
+            object.values_mut().for_each(|child| for_each_mut(rest, child, f));
+        }
+        _ => {}
+    }
+}
+
+fn for_each_path_mut(
+    segments: &[Segment],
+    value: &mut Value,
+    path: String,
+    f: &mut impl FnMut(&str, &mut Value),
+) {
+    let Some((segment, rest)) = segments.split_first() else {
+        f(&path, value);
+        return;
+    };
+
+    match (segment, value) {
+        (Segment::Key(key), Value::Object(object)) => {
+            if let Some(child) = object.get_mut(key) {
+                for_each_path_mut(rest, child, push_key(&path, key), f);
+            }
+        }
+        (Segment::Index(i), Value::Array(items)) => {
+            if let Some(child) = items.get_mut(*i) {
+                for_each_path_mut(rest, child, format!("{}[{}]", path, i), f);
+            }
+        }
+        (Segment::Wildcard, Value::Array(items)) => {
+            for (i, child) in items.iter_mut().enumerate() {
+                for_each_path_mut(rest, child, format!("{}[{}]", path, i), f);
+            }
+        }
+        (Segment::Wildcard, Value::Object(object)) => {
+            for (key, child) in object.iter_mut() {
+                for_each_path_mut(rest, child, push_key(&path, key), f);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn set(segments: &[Segment], value: &mut Value, new_value: &Value) -> usize {
+    let Some((segment, rest)) = segments.split_first() else {
+        *value = new_value.clone();
+        return 1;
+    };
+
+    match (segment, value) {
+        (Segment::Key(key), Value::Object(object)) => {
+            let child = object.entry(key.clone()).or_insert_with(|| match rest.first() {
+                None => Value::Null,
+                Some(_) => Value::Object(Map::new()),
+            });
+            set(rest, child, new_value)
+        }
+        (Segment::Index(i), Value::Array(items)) => match items.get_mut(*i) {
+            Some(child) => set(rest, child, new_value),
+            None => 0,
+        },
+        (Segment::Wildcard, Value::Array(items)) => {
+            items.iter_mut().map(|child| set(rest, child, new_value)).sum()
+        }
+        (Segment::Wildcard, Value::Object(object)) => {
+            object.values_mut().map(|child| set(rest, child, new_value)).sum()
+        }
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn order() -> Value {
+        json!({
+            "customer": {"name": "Ann", "address": {"city": "Oslo"}},
+            "items": [{"sku": "a", "price": 1.5}, {"sku": "b"}],
+            "attributes": {"content-type": "json", "a.b": 1}
+        })
+    }
+
+    #[test]
+    fn test_parse_paths() {
+        assert_eq!(FieldPath::parse("name").unwrap().segments, [Segment::Key("name".into())]);
+        assert_eq!(
+            FieldPath::parse("items[2].price").unwrap().segments,
+            [
+                Segment::Key("items".into()),
+                Segment::Index(2),
+                Segment::Key("price".into())
+            ]
+        );
+        assert!(FieldPath::parse("items[*].price").unwrap().has_wildcard());
+        assert!(FieldPath::parse("a.*.b").unwrap().has_wildcard());
+        assert_eq!(
+            FieldPath::parse(r#"attributes["a.b"]"#).unwrap().segments,
+            [Segment::Key("attributes".into()), Segment::Key("a.b".into())]
+        );
+
+        for invalid in ["", "a..b", "a.", ".a", "a[x]", "a[1", "a]", "a[1]b", "a[\"b]"] {
+            assert!(FieldPath::parse(invalid).is_err(), "{} should be rejected", invalid);
+        }
+        assert_eq!(FieldPath::from("a..b"), FieldPath::key("a..b"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let value = order();
+
+        let path = FieldPath::parse("customer.address.city").unwrap();
+        assert_eq!(path.get(&value), Some(&json!("Oslo")));
+
+        let path = FieldPath::parse("items[*].price").unwrap();
+        let matches = path.resolve(&value);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].path, "items[0].price");
+        assert_eq!(matches[0].value, Some(&json!(1.5)));
+        assert_eq!(matches[1].path, "items[1].price");
+        assert_eq!(matches[1].value, None);
+
+        let path = FieldPath::parse("items[5].sku").unwrap();
+        assert_eq!(path.resolve(&value)[0].path, "items[5]");
+
+        let path = FieldPath::parse(r#"attributes["a.b"]"#).unwrap();
+        assert_eq!(path.resolve(&value)[0].path, r#"attributes["a.b"]"#);
+        assert_eq!(FieldPath::parse("attributes.*").unwrap().find(&value).len(), 2);
+    }
+
+    #[test]
+    fn test_mutation() {
+        let mut value = order();
+
+        FieldPath::parse("items[*].sku")
+            .unwrap()
+            .for_each_mut(&mut value, |v| *v = json!(v.as_str().unwrap().to_uppercase()));
+        assert_eq!(value["items"][1]["sku"], json!("B"));
+
+        let mut paths = Vec::new();
+        FieldPath::parse("attributes.*").unwrap().for_each_path_mut(&mut value, |path, v| {
+            paths.push(path.to_string());
+            *v = json!(null);
+        });
+        assert_eq!(paths, ["attributes[\"a.b\"]", "attributes.content-type"]);
+        assert_eq!(value["attributes"]["a.b"], json!(null));
+
+        let set = FieldPath::parse("items[*].currency").unwrap().set(&mut value, json!("EUR"));
+        assert_eq!(set, 2);
+        assert_eq!(value["items"][0]["currency"], json!("EUR"));
+
+        let set = FieldPath::parse("meta.source.system").unwrap().set(&mut value, json!("crm"));
+        assert_eq!(set, 1);
+        assert_eq!(value["meta"]["source"]["system"], json!("crm"));
+
+        assert_eq!(FieldPath::parse("items[9].x").unwrap().set(&mut value, json!(1)), 0);
+        assert_eq!(FieldPath::parse("customer.name.x").unwrap().set(&mut value, json!(1)), 0);
+    }
+}
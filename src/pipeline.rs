@@ -1,15 +1,55 @@
 //! Data processing pipeline module
 
-use crate::{processor::Transform, record::Record, storage::Storage, validation::Validator, Result};
+use crate::{
+    config::RetryConfig,
+    dead_letter::{DeadLetter, DeadLetterQueue},
+    deadline::{with_timeout, Deadline},
+    metrics::MetricsRecorder,
+    processor::{Transform, TransformOutcome},
+    record::Record,
+    retry::{RetryOutcome, RetryPolicy},
+    sink::Sink,
+    source::Source,
+    storage::{MeteredStorage, Storage},
+    stream::{spawn_ordered, PipelineResult, ResultStream, StreamOptions},
+    validation::Validator,
+    Error, Result,
+};
 use async_trait::async_trait;
-use std::sync::Arc;
-use tracing::{debug, info};
+use futures::Stream;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, info, warn};
+
+mod definition;
+
+pub use definition::{
+    parse_args, DefinitionFormat, PipelineDefinition, PipelineRegistry, RuleFactory,
+    StorageFactory, TransformFactory,
+};
+
+/// Default timeout for a single stage attempt
+const DEFAULT_STAGE_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// A data processing pipeline that chains operations
 #[derive(Debug)]
 pub struct Pipeline {
     name: String,
-    stages: Vec<Arc<dyn PipelineStage>>,
+    stages: Vec<StageEntry>,
+    retry_policy: RetryPolicy,
+    stage_timeout: Option<Duration>,
+    record_budget: Option<Duration>,
+    metrics: MetricsRecorder,
+    dead_letters: Option<DeadLetterQueue>,
+}
+
+/// A stage together with its timeout override
+#[derive(Debug)]
+struct StageEntry {
+    stage: Arc<dyn PipelineStage>,
+    timeout: Option<Duration>,
 }
 
 /// Trait for pipeline stages
@@ -18,6 +58,32 @@ pub trait PipelineStage: Send + Sync + std::fmt::Debug {
     /// Execute the stage on a record
     async fn execute(&self, record: Record) -> Result<Record>;
     
+    /// Apply the stage, allowing it to keep, drop or split the record
+    ///
+    /// The default implementation keeps the record returned by
+    /// [`execute`](Self::execute).
+    async fn apply(&self, record: Record) -> Result<TransformOutcome> {
+        self.execute(record).await.map(TransformOutcome::Keep)
+    }
+    
+    /// Flush any output buffered by the stage
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Flush and finalise any output written by the stage
+    async fn close(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether a failed attempt may be retried
+    ///
+    /// Stages with side effects that are not idempotent, such as sinks,
+    /// return `false` so a failure is never repeated.
+    fn retryable(&self) -> bool {
+        true
+    }
+
     /// Name of the stage
     fn name(&self) -> &str;
 }
@@ -25,19 +91,84 @@ pub trait PipelineStage: Send + Sync + std::fmt::Debug {
 impl Pipeline {
     /// Create a new pipeline
     pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
         Self {
-            name: name.into(),
             stages: Vec::new(),
+            retry_policy: RetryPolicy::default(),
+            stage_timeout: Some(DEFAULT_STAGE_TIMEOUT),
+            record_budget: None,
+            metrics: MetricsRecorder::default().with_label("pipeline", name.clone()),
+            dead_letters: None,
+            name,
         }
     }
 
+    /// Enable or disable metrics for this pipeline
+    ///
+    /// Metrics are labelled with the pipeline name and, where applicable,
+    /// the stage name. Pipelines start out enabled according to
+    /// [`MetricsRecorder::set_default_enabled`].
+    pub fn set_metrics_enabled(&mut self, enabled: bool) {
+        self.metrics = MetricsRecorder::new(enabled).with_label("pipeline", self.name.clone());
+    }
+
+    /// Recorder for metrics emitted from inside a stage
+    fn stage_metrics(&self, stage: &str) -> MetricsRecorder {
+        self.metrics.clone().with_label("stage", stage)
+    }
+
+    /// Set the retry policy applied to each stage
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    /// Set the default timeout for each stage attempt, or `None` to disable it
+    pub fn set_stage_timeout(&mut self, timeout: Option<Duration>) {
+        self.stage_timeout = timeout;
+    }
+
+    /// Set the total time budget for a record across all stages
+    ///
+    /// Each stage attempt is bounded by whatever remains of the budget, and
+    /// a record whose budget runs out fails with [`Error::Timeout`].
+    pub fn set_record_budget(&mut self, budget: Option<Duration>) {
+        self.record_budget = budget;
+    }
+
+    /// Send records that fail a stage to a dead-letter queue
+    ///
+    /// Each entry names the failing stage and holds its failed attempts.
+    pub fn set_dead_letter_queue(&mut self, queue: Option<DeadLetterQueue>) {
+        self.dead_letters = queue;
+    }
+
+    /// Get the dead-letter queue, if one is configured
+    pub fn dead_letter_queue(&self) -> Option<&DeadLetterQueue> {
+        self.dead_letters.as_ref()
+    }
+
     /// Add a stage to the pipeline
     pub fn add_stage(&mut self, stage: Arc<dyn PipelineStage>) {
-        self.stages.push(stage);
+        self.stages.push(StageEntry {
+            stage,
+            timeout: None,
+        });
+    }
+
+    /// Add a stage with a timeout that overrides the pipeline default
+    pub fn add_stage_with_timeout(&mut self, stage: Arc<dyn PipelineStage>, timeout: Duration) {
+        self.stages.push(StageEntry {
+            stage,
+            timeout: Some(timeout),
+        });
     }
 
     /// Execute the pipeline on a record
-    pub async fn execute(&self, mut record: Record) -> Result<Record> {
+    ///
+    /// Returns [`TransformOutcome::Drop`] if a stage filtered the record out
+    /// and [`TransformOutcome::Split`] if a stage split it, in which case each
+    /// resulting record runs through the remaining stages.
+    pub async fn execute(&self, record: Record) -> Result<TransformOutcome> {
         info!(
             pipeline = %self.name,
             record_id = %record.id,
@@ -45,7 +176,30 @@ impl Pipeline {
             "Executing pipeline"
         );
 
-        for (idx, stage) in self.stages.iter().enumerate() {
+        let start = Instant::now();
+        let result = self.execute_stages(record).await;
+        let outcome = match &result {
+            Ok(TransformOutcome::Drop) => "filtered",
+            Ok(_) => "success",
+            Err(e) => {
+                self.metrics.record_error(e.code());
+                "failed"
+            }
+        };
+        self.metrics
+            .record_pipeline(start.elapsed().as_millis() as u64, outcome);
+
+        result
+    }
+
+    /// Run a record through every stage in order
+    async fn execute_stages(&self, record: Record) -> Result<TransformOutcome> {
+        let deadline = self.record_budget.map(Deadline::after);
+        let mut batch = vec![record];
+        let mut split = false;
+
+        for (idx, entry) in self.stages.iter().enumerate() {
+            let stage = &entry.stage;
             debug!(
                 pipeline = %self.name,
                 stage = stage.name(),
@@ -53,10 +207,242 @@ impl Pipeline {
                 "Executing stage"
             );
 
-            record = stage.execute(record).await?;
+            if deadline.is_some_and(|d| d.is_expired()) {
+                let e = Error::timeout(format!(
+                    "Record deadline exceeded before stage '{}'",
+                    stage.name()
+                ));
+                for record in batch {
+                    self.dead_letter(DeadLetter::new(record, stage.name(), &e, Vec::new())).await;
+                }
+                return Err(e);
+            }
+
+            let mut next = Vec::with_capacity(batch.len());
+            let mut pending = batch.into_iter();
+            while let Some(record) = pending.next() {
+                let started = Instant::now();
+                let RetryOutcome {
+                    mut record,
+                    failures,
+                    result,
+                    ..
+                } = self.execute_stage(entry, record, deadline).await;
+                let duration_ms = started.elapsed().as_millis() as u64;
+
+                match result {
+                    Ok(TransformOutcome::Keep(record)) => {
+                        self.metrics.record_stage(stage.name(), duration_ms, "success");
+                        next.push(record);
+                    }
+                    Ok(TransformOutcome::Split(records)) if !records.is_empty() => {
+                        self.metrics.record_stage(stage.name(), duration_ms, "success");
+                        split = true;
+                        next.extend(records);
+                    }
+                    // A split into no records drops the record too
+                    Ok(TransformOutcome::Drop | TransformOutcome::Split(_)) => {
+                        self.metrics.record_stage(stage.name(), duration_ms, "filtered");
+                        self.metrics.record_filtered(stage.name());
+                    }
+                    Err(e) => {
+                        self.metrics.record_stage(stage.name(), duration_ms, "failed");
+                        if let Error::ValidationFailed(report) = &e {
+                            record.metadata.validation = Some(Box::new(report.clone()));
+                        }
+                        self.dead_letter(DeadLetter::new(record, stage.name(), &e, failures)).await;
+                        // Split siblings are not output either, so dead-letter them too
+                        for sibling in next.into_iter().chain(pending) {
+                            let letter = DeadLetter::new(sibling, stage.name(), &e, Vec::new());
+                            self.dead_letter(letter).await;
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+
+            batch = next;
+            if batch.is_empty() {
+                info!(pipeline = %self.name, stage = stage.name(), "Record filtered out");
+                return Ok(TransformOutcome::Drop);
+            }
         }
 
-        Ok(record)
+        if split {
+            Ok(TransformOutcome::Split(batch))
+        } else {
+            Ok(TransformOutcome::Keep(batch.remove(0)))
+        }
+    }
+
+    /// Flush buffered output of every stage
+    pub async fn flush(&self) -> Result<()> {
+        for entry in &self.stages {
+            entry.stage.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Flush and finalise the output of every stage
+    ///
+    /// Call this on shutdown so sinks finalise their files. Every stage is
+    /// closed even if an earlier one fails; the first error is returned.
+    pub async fn close(&self) -> Result<()> {
+        let mut first_error = None;
+        for entry in &self.stages {
+            if let Err(e) = entry.stage.close().await {
+                warn!(
+                    pipeline = %self.name,
+                    stage = entry.stage.name(),
+                    error = %e,
+                    "Failed to close stage"
+                );
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Execute the pipeline on a stream of records, emitting results as a stream
+    ///
+    /// Up to `options.concurrency` records run at once and results are
+    /// buffered in a channel of `options.buffer_size`; when the consumer falls
+    /// behind, no further records are pulled from the input. Results are
+    /// emitted in input order.
+    pub fn execute_stream<S>(
+        self: &Arc<Self>,
+        records: S,
+        options: StreamOptions,
+    ) -> ResultStream<PipelineResult>
+    where
+        S: Stream<Item = Record> + Send + 'static,
+    {
+        let pipeline = Arc::clone(self);
+        spawn_ordered(
+            records,
+            options,
+            move |record| {
+                let pipeline = Arc::clone(&pipeline);
+                async move {
+                    PipelineResult {
+                        record_id: record.id,
+                        outcome: pipeline.execute(record).await,
+                    }
+                }
+            },
+            |record| {
+                let record_id = record.id;
+                move |e| PipelineResult {
+                    record_id,
+                    outcome: Err(e),
+                }
+            },
+        )
+    }
+
+    /// Execute the pipeline on every record read from a source
+    ///
+    /// Entries the source fails to read are passed through as errors.
+    pub fn execute_source<S>(
+        self: &Arc<Self>,
+        source: S,
+        options: StreamOptions,
+    ) -> ResultStream<Result<PipelineResult>>
+    where
+        S: Source + 'static,
+    {
+        let pipeline = Arc::clone(self);
+        spawn_ordered(
+            source.into_stream(),
+            options,
+            move |read| {
+                let pipeline = Arc::clone(&pipeline);
+                async move {
+                    let record = read?;
+                    Ok(PipelineResult {
+                        record_id: record.id,
+                        outcome: pipeline.execute(record).await,
+                    })
+                }
+            },
+            |read| {
+                let record_id = read.as_ref().map(|record| record.id).ok();
+                move |e| match record_id {
+                    Some(record_id) => Ok(PipelineResult {
+                        record_id,
+                        outcome: Err(e),
+                    }),
+                    None => Err(e),
+                }
+            },
+        )
+    }
+
+    /// Push an entry to the dead-letter queue, if one is configured
+    async fn dead_letter(&self, letter: DeadLetter) {
+        let Some(queue) = &self.dead_letters else {
+            return;
+        };
+
+        let (record_id, stage) = (letter.id(), letter.stage.clone());
+        match queue.push(letter).await {
+            Ok(()) => self.metrics.record_dead_lettered(&stage),
+            Err(e) => {
+                error!(
+                    pipeline = %self.name,
+                    record_id = %record_id,
+                    error = %e,
+                    "Failed to dead-letter record"
+                );
+                self.metrics.record_error(e.code());
+            }
+        }
+    }
+
+    /// Run a single stage on a record with retries and timeouts
+    ///
+    /// The outcome holds the record with its failed attempts recorded.
+    async fn execute_stage(
+        &self,
+        entry: &StageEntry,
+        record: Record,
+        deadline: Option<Deadline>,
+    ) -> RetryOutcome<TransformOutcome> {
+        let stage = &entry.stage;
+        let timeout = entry.timeout.or(self.stage_timeout);
+        let no_retry;
+        let policy = if stage.retryable() {
+            &self.retry_policy
+        } else {
+            no_retry = RetryPolicy::no_retry();
+            &no_retry
+        };
+        let outcome = policy
+            .execute_within(stage.name(), record, deadline, |r| {
+                let stage = Arc::clone(stage);
+                let timeout = match deadline {
+                    Some(deadline) => Some(deadline.bound(timeout)),
+                    None => timeout,
+                };
+                async move {
+                    match timeout {
+                        Some(timeout) => with_timeout(stage.name(), timeout, stage.apply(r)).await,
+                        None => stage.apply(r).await,
+                    }
+                }
+            })
+            .await;
+
+        if outcome.attempts > 1 {
+            debug!(
+                pipeline = %self.name,
+                stage = stage.name(),
+                attempts = outcome.attempts,
+                "Stage required retries"
+            );
+        }
+
+        outcome
     }
 
     /// Get pipeline name
@@ -74,6 +460,28 @@ impl Pipeline {
 #[derive(Debug)]
 pub struct PipelineBuilder {
     pipeline: Pipeline,
+    stages: Vec<PendingStage>,
+}
+
+/// A stage added to a [`PipelineBuilder`] and its timeout override
+///
+/// Validation and storage stages are created in
+/// [`build`](PipelineBuilder::build), once the pipeline's metrics settings
+/// are final, so their metrics carry the pipeline and stage labels.
+#[derive(Debug)]
+struct PendingStage {
+    kind: PendingKind,
+    timeout: Option<Duration>,
+}
+
+#[derive(Debug)]
+enum PendingKind {
+    Validate {
+        validator: Arc<Validator>,
+        attach_report: bool,
+    },
+    Store(Arc<dyn Storage>),
+    Stage(Arc<dyn PipelineStage>),
 }
 
 impl PipelineBuilder {
@@ -81,30 +489,121 @@ impl PipelineBuilder {
     pub fn new(name: impl Into<String>) -> Self {
         Self {
             pipeline: Pipeline::new(name),
+            stages: Vec::new(),
         }
     }
 
-    /// Add a validation stage
-    pub fn validate(mut self, validator: Arc<Validator>) -> Self {
-        self.pipeline.add_stage(Arc::new(ValidationStage::new(validator)));
+    fn push(mut self, kind: PendingKind) -> Self {
+        self.stages.push(PendingStage {
+            kind,
+            timeout: None,
+        });
         self
     }
 
+    /// Add a validation stage
+    pub fn validate(self, validator: Arc<Validator>) -> Self {
+        self.push(PendingKind::Validate {
+            validator,
+            attach_report: false,
+        })
+    }
+
+    /// Add a validation stage that runs every rule and attaches the report
+    ///
+    /// The [`ValidationReport`](crate::validation::ValidationReport) is stored
+    /// in `record.metadata.validation`. Records with errors fail with
+    /// [`Error::ValidationFailed`](crate::Error::ValidationFailed); warnings pass.
+    /// Failed records keep the report too, so it reaches the dead-letter queue.
+    pub fn validate_with_report(self, validator: Arc<Validator>) -> Self {
+        self.push(PendingKind::Validate {
+            validator,
+            attach_report: true,
+        })
+    }
+
     /// Add a transformation stage
-    pub fn transform(mut self, transform: Arc<dyn Transform>) -> Self {
-        self.pipeline.add_stage(Arc::new(TransformStage::new(transform)));
-        self
+    pub fn transform(self, transform: Arc<dyn Transform>) -> Self {
+        self.push(PendingKind::Stage(Arc::new(TransformStage::new(transform))))
     }
 
     /// Add a storage stage
-    pub fn store(mut self, storage: Arc<dyn Storage>) -> Self {
-        self.pipeline.add_stage(Arc::new(StorageStage::new(storage)));
+    ///
+    /// Storage calls are recorded through [`MeteredStorage`].
+    pub fn store(self, storage: Arc<dyn Storage>) -> Self {
+        self.push(PendingKind::Store(storage))
+    }
+
+    /// Add a sink stage that writes each record and passes it on
+    pub fn sink(self, sink: Arc<dyn Sink>) -> Self {
+        self.push(PendingKind::Stage(Arc::new(SinkStage::new(sink))))
+    }
+
+    /// Override the timeout of the most recently added stage
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        if let Some(entry) = self.stages.last_mut() {
+            entry.timeout = Some(timeout);
+        }
+        self
+    }
+
+    /// Set the default timeout for each stage attempt
+    pub fn stage_timeout(mut self, timeout: Duration) -> Self {
+        self.pipeline.set_stage_timeout(Some(timeout));
+        self
+    }
+
+    /// Set the total time budget for a record across all stages
+    pub fn record_deadline(mut self, budget: Duration) -> Self {
+        self.pipeline.set_record_budget(Some(budget));
+        self
+    }
+
+    /// Set the retry configuration applied to each stage
+    pub fn retry(mut self, config: RetryConfig) -> Self {
+        self.pipeline.set_retry_policy(RetryPolicy::new(config));
+        self
+    }
+
+    /// Disable retries for all stages
+    pub fn no_retry(mut self) -> Self {
+        self.pipeline.set_retry_policy(RetryPolicy::no_retry());
+        self
+    }
+
+    /// Send records that fail a stage to a dead-letter queue
+    pub fn dead_letter_queue(mut self, queue: DeadLetterQueue) -> Self {
+        self.pipeline.set_dead_letter_queue(Some(queue));
+        self
+    }
+
+    /// Enable or disable metrics
+    pub fn metrics(mut self, enabled: bool) -> Self {
+        self.pipeline.set_metrics_enabled(enabled);
         self
     }
 
     /// Build the pipeline
     pub fn build(self) -> Pipeline {
-        self.pipeline
+        let mut pipeline = self.pipeline;
+        for PendingStage { kind, timeout } in self.stages {
+            let stage: Arc<dyn PipelineStage> = match kind {
+                PendingKind::Validate {
+                    validator,
+                    attach_report,
+                } => {
+                    let metrics = pipeline.stage_metrics(ValidationStage::NAME);
+                    Arc::new(ValidationStage::new(validator, metrics, attach_report))
+                }
+                PendingKind::Store(storage) => {
+                    let metrics = pipeline.stage_metrics(StorageStage::NAME);
+                    Arc::new(StorageStage::new(storage, metrics))
+                }
+                PendingKind::Stage(stage) => stage,
+            };
+            pipeline.stages.push(StageEntry { stage, timeout });
+        }
+        pipeline
     }
 }
 
@@ -112,23 +611,38 @@ impl PipelineBuilder {
 #[derive(Debug)]
 struct ValidationStage {
     validator: Arc<Validator>,
+    metrics: MetricsRecorder,
+    attach_report: bool,
 }
 
 impl ValidationStage {
-    fn new(validator: Arc<Validator>) -> Self {
-        Self { validator }
+    const NAME: &'static str = "validation";
+
+    fn new(validator: Arc<Validator>, metrics: MetricsRecorder, attach_report: bool) -> Self {
+        Self {
+            validator,
+            metrics,
+            attach_report,
+        }
     }
 }
 
 #[async_trait]
 impl PipelineStage for ValidationStage {
-    async fn execute(&self, record: Record) -> Result<Record> {
-        self.validator.validate(&record).await?;
+    async fn execute(&self, mut record: Record) -> Result<Record> {
+        if !self.attach_report {
+            self.validator.validate_with(&record, &self.metrics).await?;
+            return Ok(record);
+        }
+
+        let report = self.validator.validate_all_with(&record, &self.metrics).await?;
+        let report = report.into_result()?;
+        record.metadata.validation = Some(Box::new(report));
         Ok(record)
     }
 
     fn name(&self) -> &str {
-        "validation"
+        Self::NAME
     }
 }
 
@@ -150,6 +664,10 @@ impl PipelineStage for TransformStage {
         self.transform.transform(record).await
     }
 
+    async fn apply(&self, record: Record) -> Result<TransformOutcome> {
+        self.transform.apply(record).await
+    }
+
     fn name(&self) -> &str {
         self.transform.name()
     }
@@ -158,12 +676,16 @@ impl PipelineStage for TransformStage {
 /// Storage pipeline stage
 #[derive(Debug)]
 struct StorageStage {
-    storage: Arc<dyn Storage>,
+    storage: MeteredStorage<Arc<dyn Storage>>,
 }
 
 impl StorageStage {
-    fn new(storage: Arc<dyn Storage>) -> Self {
-        Self { storage }
+    const NAME: &'static str = "storage";
+
+    fn new(storage: Arc<dyn Storage>, metrics: MetricsRecorder) -> Self {
+        Self {
+            storage: MeteredStorage::new(storage, metrics),
+        }
     }
 }
 
@@ -175,7 +697,44 @@ impl PipelineStage for StorageStage {
     }
 
     fn name(&self) -> &str {
-        "storage"
+        Self::NAME
+    }
+}
+
+/// Sink pipeline stage
+#[derive(Debug)]
+struct SinkStage {
+    sink: Arc<dyn Sink>,
+}
+
+impl SinkStage {
+    fn new(sink: Arc<dyn Sink>) -> Self {
+        Self { sink }
+    }
+}
+
+#[async_trait]
+impl PipelineStage for SinkStage {
+    async fn execute(&self, record: Record) -> Result<Record> {
+        self.sink.write(&record).await?;
+        Ok(record)
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.sink.flush().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.sink.close().await
+    }
+
+    // A retried write would duplicate whatever the failed attempt wrote
+    fn retryable(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &str {
+        self.sink.name()
     }
 }
 
@@ -209,7 +768,7 @@ mod tests {
             .build();
 
         let record = Record::new("test", json!({"name": "test"}));
-        let result = pipeline.execute(record).await.unwrap();
+        let result = pipeline.execute(record).await.unwrap().into_record().unwrap();
 
         assert_eq!(result.value["processed"], json!(true));
         assert_eq!(storage.count().await.unwrap(), 1);
@@ -229,4 +788,458 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_pipeline_attaches_validation_report() {
+        let mut validator = Validator::new();
+        validator.add_rule(Arc::new(RequiredFieldRule::new("name")));
+        validator.add_rule(Arc::new(RequiredFieldRule::new("id")));
+        validator.add_rule_with_severity(
+            Arc::new(RequiredFieldRule::new("email")),
+            crate::error::Severity::Warning,
+        );
+
+        let pipeline = PipelineBuilder::new("test_pipeline")
+            .validate_with_report(Arc::new(validator))
+            .no_retry()
+            .build();
+
+        let record = Record::new("test", json!({"name": "test", "id": 1}));
+        let result = pipeline.execute(record).await.unwrap().into_record().unwrap();
+        let report = result.metadata.validation.unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.warnings().count(), 1);
+
+        match pipeline.execute(Record::new("test", json!({}))).await {
+            Err(crate::Error::ValidationFailed(report)) => {
+                assert_eq!(report.errors().count(), 2);
+                assert_eq!(report.warnings().count(), 1);
+            }
+            other => panic!("expected validation failure, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_attaches_validation_report_on_failure() {
+        let queue = DeadLetterQueue::in_memory();
+        let mut validator = Validator::new();
+        validator.add_rule(Arc::new(RequiredFieldRule::new("name")));
+        validator.add_rule(Arc::new(RequiredFieldRule::new("id")));
+
+        let pipeline = PipelineBuilder::new("test_pipeline")
+            .validate_with_report(Arc::new(validator))
+            .dead_letter_queue(queue.clone())
+            .build();
+
+        let record = Record::new("test", json!({}));
+        let id = record.id;
+        pipeline.execute(record).await.unwrap_err();
+
+        let letter = queue.get(&id).await.unwrap().unwrap();
+        let report = letter.record.metadata.validation.unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(report.errors().count(), 2);
+    }
+
+    #[derive(Debug, Default)]
+    struct FlakyStage {
+        calls: std::sync::atomic::AtomicU32,
+    }
+
+    #[async_trait]
+    impl PipelineStage for FlakyStage {
+        async fn execute(&self, record: Record) -> Result<Record> {
+            if self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                return Err(crate::Error::timeout("transient"));
+            }
+            Ok(record)
+        }
+
+        fn name(&self) -> &str {
+            "flaky"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_retries_retryable_stage_errors() {
+        let stage = Arc::new(FlakyStage::default());
+        let mut pipeline = PipelineBuilder::new("retry_pipeline")
+            .retry(RetryConfig {
+                initial_backoff: std::time::Duration::from_millis(1),
+                ..RetryConfig::default()
+            })
+            .build();
+        pipeline.add_stage(stage.clone());
+
+        let result = pipeline
+            .execute(Record::new("test", json!({})))
+            .await
+            .unwrap()
+            .into_record()
+            .unwrap();
+        assert_eq!(result.metadata.failure_count, 1);
+        assert_eq!(stage.calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_dead_letters_stage_failures() {
+        let queue = DeadLetterQueue::in_memory();
+        let mut validator = Validator::new();
+        validator.add_rule(Arc::new(RequiredFieldRule::new("name")));
+
+        let mut pipeline = PipelineBuilder::new("dlq_pipeline")
+            .validate(Arc::new(validator))
+            .retry(RetryConfig {
+                max_attempts: 2,
+                initial_backoff: std::time::Duration::from_millis(1),
+                ..RetryConfig::default()
+            })
+            .dead_letter_queue(queue.clone())
+            .build();
+        pipeline.add_stage(Arc::new(FailingStage));
+        assert!(pipeline.dead_letter_queue().is_some());
+
+        let invalid = Record::new("invalid", json!({}));
+        let timed_out = Record::new("timed_out", json!({"name": "test"}));
+        let (invalid_id, timed_out_id) = (invalid.id, timed_out.id);
+        pipeline.execute(invalid).await.unwrap_err();
+        pipeline.execute(timed_out).await.unwrap_err();
+
+        let letter = queue.get(&invalid_id).await.unwrap().unwrap();
+        assert_eq!(letter.stage, "validation");
+        assert_eq!(letter.code, "VALIDATION_ERROR");
+        assert_eq!(letter.attempts.len(), 1);
+
+        // Retryable failures are dead-lettered once retries are exhausted
+        let letter = queue.get(&timed_out_id).await.unwrap().unwrap();
+        assert_eq!(letter.stage, "failing");
+        assert_eq!(letter.code, "TIMEOUT");
+        assert_eq!(letter.attempts.len(), 2);
+        assert_eq!(letter.record.metadata.failure_count, 2);
+    }
+
+    #[derive(Debug)]
+    struct FailingStage;
+
+    #[async_trait]
+    impl PipelineStage for FailingStage {
+        async fn execute(&self, _record: Record) -> Result<Record> {
+            Err(crate::Error::timeout("always"))
+        }
+
+        fn name(&self) -> &str {
+            "failing"
+        }
+    }
+
+    #[derive(Debug)]
+    struct SleepStage {
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl PipelineStage for SleepStage {
+        async fn execute(&self, record: Record) -> Result<Record> {
+            tokio::time::sleep(self.delay).await;
+            Ok(record)
+        }
+
+        fn name(&self) -> &str {
+            "sleep"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stage_timeout_override() {
+        let mut pipeline = PipelineBuilder::new("timeout_pipeline")
+            .no_retry()
+            .stage_timeout(Duration::from_millis(10))
+            .build();
+        pipeline.add_stage_with_timeout(
+            Arc::new(SleepStage {
+                delay: Duration::from_millis(30),
+            }),
+            Duration::from_secs(5),
+        );
+        assert!(pipeline.execute(Record::new("test", json!({}))).await.is_ok());
+
+        pipeline.add_stage(Arc::new(SleepStage {
+            delay: Duration::from_millis(30),
+        }));
+        let err = pipeline.execute(Record::new("test", json!({}))).await.unwrap_err();
+        assert!(matches!(err, crate::Error::Timeout(_)));
+    }
+
+    #[tokio::test]
+    async fn test_record_deadline_carries_across_stages() {
+        let mut pipeline = PipelineBuilder::new("deadline_pipeline")
+            .no_retry()
+            .record_deadline(Duration::from_millis(50))
+            .build();
+        for _ in 0..3 {
+            pipeline.add_stage(Arc::new(SleepStage {
+                delay: Duration::from_millis(30),
+            }));
+        }
+
+        // Each stage fits its own timeout, but together they exceed the budget
+        let err = pipeline.execute(Record::new("test", json!({}))).await.unwrap_err();
+        assert!(matches!(err, crate::Error::Timeout(_)));
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_filter_is_not_a_failure() {
+        use crate::transform::FilterTransform;
+
+        let storage = Arc::new(InMemoryStorage::new());
+        let pipeline = PipelineBuilder::new("filter_pipeline")
+            .transform(Arc::new(FilterTransform::new("keep_active", |r: &Record| {
+                r.value["active"] == json!(true)
+            })))
+            .store(storage.clone())
+            .build();
+
+        let outcome = pipeline
+            .execute(Record::new("a", json!({"active": false})))
+            .await
+            .unwrap();
+        assert!(outcome.is_dropped());
+        assert_eq!(storage.count().await.unwrap(), 0);
+
+        let outcome = pipeline
+            .execute(Record::new("b", json!({"active": true})))
+            .await
+            .unwrap();
+        assert!(outcome.into_record().is_some());
+        assert_eq!(storage.count().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_split_runs_remaining_stages() {
+        use crate::transform::SplitTransform;
+
+        let storage = Arc::new(InMemoryStorage::new());
+        let pipeline = PipelineBuilder::new("split_pipeline")
+            .transform(Arc::new(SplitTransform::new("split", |r: Record| {
+                vec![Record::new(&r.key, json!({"n": 1})), Record::new(&r.key, json!({"n": 2}))]
+            })))
+            .transform(Arc::new(EnrichTransform::new("enrich", "seen", json!(true))))
+            .store(storage.clone())
+            .build();
+
+        let records = pipeline
+            .execute(Record::new("order", json!({})))
+            .await
+            .unwrap()
+            .into_records();
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.value["seen"] == json!(true)));
+        assert_eq!(storage.count().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_dead_letters_split_siblings_of_a_failure() {
+        use crate::transform::SplitTransform;
+
+        let queue = DeadLetterQueue::in_memory();
+        let mut pipeline = PipelineBuilder::new("split_pipeline")
+            .transform(Arc::new(SplitTransform::new("split", |r: Record| {
+                (0..3).map(|i| Record::new(&r.key, json!({"n": i}))).collect()
+            })))
+            .dead_letter_queue(queue.clone())
+            .build();
+        pipeline.add_stage(Arc::new(RejectOneStage));
+
+        pipeline.execute(Record::new("order", json!({}))).await.unwrap_err();
+
+        // The failing child and both siblings are dead-lettered
+        let letters = queue.list().await.unwrap();
+        let mut values: Vec<_> = letters.iter().map(|l| l.record.value["n"].clone()).collect();
+        values.sort_by_key(|v| v.as_i64());
+        assert_eq!(values, [json!(0), json!(1), json!(2)]);
+        assert!(letters.iter().all(|l| l.stage == "reject_one"));
+    }
+
+    #[derive(Debug)]
+    struct RejectOneStage;
+
+    #[async_trait]
+    impl PipelineStage for RejectOneStage {
+        async fn execute(&self, record: Record) -> Result<Record> {
+            if record.value["n"] == json!(1) {
+                return Err(crate::Error::processing("n = 1 rejected"));
+            }
+            Ok(record)
+        }
+
+        fn name(&self) -> &str {
+            "reject_one"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_execute_stream() {
+        use crate::transform::FilterTransform;
+        use futures::StreamExt;
+
+        let storage = Arc::new(InMemoryStorage::new());
+        let pipeline = Arc::new(
+            PipelineBuilder::new("stream_pipeline")
+                .transform(Arc::new(FilterTransform::new("even", |r: &Record| {
+                    r.value.as_u64().unwrap_or(1) % 2 == 0
+                })))
+                .store(storage.clone())
+                .build(),
+        );
+
+        let records = futures::stream::iter((0..100u64).map(|i| Record::new("n", i)));
+        let options = StreamOptions {
+            buffer_size: 8,
+            concurrency: 4,
+        };
+        let results: Vec<_> = pipeline.execute_stream(records, options).collect().await;
+
+        assert_eq!(results.len(), 100);
+        let dropped = results
+            .iter()
+            .filter(|r| r.outcome.as_ref().unwrap().is_dropped())
+            .count();
+        assert_eq!(dropped, 50);
+        assert_eq!(storage.count().await.unwrap(), 50);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_sink_stage() {
+        use crate::sink::JsonLinesSink;
+
+        let dir = tempfile::tempdir().unwrap();
+        let sink = Arc::new(JsonLinesSink::new(dir.path(), "out"));
+        let pipeline = PipelineBuilder::new("to_files")
+            .transform(Arc::new(EnrichTransform::new("tag", "seen", json!(true))))
+            .sink(sink.clone())
+            .build();
+
+        for i in 0..3 {
+            pipeline.execute(Record::new(format!("k{}", i), json!({"i": i}))).await.unwrap();
+        }
+        assert!(sink.completed_files().await.is_empty());
+
+        pipeline.close().await.unwrap();
+        let files = sink.completed_files().await;
+        assert_eq!(files.len(), 1);
+
+        let contents = std::fs::read_to_string(&files[0]).unwrap();
+        assert_eq!(contents.lines().count(), 3);
+        assert!(contents.starts_with("{\"i\":0,\"seen\":true}"));
+    }
+
+    #[derive(Debug, Default)]
+    struct FailingSink {
+        writes: std::sync::atomic::AtomicU32,
+    }
+
+    #[async_trait]
+    impl crate::sink::Sink for FailingSink {
+        async fn write(&self, _record: &Record) -> Result<()> {
+            self.writes.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Err(crate::Error::timeout("write"))
+        }
+
+        async fn flush(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn close(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn name(&self) -> &str {
+            "failing_sink"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_never_retries_sink_writes() {
+        let sink = Arc::new(FailingSink::default());
+        let pipeline = PipelineBuilder::new("to_sink")
+            .retry(RetryConfig {
+                initial_backoff: std::time::Duration::from_millis(1),
+                ..RetryConfig::default()
+            })
+            .sink(sink.clone())
+            .build();
+
+        pipeline.execute(Record::new("k", json!({}))).await.unwrap_err();
+        assert_eq!(sink.writes.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_pipeline_metrics_labels() {
+        use crate::{metrics::tests::capture_metrics, transform::FilterTransform};
+
+        let (_, metrics) = capture_metrics(async {
+            let mut validator = Validator::new();
+            validator.add_rule(Arc::new(RequiredFieldRule::new("name")));
+
+            let pipeline = PipelineBuilder::new("users")
+                .validate(Arc::new(validator))
+                .transform(Arc::new(FilterTransform::new("adults", |r: &Record| {
+                    r.value["age"].as_u64().unwrap_or(0) >= 18
+                })))
+                .no_retry()
+                .build();
+
+            pipeline.execute(Record::new("a", json!({"name": "A", "age": 30}))).await.unwrap();
+            pipeline.execute(Record::new("b", json!({"name": "B", "age": 10}))).await.unwrap();
+            pipeline.execute(Record::new("c", json!({"age": 40}))).await.unwrap_err();
+        });
+
+        let users = ("pipeline", "users");
+        assert_eq!(metrics.counter("pipeline_records_total", &[users, ("outcome", "success")]), 1);
+        assert_eq!(metrics.counter("pipeline_records_total", &[users, ("outcome", "filtered")]), 1);
+        assert_eq!(metrics.counter("pipeline_records_total", &[users, ("outcome", "failed")]), 1);
+
+        let validation = [users, ("stage", "validation"), ("outcome", "failed")];
+        assert_eq!(metrics.counter("pipeline_stage_executions_total", &validation), 1);
+        let adults = [users, ("stage", "adults"), ("outcome", "filtered")];
+        assert_eq!(metrics.counter("pipeline_stage_executions_total", &adults), 1);
+        assert_eq!(metrics.histogram_samples("pipeline_stage_duration_ms", &[users]), 5);
+
+        assert_eq!(metrics.counter("validations_failed", &[("rule", "required_field")]), 1);
+        assert_eq!(metrics.counter("errors_total", &[users, ("type", "VALIDATION_ERROR")]), 1);
+    }
+
+    #[test]
+    fn test_pipeline_validation_and_storage_metrics_labels() {
+        use crate::metrics::tests::capture_metrics;
+
+        let (_, metrics) = capture_metrics(async {
+            let mut validator = Validator::new();
+            validator.add_rule(Arc::new(RequiredFieldRule::new("name")));
+            let validator = Arc::new(validator);
+
+            let pipeline = PipelineBuilder::new("users")
+                .validate(validator.clone())
+                .store(Arc::new(InMemoryStorage::new()))
+                .no_retry()
+                .build();
+            pipeline.execute(Record::new("a", json!({"name": "A"}))).await.unwrap();
+
+            // Metrics switched off after the stages were added still apply to them
+            let quiet = PipelineBuilder::new("quiet")
+                .validate(validator)
+                .store(Arc::new(InMemoryStorage::new()))
+                .metrics(false)
+                .build();
+            quiet.execute(Record::new("b", json!({"name": "B"}))).await.unwrap();
+        });
+
+        let validation = [("pipeline", "users"), ("stage", "validation")];
+        assert_eq!(metrics.counter("validations_success", &validation), 1);
+        let storage = [("pipeline", "users"), ("stage", "storage"), ("operation", "store")];
+        assert_eq!(metrics.counter("storage_operations_success", &storage), 1);
+
+        assert_eq!(metrics.counter("validations_total", &[]), 1);
+        assert_eq!(metrics.counter("storage_operations_total", &[]), 1);
+    }
 }
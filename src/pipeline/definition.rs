@@ -0,0 +1,981 @@
+//! Declarative pipeline definitions loaded from YAML, TOML or JSON
+//!
+//! A definition names its validators and storage targets up front and then
+//! lists the stages that use them:
+//!
+//! ```yaml
+//! name: orders
+//! stage_timeout: 5s
+//! validators:
+//!   order:
+//!     rules:
+//!       - type: required
+//!         field: customer.email
+//!       - type: numeric_range
+//!         field: items[*].price
+//!         min: 0
+//!         severity: warning
+//! storage:
+//!   primary:
+//!     type: file
+//!     path: /var/lib/orders
+//! stages:
+//!   - validate: order
+//!     report: true
+//!   - transform: normalize
+//!     fields: [customer.email]
+//!   - store: primary
+//!     timeout: 2s
+//! ```
+//!
+//! Rules, transforms and storage types are looked up by name in a
+//! [`PipelineRegistry`], which can be extended with custom factories.
+
+use super::{Pipeline, PipelineBuilder};
+use crate::{
+    compression::Compression,
+    config::{parse_duration, ProcessorConfig, RetryConfig},
+    error::Severity,
+    metrics::MetricsRecorder,
+    path::FieldPath,
+    processor::Transform,
+    storage::{FileStorage, FileStorageConfig, InMemoryStorage, Storage},
+    transform::{EnrichTransform, NormalizeTransform},
+    validation::{
+        JsonSchemaRule, NonEmptyStringRule, NumericRangeRule, RequiredFieldRule, ValidationRule,
+        Validator,
+    },
+    Error, Result,
+};
+#[cfg(feature = "cloud")]
+use crate::storage::{ObjectStoreStorage, S3Config};
+use futures::future::BoxFuture;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+/// Factory creating a transform from its definition arguments
+pub type TransformFactory = Arc<dyn Fn(&Value) -> Result<Arc<dyn Transform>> + Send + Sync>;
+
+/// Factory creating a validation rule from its definition arguments
+pub type RuleFactory = Arc<dyn Fn(&Value) -> Result<Arc<dyn ValidationRule>> + Send + Sync>;
+
+/// Factory opening a storage backend from its definition arguments
+pub type StorageFactory =
+    Arc<dyn Fn(&Value) -> BoxFuture<'static, Result<Arc<dyn Storage>>> + Send + Sync>;
+
+/// File format of a pipeline definition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionFormat {
+    /// YAML (`.yaml`, `.yml`)
+    Yaml,
+    /// TOML (`.toml`)
+    Toml,
+    /// JSON (`.json`)
+    Json,
+}
+
+impl DefinitionFormat {
+    /// Detect the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Some(DefinitionFormat::Yaml),
+            "toml" => Some(DefinitionFormat::Toml),
+            "json" => Some(DefinitionFormat::Json),
+            _ => None,
+        }
+    }
+
+    fn file_format(self) -> ::config::FileFormat {
+        match self {
+            DefinitionFormat::Yaml => ::config::FileFormat::Yaml,
+            DefinitionFormat::Toml => ::config::FileFormat::Toml,
+            DefinitionFormat::Json => ::config::FileFormat::Json,
+        }
+    }
+}
+
+/// Parsed, not yet built, pipeline definition
+#[derive(Debug, Clone)]
+pub struct PipelineDefinition {
+    origin: Option<PathBuf>,
+    root: Value,
+}
+
+impl PipelineDefinition {
+    /// Read a definition file, detecting the format from its extension
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let format = DefinitionFormat::from_path(path).ok_or_else(|| {
+            Error::config(format!(
+                "{}: unknown pipeline definition format, expected .yaml, .yml, .toml or .json",
+                path.display()
+            ))
+        })?;
+        let contents = std::fs::read_to_string(path)?;
+
+        let mut definition = Self::parse(&contents, format)?;
+        definition.origin = Some(path.to_path_buf());
+        Ok(definition)
+    }
+
+    /// Parse a definition from a string
+    pub fn parse(contents: &str, format: DefinitionFormat) -> Result<Self> {
+        let root = ::config::Config::builder()
+            .add_source(::config::File::from_str(contents, format.file_format()))
+            .build()
+            .and_then(|settings| settings.try_deserialize::<Value>())
+            .map_err(|e| Error::config(format!("Invalid pipeline definition: {}", e)))?;
+        Ok(Self::from_value(root))
+    }
+
+    /// Use an already parsed definition
+    pub fn from_value(root: Value) -> Self {
+        Self { origin: None, root }
+    }
+
+    /// File the definition was read from, if any
+    pub fn origin(&self) -> Option<&Path> {
+        self.origin.as_deref()
+    }
+
+    /// Configuration error pointing at `key`, wrapping an error from a factory
+    fn wrap(&self, key: &str, error: Error) -> Error {
+        match error {
+            Error::Config(message) => self.error(key, message),
+            other => self.error(key, other),
+        }
+    }
+
+    /// Configuration error pointing at `key`
+    fn error(&self, key: &str, message: impl fmt::Display) -> Error {
+        match &self.origin {
+            Some(path) => Error::config(format!("{}: `{}`: {}", path.display(), key, message)),
+            None => Error::config(format!("`{}`: {}", key, message)),
+        }
+    }
+}
+
+/// Registry of named rule, transform and storage factories
+///
+/// [`PipelineRegistry::new`] includes the built-in components:
+///
+/// - rules: `required` (`field`), `non_empty` (`field`), `numeric_range`
+///   (`field`, `min`, `max`) and `json_schema` (`schema` or `file`)
+/// - transforms: `normalize` (`fields`) and `enrich` (`field`, `value`)
+/// - storage: `memory` and `file` (`path`, optional `compression` with `codec` and `level`);
+///   with the `cloud` feature also `s3` (`bucket`, optional `prefix`, `region`, `endpoint`
+///   and `allow_http`) and `local_object` (`path`, optional `prefix`), an emulated
+///   object store in a local directory
+///
+/// [`PipelineRegistry::with_config`] also applies processor-wide settings.
+#[derive(Clone)]
+pub struct PipelineRegistry {
+    rules: HashMap<String, RuleFactory>,
+    transforms: HashMap<String, TransformFactory>,
+    storage: HashMap<String, StorageFactory>,
+    metrics: Option<bool>,
+}
+
+impl fmt::Debug for PipelineRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PipelineRegistry")
+            .field("rules", &sorted_names(&self.rules))
+            .field("transforms", &sorted_names(&self.transforms))
+            .field("storage", &sorted_names(&self.storage))
+            .field("metrics", &self.metrics)
+            .finish()
+    }
+}
+
+impl Default for PipelineRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PipelineRegistry {
+    /// Create a registry with the built-in components
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+
+        registry.register_rule("required", |args| {
+            let args: FieldArgs = parse_args(args)?;
+            Ok(Arc::new(RequiredFieldRule::new(FieldPath::parse(&args.field)?)))
+        });
+        registry.register_rule("non_empty", |args| {
+            let args: FieldArgs = parse_args(args)?;
+            Ok(Arc::new(NonEmptyStringRule::new(FieldPath::parse(&args.field)?)))
+        });
+        registry.register_rule("numeric_range", |args| {
+            let args: RangeArgs = parse_args(args)?;
+            let mut rule = NumericRangeRule::new(FieldPath::parse(&args.field)?);
+            if let Some(min) = args.min {
+                rule = rule.min(min);
+            }
+            if let Some(max) = args.max {
+                rule = rule.max(max);
+            }
+            Ok(Arc::new(rule))
+        });
+        registry.register_rule("json_schema", |args| {
+            let args: SchemaArgs = parse_args(args)?;
+            let rule = match (args.schema, args.file) {
+                (Some(schema), None) => JsonSchemaRule::new(schema)?,
+                (None, Some(file)) => JsonSchemaRule::from_file(file)?,
+                _ => return Err(Error::config("expected exactly one of `schema` or `file`")),
+            };
+            Ok(Arc::new(rule))
+        });
+
+        registry.register_transform("normalize", |args| {
+            let args: NormalizeArgs = parse_args(args)?;
+            let fields = args
+                .fields
+                .iter()
+                .map(|field| FieldPath::parse(field))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Arc::new(NormalizeTransform::new("normalize", fields)))
+        });
+        registry.register_transform("enrich", |args| {
+            let args: EnrichArgs = parse_args(args)?;
+            let field = FieldPath::parse(&args.field)?;
+            Ok(Arc::new(EnrichTransform::new("enrich", field, args.value)))
+        });
+
+        registry.register_storage("memory", |args| {
+            let parsed = parse_args::<EmptyArgs>(args);
+            Box::pin(async move {
+                parsed?;
+                Ok(Arc::new(InMemoryStorage::new()) as Arc<dyn Storage>)
+            })
+        });
+        registry.register_storage("file", file_storage_factory(None, None));
+        #[cfg(feature = "cloud")]
+        registry.register_storage("s3", |args| {
+            let parsed = parse_args::<S3StorageArgs>(args);
+            Box::pin(async move {
+                let parsed = parsed?;
+                let mut config = S3Config::new(parsed.bucket).allow_http(parsed.allow_http);
+                config.region = parsed.region;
+                config.endpoint = parsed.endpoint;
+                let storage = ObjectStoreStorage::s3(&config, parsed.prefix)?;
+                Ok(Arc::new(storage) as Arc<dyn Storage>)
+            })
+        });
+        #[cfg(feature = "cloud")]
+        registry.register_storage("local_object", |args| {
+            let parsed = parse_args::<LocalObjectStorageArgs>(args);
+            Box::pin(async move {
+                let parsed = parsed?;
+                let storage = ObjectStoreStorage::local(parsed.path, parsed.prefix).await?;
+                Ok(Arc::new(storage) as Arc<dyn Storage>)
+            })
+        });
+
+        registry
+    }
+
+    /// Create a registry with the built-in components and processor-wide settings
+    ///
+    /// `file` storage without a `compression` setting uses
+    /// [`ProcessorConfig::active_compression`], and pipelines without a
+    /// `metrics` setting, along with their file storage, follow
+    /// `enable_metrics`.
+    pub fn with_config(config: &ProcessorConfig) -> Self {
+        let mut registry = Self::new();
+        let metrics = MetricsRecorder::new(config.enable_metrics);
+        let factory = file_storage_factory(config.active_compression(), Some(metrics));
+        registry.register_storage("file", factory);
+        registry.metrics = Some(config.enable_metrics);
+        registry
+    }
+
+    /// Create a registry with no components
+    pub fn empty() -> Self {
+        Self {
+            rules: HashMap::new(),
+            transforms: HashMap::new(),
+            storage: HashMap::new(),
+            metrics: None,
+        }
+    }
+
+    /// Register a validation rule factory, replacing any with the same name
+    pub fn register_rule<F>(&mut self, name: impl Into<String>, factory: F)
+    where
+        F: Fn(&Value) -> Result<Arc<dyn ValidationRule>> + Send + Sync + 'static,
+    {
+        self.rules.insert(name.into(), Arc::new(factory));
+    }
+
+    /// Register a transform factory, replacing any with the same name
+    pub fn register_transform<F>(&mut self, name: impl Into<String>, factory: F)
+    where
+        F: Fn(&Value) -> Result<Arc<dyn Transform>> + Send + Sync + 'static,
+    {
+        self.transforms.insert(name.into(), Arc::new(factory));
+    }
+
+    /// Register a storage factory, replacing any with the same name
+    pub fn register_storage<F>(&mut self, name: impl Into<String>, factory: F)
+    where
+        F: Fn(&Value) -> BoxFuture<'static, Result<Arc<dyn Storage>>> + Send + Sync + 'static,
+    {
+        self.storage.insert(name.into(), Arc::new(factory));
+    }
+
+    /// Read a definition file and build its pipeline
+    pub async fn load(&self, path: impl AsRef<Path>) -> Result<Pipeline> {
+        self.build(&PipelineDefinition::from_file(path)?).await
+    }
+
+    /// Build the pipeline described by a definition
+    ///
+    /// Errors name the offending key, such as `stages[2].transform`.
+    pub async fn build(&self, definition: &PipelineDefinition) -> Result<Pipeline> {
+        let root = definition
+            .root
+            .as_object()
+            .ok_or_else(|| definition.error("(root)", "expected a table of pipeline settings"))?;
+        check_keys(definition, "", root, ROOT_KEYS)?;
+
+        let name = match root.get("name") {
+            Some(Value::String(name)) => name.clone(),
+            Some(_) => return Err(definition.error("name", "expected a string")),
+            None => return Err(definition.error("name", "missing pipeline name")),
+        };
+        let mut builder = PipelineBuilder::new(name);
+
+        if let Some(value) = root.get("stage_timeout") {
+            builder = builder.stage_timeout(duration(definition, "stage_timeout", value)?);
+        }
+        if let Some(value) = root.get("record_deadline") {
+            builder = builder.record_deadline(duration(definition, "record_deadline", value)?);
+        }
+        if let Some(value) = root.get("retry_attempts") {
+            match value.as_u64() {
+                Some(0) | Some(1) => builder = builder.no_retry(),
+                Some(attempts) => {
+                    builder = builder.retry(RetryConfig {
+                        max_attempts: attempts.min(u32::MAX as u64) as u32,
+                        ..RetryConfig::default()
+                    })
+                }
+                None => {
+                    return Err(definition.error("retry_attempts", "expected a positive integer"))
+                }
+            }
+        }
+        if let Some(value) = root.get("metrics") {
+            let enabled = value
+                .as_bool()
+                .ok_or_else(|| definition.error("metrics", "expected true or false"))?;
+            builder = builder.metrics(enabled);
+        } else if let Some(enabled) = self.metrics {
+            builder = builder.metrics(enabled);
+        }
+
+        let validators = self.build_validators(definition, root.get("validators"))?;
+        let storage = self.build_storage(definition, root.get("storage")).await?;
+
+        let stages = match root.get("stages") {
+            Some(Value::Array(stages)) if !stages.is_empty() => stages,
+            Some(Value::Array(_)) | None => {
+                return Err(definition.error("stages", "a pipeline needs at least one stage"))
+            }
+            Some(_) => return Err(definition.error("stages", "expected a list of stages")),
+        };
+        for (i, stage) in stages.iter().enumerate() {
+            let key = format!("stages[{}]", i);
+            builder = self.add_stage(definition, &key, stage, builder, &validators, &storage)?;
+        }
+
+        Ok(builder.build())
+    }
+
+    fn build_validators(
+        &self,
+        definition: &PipelineDefinition,
+        section: Option<&Value>,
+    ) -> Result<BTreeMap<String, Arc<Validator>>> {
+        let mut validators = BTreeMap::new();
+        for (name, value) in table(definition, "validators", section)? {
+            let key = format!("validators.{}", name);
+            let object = value
+                .as_object()
+                .ok_or_else(|| definition.error(&key, "expected a table with `rules`"))?;
+            check_keys(definition, &key, object, &["rules"])?;
+
+            let rules = match object.get("rules") {
+                Some(Value::Array(rules)) => rules,
+                _ => return Err(definition.error(&key, "expected a list of `rules`")),
+            };
+
+            let mut validator = Validator::new();
+            for (i, rule) in rules.iter().enumerate() {
+                let key = format!("{}.rules[{}]", key, i);
+                let (kind, mut args) = component(definition, &key, rule, "type")?;
+                let severity = match args.remove("severity") {
+                    None => None,
+                    Some(value) => Some(
+                        serde_json::from_value::<Severity>(value).map_err(|_| {
+                            definition.error(
+                                &format!("{}.severity", key),
+                                "expected `error` or `warning`",
+                            )
+                        })?,
+                    ),
+                };
+
+                let factory = self.rules.get(&kind).ok_or_else(|| {
+                    let message = unknown("rule", &kind, sorted_names(&self.rules));
+                    definition.error(&format!("{}.type", key), message)
+                })?;
+                let rule = factory(&Value::Object(args)).map_err(|e| definition.wrap(&key, e))?;
+
+                match severity {
+                    Some(severity) => validator.add_rule_with_severity(rule, severity),
+                    None => validator.add_rule(rule),
+                }
+            }
+            validators.insert(name.clone(), Arc::new(validator));
+        }
+        Ok(validators)
+    }
+
+    async fn build_storage(
+        &self,
+        definition: &PipelineDefinition,
+        section: Option<&Value>,
+    ) -> Result<BTreeMap<String, Arc<dyn Storage>>> {
+        let mut targets = BTreeMap::new();
+        for (name, value) in table(definition, "storage", section)? {
+            let key = format!("storage.{}", name);
+            let (kind, args) = component(definition, &key, value, "type")?;
+            let factory = self.storage.get(&kind).ok_or_else(|| {
+                let message = unknown("storage type", &kind, sorted_names(&self.storage));
+                definition.error(&format!("{}.type", key), message)
+            })?;
+
+            let storage = factory(&Value::Object(args))
+                .await
+                .map_err(|e| definition.wrap(&key, e))?;
+            targets.insert(name.clone(), storage);
+        }
+        Ok(targets)
+    }
+
+    fn add_stage(
+        &self,
+        definition: &PipelineDefinition,
+        key: &str,
+        stage: &Value,
+        builder: PipelineBuilder,
+        validators: &BTreeMap<String, Arc<Validator>>,
+        storage: &BTreeMap<String, Arc<dyn Storage>>,
+    ) -> Result<PipelineBuilder> {
+        let object = stage
+            .as_object()
+            .ok_or_else(|| definition.error(key, "expected a table"))?;
+        let kinds: Vec<_> = STAGE_KINDS
+            .iter()
+            .copied()
+            .filter(|kind| object.contains_key(*kind))
+            .collect();
+        let kind = match kinds.as_slice() {
+            [kind] => *kind,
+            _ => {
+                let message = "expected exactly one of `validate`, `transform` or `store`";
+                return Err(definition.error(key, message));
+            }
+        };
+
+        let (name, mut args) = component(definition, key, stage, kind)?;
+        let timeout = match args.remove("timeout") {
+            Some(value) => Some(duration(definition, &format!("{}.timeout", key), &value)?),
+            None => None,
+        };
+        let kind_key = format!("{}.{}", key, kind);
+
+        let builder = match kind {
+            "validate" => {
+                let report = match args.remove("report") {
+                    None => false,
+                    Some(Value::Bool(report)) => report,
+                    Some(_) => {
+                        let key = format!("{}.report", key);
+                        return Err(definition.error(&key, "expected true or false"));
+                    }
+                };
+                check_keys(definition, key, &args, &[])?;
+                let validator = validators.get(&name).cloned().ok_or_else(|| {
+                    let known = validators.keys().map(String::as_str).collect();
+                    definition.error(&kind_key, unknown("validator", &name, known))
+                })?;
+                if report {
+                    builder.validate_with_report(validator)
+                } else {
+                    builder.validate(validator)
+                }
+            }
+            "transform" => {
+                let factory = self.transforms.get(&name).ok_or_else(|| {
+                    let message = unknown("transform", &name, sorted_names(&self.transforms));
+                    definition.error(&kind_key, message)
+                })?;
+                let transform =
+                    factory(&Value::Object(args)).map_err(|e| definition.wrap(key, e))?;
+                builder.transform(transform)
+            }
+            _ => {
+                check_keys(definition, key, &args, &[])?;
+                let target = storage.get(&name).cloned().ok_or_else(|| {
+                    let known = storage.keys().map(String::as_str).collect();
+                    definition.error(&kind_key, unknown("storage target", &name, known))
+                })?;
+                builder.store(target)
+            }
+        };
+
+        Ok(match timeout {
+            Some(timeout) => builder.with_timeout(timeout),
+            None => builder,
+        })
+    }
+}
+
+/// Keys allowed at the top level of a definition
+const ROOT_KEYS: &[&str] = &[
+    "name",
+    "stage_timeout",
+    "record_deadline",
+    "retry_attempts",
+    "metrics",
+    "validators",
+    "storage",
+    "stages",
+];
+
+/// Keys naming the kind of a stage
+const STAGE_KINDS: &[&str] = &["validate", "transform", "store"];
+
+/// Split a component table into its name (under `kind_key`) and remaining arguments
+fn component(
+    definition: &PipelineDefinition,
+    key: &str,
+    value: &Value,
+    kind_key: &str,
+) -> Result<(String, Map<String, Value>)> {
+    let mut args = value
+        .as_object()
+        .cloned()
+        .ok_or_else(|| definition.error(key, "expected a table"))?;
+    match args.remove(kind_key) {
+        Some(Value::String(name)) => Ok((name, args)),
+        Some(_) => Err(definition.error(&format!("{}.{}", key, kind_key), "expected a name")),
+        None => Err(definition.error(key, format!("missing `{}`", kind_key))),
+    }
+}
+
+/// Entries of an optional table section
+fn table<'a>(
+    definition: &PipelineDefinition,
+    key: &str,
+    section: Option<&'a Value>,
+) -> Result<Vec<(&'a String, &'a Value)>> {
+    match section {
+        None => Ok(Vec::new()),
+        Some(Value::Object(entries)) => Ok(entries.iter().collect()),
+        Some(_) => Err(definition.error(key, "expected a table of named entries")),
+    }
+}
+
+/// Reject keys that are not in `allowed`
+fn check_keys(
+    definition: &PipelineDefinition,
+    key: &str,
+    object: &Map<String, Value>,
+    allowed: &[&str],
+) -> Result<()> {
+    match object.keys().find(|k| !allowed.contains(&k.as_str())) {
+        Some(unexpected) => {
+            let full = if key.is_empty() {
+                unexpected.clone()
+            } else {
+                format!("{}.{}", key, unexpected)
+            };
+            Err(definition.error(&full, "unknown key"))
+        }
+        None => Ok(()),
+    }
+}
+
+fn duration(definition: &PipelineDefinition, key: &str, value: &Value) -> Result<Duration> {
+    let parsed = match value {
+        Value::String(s) => parse_duration(s),
+        Value::Number(n) => n
+            .as_f64()
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .ok_or_else(|| Error::config("expected a non-negative number of seconds")),
+        _ => Err(Error::config("expected a duration such as \"30s\"")),
+    };
+    parsed.map_err(|e| definition.wrap(key, e))
+}
+
+/// Deserialize factory arguments, rejecting unknown keys
+///
+/// Useful for custom factories registered with a [`PipelineRegistry`].
+pub fn parse_args<T: DeserializeOwned>(args: &Value) -> Result<T> {
+    serde_json::from_value(args.clone())
+        .map_err(|e| Error::config(format!("invalid arguments: {}", e)))
+}
+
+fn sorted_names<V>(map: &HashMap<String, V>) -> Vec<&str> {
+    let mut names: Vec<_> = map.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    names
+}
+
+fn unknown(what: &str, name: &str, known: Vec<&str>) -> String {
+    if known.is_empty() {
+        format!("unknown {} '{}'", what, name)
+    } else {
+        format!("unknown {} '{}' (known: {})", what, name, known.join(", "))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EmptyArgs {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldArgs {
+    field: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RangeArgs {
+    field: String,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SchemaArgs {
+    schema: Option<Value>,
+    file: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NormalizeArgs {
+    fields: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnrichArgs {
+    field: String,
+    value: Value,
+}
+
+/// Factory for `file` storage, with the compression used when none is given
+fn file_storage_factory(
+    default_compression: Option<Compression>,
+    metrics: Option<MetricsRecorder>,
+) -> impl Fn(&Value) -> BoxFuture<'static, Result<Arc<dyn Storage>>> + Send + Sync + 'static {
+    move |args| {
+        let parsed = parse_args::<FileStorageArgs>(args);
+        let metrics = metrics.clone();
+        Box::pin(async move {
+            let parsed = parsed?;
+            let mut config = FileStorageConfig::default();
+            if let Some(compression) = parsed.compression.or(default_compression) {
+                config = config.compression(compression);
+            }
+            let mut storage = FileStorage::open_with_config(parsed.path, config).await?;
+            if let Some(metrics) = metrics {
+                storage = storage.with_metrics(metrics);
+            }
+            Ok(Arc::new(storage) as Arc<dyn Storage>)
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileStorageArgs {
+    path: PathBuf,
+    #[serde(default)]
+    compression: Option<Compression>,
+}
+
+#[cfg(feature = "cloud")]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct S3StorageArgs {
+    bucket: String,
+    #[serde(default)]
+    prefix: String,
+    #[serde(default)]
+    region: Option<String>,
+    #[serde(default)]
+    endpoint: Option<String>,
+    #[serde(default)]
+    allow_http: bool,
+}
+
+#[cfg(feature = "cloud")]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LocalObjectStorageArgs {
+    path: PathBuf,
+    #[serde(default)]
+    prefix: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{processor::TransformOutcome, record::Record};
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    const ORDERS_YAML: &str = r#"
+name: orders
+stage_timeout: 5s
+retry_attempts: 1
+validators:
+  order:
+    rules:
+      - type: required
+        field: customer.email
+      - type: numeric_range
+        field: items[*].price
+        min: 0
+        severity: warning
+storage:
+  primary:
+    type: memory
+stages:
+  - validate: order
+    report: true
+  - transform: normalize
+    fields: [customer.email]
+  - transform: upper_key
+  - store: primary
+    timeout: 2s
+"#;
+
+    #[derive(Debug)]
+    struct UpperKey;
+
+    #[async_trait]
+    impl Transform for UpperKey {
+        async fn transform(&self, mut record: Record) -> Result<Record> {
+            record.key = record.key.to_uppercase();
+            Ok(record)
+        }
+
+        fn name(&self) -> &str {
+            "upper_key"
+        }
+    }
+
+    fn registry() -> PipelineRegistry {
+        let mut registry = PipelineRegistry::new();
+        registry.register_transform("upper_key", |args| {
+            parse_args::<EmptyArgs>(args)?;
+            Ok(Arc::new(UpperKey))
+        });
+        registry
+    }
+
+    async fn build_error(yaml: &str) -> String {
+        let definition = PipelineDefinition::parse(yaml, DefinitionFormat::Yaml).unwrap();
+        registry().build(&definition).await.unwrap_err().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_build_from_yaml() {
+        let definition = PipelineDefinition::parse(ORDERS_YAML, DefinitionFormat::Yaml).unwrap();
+        let pipeline = registry().build(&definition).await.unwrap();
+        assert_eq!(pipeline.name(), "orders");
+        assert_eq!(pipeline.stage_count(), 4);
+
+        let record = Record::new(
+            "order-1",
+            json!({"customer": {"email": " A@X.COM "}, "items": [{"price": -1}]}),
+        );
+        let record = match pipeline.execute(record).await.unwrap() {
+            TransformOutcome::Keep(record) => record,
+            other => panic!("unexpected outcome {:?}", other),
+        };
+        assert_eq!(record.key, "ORDER-1");
+        assert_eq!(record.value["customer"]["email"], json!("a@x.com"));
+        assert_eq!(record.metadata.validation.unwrap().warnings().count(), 1);
+
+        let invalid = Record::new("order-2", json!({"items": []}));
+        assert!(pipeline.execute(invalid).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_toml_and_json_formats() {
+        let toml = r#"
+name = "events"
+
+[[stages]]
+transform = "enrich"
+field = "source"
+value = "toml"
+"#;
+        let definition = PipelineDefinition::parse(toml, DefinitionFormat::Toml).unwrap();
+        let pipeline = registry().build(&definition).await.unwrap();
+        let record = pipeline.execute(Record::new("k", json!({}))).await.unwrap();
+        assert_eq!(record.into_record().unwrap().value["source"], json!("toml"));
+
+        let json = r#"{"name": "events", "stages": [{"transform": "upper_key"}]}"#;
+        let definition = PipelineDefinition::parse(json, DefinitionFormat::Json).unwrap();
+        assert_eq!(registry().build(&definition).await.unwrap().stage_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_load_file_reports_origin() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("orders.yml");
+        std::fs::write(&path, ORDERS_YAML.replace("upper_key", "lower_key")).unwrap();
+
+        let err = registry().load(&path).await.unwrap_err().to_string();
+        assert!(err.contains("orders.yml"), "{}", err);
+        assert!(err.contains("`stages[2].transform`: unknown transform 'lower_key'"), "{}", err);
+        assert!(err.contains("known: enrich, normalize, upper_key"), "{}", err);
+
+        let path = dir.path().join("orders.ini");
+        std::fs::write(&path, "").unwrap();
+        assert!(registry().load(&path).await.is_err());
+    }
+
+    #[cfg(feature = "cloud")]
+    #[tokio::test]
+    async fn test_local_object_storage() {
+        use crate::storage::ObjectStoreStorage;
+
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            "name: x\nstorage: {{db: {{type: local_object, path: '{}', prefix: orders}}}}\n\
+             stages: [{{store: db}}]",
+            dir.path().display()
+        );
+        let definition = PipelineDefinition::parse(&yaml, DefinitionFormat::Yaml).unwrap();
+        let pipeline = registry().build(&definition).await.unwrap();
+        let record = Record::new("order-1", json!({"total": 3}));
+        pipeline.execute(record.clone()).await.unwrap();
+
+        let storage = ObjectStoreStorage::local(dir.path(), "orders").await.unwrap();
+        assert_eq!(storage.list().await.unwrap(), vec![record.id]);
+        assert!(dir.path().join(format!("orders/{}.json", record.id)).exists());
+
+        let err = build_error("name: x\nstorage: {db: {type: s3}}\nstages: [{store: db}]").await;
+        assert!(err.contains("missing field `bucket`"), "{}", err);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_with_config_applies_compression_and_metrics() {
+        use crate::metrics::tests::capture_metrics;
+
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = |name: &str| {
+            format!(
+                "name: {}\nstorage: {{db: {{type: file, path: '{}'}}}}\nstages: [{{store: db}}]",
+                name,
+                dir.path().join(name).display()
+            )
+        };
+        let value = json!({"text": "repetitive ".repeat(50)});
+
+        let (_, metrics) = capture_metrics(async {
+            let config = ProcessorConfig::builder().compression(Compression::gzip()).build();
+            let definition = PipelineDefinition::parse(&yaml("packed"), DefinitionFormat::Yaml);
+            let pipeline = PipelineRegistry::with_config(&config)
+                .build(&definition.unwrap())
+                .await
+                .unwrap();
+            pipeline.execute(Record::new("k", value.clone())).await.unwrap();
+
+            let config = ProcessorConfig::builder().enable_metrics(false).build();
+            let definition = PipelineDefinition::parse(&yaml("quiet"), DefinitionFormat::Yaml);
+            let pipeline = PipelineRegistry::with_config(&config)
+                .build(&definition.unwrap())
+                .await
+                .unwrap();
+            pipeline.execute(Record::new("k", value.clone())).await.unwrap();
+        });
+
+        let packed = [("codec", "gzip")];
+        let raw = metrics.counter("compression_input_bytes_total", &packed);
+        let compressed = metrics.counter("compression_output_bytes_total", &packed);
+        assert!(compressed > 0 && compressed < raw);
+
+        let packed = [("pipeline", "packed"), ("outcome", "success")];
+        assert_eq!(metrics.counter("pipeline_records_total", &packed), 1);
+        let quiet = [("pipeline", "quiet")];
+        assert_eq!(metrics.counter("pipeline_records_total", &quiet), 0);
+    }
+
+    #[tokio::test]
+    async fn test_errors_point_at_bad_key() {
+        let cases = [
+            ("stages: []", "`name`"),
+            ("name: x\nstages: []", "`stages`: a pipeline needs at least one stage"),
+            ("name: x\nstage_timeout: soon\nstages: [{transform: upper_key}]", "`stage_timeout`"),
+            ("name: x\ncolour: red\nstages: [{transform: upper_key}]", "`colour`: unknown key"),
+            ("name: x\nstages: [{validate: missing}]", "`stages[0].validate`: unknown validator"),
+            ("name: x\nstages: [{store: db}]", "`stages[0].store`: unknown storage target"),
+            (
+                "name: x\nstages: [{transform: upper_key, store: db}]",
+                "`stages[0]`: expected exactly one of",
+            ),
+            (
+                "name: x\nstages: [{transform: normalize, field: [a]}]",
+                "`stages[0]`: invalid arguments: unknown field `field`",
+            ),
+            (
+                "name: x\nvalidators: {v: {rules: [{type: regex}]}}\nstages: [{validate: v}]",
+                "`validators.v.rules[0].type`: unknown rule 'regex'",
+            ),
+            (
+                "name: x\nvalidators: {v: {rules: [{type: required}]}}\nstages: [{validate: v}]",
+                "`validators.v.rules[0]`: invalid arguments: missing field `field`",
+            ),
+            (
+                "name: x\nvalidators: {v: {rules: [{type: required, field: a, severity: fatal}]}}\n\
+                 stages: [{validate: v}]",
+                "`validators.v.rules[0].severity`",
+            ),
+            (
+                "name: x\nstages: [{transform: enrich, field: 'a..b', value: 1}]",
+                "`stages[0]`: Invalid field path 'a..b'",
+            ),
+            (
+                "name: x\nstorage: {db: {type: file}}\nstages: [{store: db}]",
+                "`storage.db`: invalid arguments: missing field `path`",
+            ),
+        ];
+
+        for (yaml, expected) in cases {
+            let err = build_error(yaml).await;
+            assert!(err.contains(expected), "{:?} gave {}", yaml, err);
+        }
+    }
+}
@@ -1,33 +1,189 @@
 //! Main data processor implementation
 
-use crate::{config::ProcessorConfig, error::Result, record::Record, Error};
+use crate::{
+    config::{ConfigLoader, ProcessorConfig},
+    dead_letter::{DeadLetter, DeadLetterQueue},
+    deadline::with_timeout,
+    error::Result,
+    metrics::MetricsRecorder,
+    record::{Record, RecordStatus},
+    retry::{FailedAttempt, RetryPolicy},
+    source::Source,
+    stream::{spawn_ordered, ResultStream, StreamOptions},
+    Error,
+};
 use async_trait::async_trait;
-use dashmap::DashMap;
-use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock, Semaphore};
+use futures::Stream;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::{mpsc, Semaphore, SemaphorePermit};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+mod records;
+mod shutdown;
+mod watch;
+
+use records::RecordStore;
+use shutdown::{FlushTarget, InFlight};
+pub use shutdown::{shutdown_signal, ShutdownReport};
+pub use watch::ConfigWatcher;
+
 /// Main data processor
 #[derive(Debug)]
 pub struct Processor {
-    config: ProcessorConfig,
     state: Arc<ProcessorState>,
-    transform_registry: Arc<TransformRegistry>,
+    transform_chain: Arc<TransformChain>,
+    dead_letters: Option<DeadLetterQueue>,
+    flush_targets: Arc<Vec<FlushTarget>>,
+    metrics: MetricsRecorder,
 }
 
 /// Internal processor state
 #[derive(Debug)]
 struct ProcessorState {
-    records: DashMap<Uuid, Record>,
-    active_tasks: RwLock<u64>,
+    /// Current configuration; each record keeps the snapshot it started with
+    config: parking_lot::RwLock<Arc<ProcessorConfig>>,
+    records: RecordStore,
+    active_tasks: parking_lot::Mutex<u64>,
     semaphore: Semaphore,
+    /// Permits still to be retired after `max_workers` was lowered
+    permit_debt: AtomicUsize,
+    in_flight: InFlight,
 }
 
-/// Registry for transformation functions
+impl ProcessorState {
+    /// Acquire a worker slot
+    async fn acquire_worker(&self) -> Result<WorkerPermit<'_>> {
+        let permit = self
+            .semaphore
+            .acquire()
+            .await
+            .map_err(|e| Error::concurrency(format!("Failed to acquire permit: {}", e)))?;
+        Ok(WorkerPermit {
+            permit: Some(permit),
+            debt: &self.permit_debt,
+        })
+    }
+
+    /// Resize the worker pool from `from` to `to` permits
+    ///
+    /// Growing pays off any outstanding debt before adding permits. Shrinking
+    /// retires idle permits immediately and the rest as in-flight work
+    /// releases them.
+    fn resize_workers(&self, from: usize, to: usize) {
+        if to > from {
+            let added = to - from;
+            let previous = self
+                .permit_debt
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |debt| {
+                    Some(debt - debt.min(added))
+                })
+                .unwrap_or_else(|debt| debt);
+            self.semaphore.add_permits(added - previous.min(added));
+        } else if to < from {
+            let shrink = from - to;
+            let retired = self.semaphore.forget_permits(shrink);
+            self.permit_debt.fetch_add(shrink - retired, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Worker slot that is retired on release while the pool is shrinking
+#[derive(Debug)]
+struct WorkerPermit<'a> {
+    permit: Option<SemaphorePermit<'a>>,
+    debt: &'a AtomicUsize,
+}
+
+impl Drop for WorkerPermit<'_> {
+    fn drop(&mut self) {
+        let Some(permit) = self.permit.take() else {
+            return;
+        };
+        let retire = self
+            .debt
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |debt| debt.checked_sub(1))
+            .is_ok();
+        if retire {
+            permit.forget();
+        }
+    }
+}
+
+/// Counts a record as an active task until dropped
+///
+/// Dropping also covers records whose processing panics or is cancelled.
+#[derive(Debug)]
+struct ActiveTask<'a> {
+    count: &'a parking_lot::Mutex<u64>,
+    metrics: &'a MetricsRecorder,
+}
+
+impl<'a> ActiveTask<'a> {
+    fn start(count: &'a parking_lot::Mutex<u64>, metrics: &'a MetricsRecorder) -> Self {
+        let mut active = count.lock();
+        *active += 1;
+        metrics.update_active_tasks(*active);
+        drop(active);
+        Self { count, metrics }
+    }
+}
+
+impl Drop for ActiveTask<'_> {
+    fn drop(&mut self) {
+        let mut active = self.count.lock();
+        *active -= 1;
+        self.metrics.update_active_tasks(*active);
+    }
+}
+
+/// Ordered chain of transformation functions
+///
+/// Transforms run in chain order and names are unique within the chain.
 #[derive(Debug, Default)]
-struct TransformRegistry {
-    transforms: DashMap<String, Arc<dyn Transform>>,
+struct TransformChain {
+    transforms: parking_lot::RwLock<Vec<Arc<dyn Transform>>>,
+}
+
+impl TransformChain {
+    /// Copy the current chain so no lock is held while transforms run
+    fn snapshot(&self) -> Vec<Arc<dyn Transform>> {
+        self.transforms.read().clone()
+    }
+
+    fn position(transforms: &[Arc<dyn Transform>], name: &str) -> Option<usize> {
+        transforms.iter().position(|t| t.name() == name)
+    }
+
+    fn insert_at(
+        &self,
+        transform: Arc<dyn Transform>,
+        anchor: Option<(&str, usize)>,
+    ) -> Result<()> {
+        let mut transforms = self.transforms.write();
+        if Self::position(&transforms, transform.name()).is_some() {
+            return Err(Error::config(format!(
+                "Transform '{}' is already registered",
+                transform.name()
+            )));
+        }
+
+        let index = match anchor {
+            Some((anchor, offset)) => Self::position(&transforms, anchor)
+                .ok_or_else(|| Error::not_found(format!("Transform '{}' not found", anchor)))?
+                + offset,
+            None => transforms.len(),
+        };
+        transforms.insert(index, transform);
+        Ok(())
+    }
 }
 
 /// Trait for record transformations
@@ -36,24 +192,106 @@ pub trait Transform: Send + Sync + std::fmt::Debug {
     /// Transform a record
     async fn transform(&self, record: Record) -> Result<Record>;
     
+    /// Apply the transform, allowing it to keep, drop or split the record
+    ///
+    /// The default implementation keeps the record returned by
+    /// [`transform`](Self::transform). Override this to filter records out
+    /// or fan one record out into several.
+    async fn apply(&self, record: Record) -> Result<TransformOutcome> {
+        self.transform(record).await.map(TransformOutcome::Keep)
+    }
+    
     /// Name of the transformation
     fn name(&self) -> &str;
 }
 
+/// Outcome of applying a transform or pipeline stage to a record
+///
+/// Errors are reported through the surrounding `Result`.
+#[derive(Debug, Clone)]
+pub enum TransformOutcome {
+    /// Keep the (possibly modified) record
+    Keep(Record),
+    
+    /// Drop the record; it is reported as filtered rather than failed
+    Drop,
+    
+    /// Replace the record with zero or more records
+    Split(Vec<Record>),
+}
+
+impl TransformOutcome {
+    /// Check whether the record was dropped
+    pub fn is_dropped(&self) -> bool {
+        matches!(self, TransformOutcome::Drop)
+    }
+    
+    /// Get the kept record, if the outcome is [`TransformOutcome::Keep`]
+    pub fn into_record(self) -> Option<Record> {
+        match self {
+            TransformOutcome::Keep(record) => Some(record),
+            _ => None,
+        }
+    }
+    
+    /// Get all resulting records
+    pub fn into_records(self) -> Vec<Record> {
+        match self {
+            TransformOutcome::Keep(record) => vec![record],
+            TransformOutcome::Drop => Vec::new(),
+            TransformOutcome::Split(records) => records,
+        }
+    }
+}
+
 /// Result of processing a record
 #[derive(Debug, Clone)]
 pub struct ProcessingResult {
     /// The processed record
+    ///
+    /// For split records this is the first output; for filtered or failed
+    /// records it is the input with its final status.
     pub record: Record,
     
+    /// All records emitted by processing: one normally, none if the record
+    /// was filtered out, several if a transform split it
+    pub outputs: Vec<Record>,
+    
     /// Processing duration in milliseconds
     pub duration_ms: u64,
     
-    /// Whether the processing was successful
+    /// Whether the processing completed without error
+    ///
+    /// Filtered records are successful; check [`filtered`](Self::filtered).
     pub success: bool,
     
+    /// Whether a transform filtered the record out
+    pub filtered: bool,
+    
     /// Error message if processing failed
     pub error: Option<String>,
+
+    /// Number of attempts it took, i.e. 1 plus the number of retries
+    pub attempts: u32,
+}
+
+/// Result of running a record through the transform chain
+#[derive(Debug)]
+struct ChainOutcome {
+    outputs: Vec<Record>,
+    /// Transform that dropped the last dropped record
+    dropped: Option<String>,
+    failed: Option<ChainFailure>,
+    attempts: u32,
+}
+
+/// Record that failed in the transform chain
+#[derive(Debug)]
+struct ChainFailure {
+    record: Record,
+    error: Error,
+    transform: String,
+    failures: Vec<FailedAttempt>,
 }
 
 impl Processor {
@@ -68,160 +306,519 @@ impl Processor {
         );
         
         Ok(Self {
-            config: config.clone(),
             state: Arc::new(ProcessorState {
-                records: DashMap::new(),
-                active_tasks: RwLock::new(0),
+                records: RecordStore::default(),
+                active_tasks: parking_lot::Mutex::new(0),
                 semaphore: Semaphore::new(config.max_workers),
+                permit_debt: AtomicUsize::new(0),
+                in_flight: InFlight::default(),
+                config: parking_lot::RwLock::new(Arc::new(config.clone())),
             }),
-            transform_registry: Arc::new(TransformRegistry::default()),
+            transform_chain: Arc::new(TransformChain::default()),
+            dead_letters: None,
+            flush_targets: Arc::default(),
+            metrics: MetricsRecorder::new(config.enable_metrics),
         })
     }
 
+    /// Send records that fail processing to a dead-letter queue
+    ///
+    /// A record is dead-lettered once its retries are exhausted, along with
+    /// the error code, the failing transform and the failed attempts. Records
+    /// in a batch whose task errors or panics are dead-lettered too.
+    pub fn with_dead_letter_queue(mut self, queue: DeadLetterQueue) -> Self {
+        self.dead_letters = Some(queue);
+        self
+    }
+
+    /// Get the dead-letter queue, if one is configured
+    pub fn dead_letter_queue(&self) -> Option<&DeadLetterQueue> {
+        self.dead_letters.as_ref()
+    }
+
     /// Process a single record
+    ///
+    /// The record is processed with the configuration current when it
+    /// started, even if [`reload`](Self::reload) is called meanwhile. Fails
+    /// once the processor is [shutting down](Self::shutdown), or if the record
+    /// is abandoned when shutdown times out.
     pub async fn process(&self, mut record: Record) -> Result<ProcessingResult> {
         let start = std::time::Instant::now();
+        let config = self.config();
+        let _in_flight = self.state.in_flight.enter(&record)?;
         
         debug!(record_id = %record.id, key = %record.key, "Processing record");
         
-        // Acquire semaphore to limit concurrency
-        let _permit = self
-            .state
-            .semaphore
-            .acquire()
-            .await
-            .map_err(|e| Error::concurrency(format!("Failed to acquire permit: {}", e)))?;
+        // Acquire a worker slot to limit concurrency
+        let _permit = tokio::select! {
+            permit = self.state.acquire_worker() => permit?,
+            _ = self.state.in_flight.aborted() => return Err(shutdown::abandoned()),
+        };
         
-        // Increment active tasks
-        {
-            let mut active = self.state.active_tasks.write().await;
-            *active += 1;
-        }
+        // Count the record as active until processing ends
+        let active = ActiveTask::start(&self.state.active_tasks, &self.metrics);
         
         // Mark record as processing
         record.mark_processing();
         
         // Store record
-        self.state.records.insert(record.id, record.clone());
+        self.state.records.insert(record.clone(), &config.record_retention);
         
         // Perform actual processing
-        let result = self.process_internal(record).await;
+        let input = record.clone();
+        let outcome = tokio::select! {
+            outcome = self.process_internal(record, &config) => Some(outcome),
+            _ = self.state.in_flight.aborted() => None,
+        };
+        
+        drop(active);
+        
+        let Some(outcome) = outcome else {
+            warn!(record_id = %input.id, "Record abandoned at shutdown");
+            return Err(shutdown::abandoned());
+        };
         
-        // Decrement active tasks
+        let duration_ms = start.elapsed().as_millis() as u64;
+        let ChainOutcome {
+            mut outputs,
+            dropped,
+            failed,
+            attempts,
+        } = outcome;
+        
+        if let Some(ChainFailure {
+            record: failed,
+            error: e,
+            transform,
+            failures,
+        }) = failed
         {
-            let mut active = self.state.active_tasks.write().await;
-            *active -= 1;
+            error!(error = %e, duration_ms, attempts, "Record processing failed");
+            
+            // The input is dead-lettered rather than the partly transformed
+            // record or split child that failed, so a replay re-runs all of it
+            let mut record = input;
+            record.metadata.status = RecordStatus::Failed;
+            record.metadata.failure_count += failures.len() as u32;
+            record.metadata.last_error = failed.metadata.last_error;
+            record.metadata.updated_at = failed.metadata.updated_at;
+            self.state.records.insert(record.clone(), &config.record_retention);
+            self.metrics.record_error(e.code());
+            self.metrics.record_processed(duration_ms, false);
+            self.dead_letter(DeadLetter::new(record.clone(), transform, &e, failures)).await;
+            
+            return Ok(ProcessingResult {
+                record,
+                outputs: Vec::new(),
+                duration_ms,
+                success: false,
+                filtered: false,
+                error: Some(e.to_string()),
+                attempts,
+            });
         }
         
-        let duration_ms = start.elapsed().as_millis() as u64;
+        if outputs.is_empty() {
+            // Drops were counted as they happened in the chain. The input is
+            // reported even when split children were dropped, so it leaves `Processing`
+            let transform = dropped.unwrap_or_default();
+            let mut record = input;
+            record.mark_filtered();
+            self.state.records.insert(record.clone(), &config.record_retention);
+            
+            info!(record_id = %record.id, transform, duration_ms, "Record filtered out");
+            
+            return Ok(ProcessingResult {
+                record,
+                outputs,
+                duration_ms,
+                success: true,
+                filtered: true,
+                error: None,
+                attempts,
+            });
+        }
         
-        match result {
-            Ok(processed_record) => {
-                info!(
-                    record_id = %processed_record.id,
-                    duration_ms,
-                    "Record processed successfully"
-                );
-                
-                Ok(ProcessingResult {
-                    record: processed_record,
-                    duration_ms,
-                    success: true,
-                    error: None,
-                })
-            }
-            Err(e) => {
-                error!(error = %e, duration_ms, "Record processing failed");
-                
-                Ok(ProcessingResult {
-                    record: self
-                        .state
-                        .records
-                        .get(&record.id)
-                        .map(|r| r.clone())
-                        .unwrap_or(record),
-                    duration_ms,
-                    success: false,
-                    error: Some(e.to_string()),
-                })
-            }
+        for output in &mut outputs {
+            output.mark_completed();
+            self.state.records.insert(output.clone(), &config.record_retention);
+        }
+        
+        // A record that was split into new records has been consumed
+        if !outputs.iter().any(|r| r.id == input.id) {
+            let mut consumed = input;
+            consumed.mark_completed();
+            self.state.records.insert(consumed, &config.record_retention);
         }
+        
+        self.metrics.record_processed(duration_ms, true);
+        
+        info!(
+            record_id = %outputs[0].id,
+            outputs = outputs.len(),
+            duration_ms,
+            attempts,
+            "Record processed successfully"
+        );
+        
+        Ok(ProcessingResult {
+            record: outputs[0].clone(),
+            outputs,
+            duration_ms,
+            success: true,
+            filtered: false,
+            error: None,
+            attempts,
+        })
     }
 
     /// Process a batch of records
+    ///
+    /// Returns one result per input, in input order. A record whose task
+    /// errors or panics gets a failed result and is dead-lettered if a queue
+    /// is configured.
     pub async fn process_batch(&self, records: Vec<Record>) -> Result<Vec<ProcessingResult>> {
         info!(count = records.len(), "Processing batch of records");
+        let start = std::time::Instant::now();
+        let max_batch_size = self.config().max_batch_size;
         
-        if records.len() > self.config.max_batch_size {
+        if self.is_shutting_down() {
+            return Err(Error::invalid_state("Processor is shutting down"));
+        }
+        
+        if records.len() > max_batch_size {
             return Err(Error::processing(format!(
                 "Batch size {} exceeds maximum of {}",
                 records.len(),
-                self.config.max_batch_size
+                max_batch_size
             )));
         }
         
         let mut handles = Vec::new();
         
         for record in records {
+            // Keep a copy to report if the task fails outright
+            let input = record.clone();
             let processor = self.clone();
             let handle = tokio::spawn(async move { processor.process(record).await });
-            handles.push(handle);
+            handles.push((input, handle));
         }
         
         let mut results = Vec::new();
-        for handle in handles {
+        for (input, handle) in handles {
             match handle.await {
                 Ok(Ok(result)) => results.push(result),
                 Ok(Err(e)) => {
                     warn!(error = %e, "Failed to process record in batch");
+                    // Records refused or abandoned at shutdown are reported by `shutdown`
+                    if !self.is_shutting_down() {
+                        let letter =
+                            DeadLetter::new(input.clone(), "process_batch", &e, Vec::new());
+                        self.dead_letter(letter).await;
+                    }
+                    results.push(self.failed_result(input, &e, start));
                 }
                 Err(e) => {
                     error!(error = %e, "Task panicked while processing record");
+                    let e = Error::processing(format!("Task panicked: {}", e));
+                    self.metrics.record_error(e.code());
+                    self.metrics.record_processed(start.elapsed().as_millis() as u64, false);
+                    let letter = DeadLetter::new(input.clone(), "process_batch", &e, Vec::new());
+                    self.dead_letter(letter).await;
+                    results.push(self.failed_result(input, &e, start));
                 }
             }
         }
         
+        self.metrics
+            .record_batch_processed(results.len(), start.elapsed().as_millis() as u64);
+        
         Ok(results)
     }
 
+    /// Result for a batch record whose task ended without a result
+    ///
+    /// The record is marked failed in the processor's state.
+    fn failed_result(
+        &self,
+        mut record: Record,
+        error: &Error,
+        start: std::time::Instant,
+    ) -> ProcessingResult {
+        record.mark_failed(error.to_string());
+        self.state.records.insert(record.clone(), &self.config().record_retention);
+        
+        ProcessingResult {
+            record,
+            outputs: Vec::new(),
+            duration_ms: start.elapsed().as_millis() as u64,
+            success: false,
+            filtered: false,
+            error: Some(error.to_string()),
+            attempts: 1,
+        }
+    }
+
+    /// Process a stream of records, emitting results as a stream
+    ///
+    /// Unlike [`process_batch`](Self::process_batch) the input size is not
+    /// limited by `max_batch_size`. Up to `max_workers` records are processed
+    /// at once and results are buffered in a channel of `buffer_size`; when
+    /// the consumer falls behind, no further records are pulled from the
+    /// input. Results are emitted in input order.
+    pub fn process_stream<S>(&self, records: S) -> ResultStream<Result<ProcessingResult>>
+    where
+        S: Stream<Item = Record> + Send + 'static,
+    {
+        let processor = self.clone();
+        let options = StreamOptions::from(&*self.config());
+        spawn_ordered(
+            records,
+            options,
+            move |record| {
+                let processor = processor.clone();
+                async move { processor.process(record).await }
+            },
+            |_| Err,
+        )
+    }
+
+    /// Process every record read from a source, emitting results as a stream
+    ///
+    /// Records are read only as fast as results are consumed. Entries the
+    /// source fails to read are passed through as errors.
+    pub fn process_source<S>(&self, source: S) -> ResultStream<Result<ProcessingResult>>
+    where
+        S: Source + 'static,
+    {
+        let processor = self.clone();
+        let options = StreamOptions::from(&*self.config());
+        spawn_ordered(
+            source.into_stream(),
+            options,
+            move |read| {
+                let processor = processor.clone();
+                async move { processor.process(read?).await }
+            },
+            |_| Err,
+        )
+    }
+
+    /// Open a bounded channel that feeds records into stream processing
+    ///
+    /// Sending blocks once `buffer_size` records are queued, so producers are
+    /// throttled to the processing rate. Drop the sender to finish the stream.
+    pub fn stream_channel(
+        &self,
+    ) -> (mpsc::Sender<Record>, ResultStream<Result<ProcessingResult>>) {
+        let (tx, rx) = mpsc::channel(self.config().buffer_size);
+        (tx, self.process_stream(ResultStream::new(rx)))
+    }
+
     /// Internal processing logic
-    async fn process_internal(&self, mut record: Record) -> Result<Record> {
-        // Apply all registered transforms
-        for transform_ref in self.transform_registry.transforms.iter() {
-            let transform = transform_ref.value();
-            debug!(
-                record_id = %record.id,
-                transform = transform.name(),
-                "Applying transform"
-            );
+    ///
+    /// Records produced by a split continue through the rest of the chain
+    /// individually.
+    async fn process_internal(&self, record: Record, config: &ProcessorConfig) -> ChainOutcome {
+        let mut attempts = 1;
+        let mut batch = vec![record];
+        let mut dropped = None;
+        
+        let transforms = self.transform_chain.snapshot();
+        let timeout = config.operation_timeout;
+        let retry_policy = RetryPolicy::new(config.retry_config.clone());
+        
+        // Apply the transform chain in order, each attempt bounded by the operation timeout
+        for transform in transforms {
+            let mut next = Vec::with_capacity(batch.len());
             
-            record = transform.transform(record).await?;
+            for record in batch {
+                debug!(
+                    record_id = %record.id,
+                    transform = transform.name(),
+                    "Applying transform"
+                );
+                
+                let started = std::time::Instant::now();
+                let outcome = retry_policy
+                    .execute(transform.name(), record, |r| {
+                        let transform = Arc::clone(&transform);
+                        async move {
+                            with_timeout(transform.name(), timeout, transform.apply(r)).await
+                        }
+                    })
+                    .await;
+                
+                attempts += outcome.attempts - 1;
+                self.metrics.record_transform(
+                    transform.name(),
+                    started.elapsed().as_millis() as u64,
+                    outcome.result.is_ok(),
+                );
+                
+                match outcome.result {
+                    Ok(TransformOutcome::Keep(record)) => next.push(record),
+                    Ok(TransformOutcome::Split(records)) if !records.is_empty() => {
+                        next.extend(records)
+                    }
+                    // Counted per record, so children of a split are counted one by one
+                    Ok(TransformOutcome::Drop | TransformOutcome::Split(_)) => {
+                        debug!(
+                            record_id = %outcome.record.id,
+                            transform = transform.name(),
+                            "Record dropped by transform"
+                        );
+                        self.metrics.record_filtered(transform.name());
+                        dropped = Some(transform.name().to_string());
+                    }
+                    Err(e) => {
+                        return ChainOutcome {
+                            outputs: Vec::new(),
+                            dropped,
+                            failed: Some(ChainFailure {
+                                record: outcome.record,
+                                error: e,
+                                transform: transform.name().to_string(),
+                                failures: outcome.failures,
+                            }),
+                            attempts,
+                        };
+                    }
+                }
+            }
+            
+            batch = next;
+            if batch.is_empty() {
+                break;
+            }
         }
         
-        // Mark as completed
-        record.mark_completed();
+        ChainOutcome {
+            outputs: batch,
+            dropped,
+            failed: None,
+            attempts,
+        }
+    }
+
+    /// Push an entry to the dead-letter queue, if one is configured
+    async fn dead_letter(&self, letter: DeadLetter) {
+        let Some(queue) = &self.dead_letters else {
+            return;
+        };
         
-        // Update stored record
-        self.state.records.insert(record.id, record.clone());
+        let (record_id, stage) = (letter.id(), letter.stage.clone());
+        match queue.push(letter).await {
+            Ok(()) => self.metrics.record_dead_lettered(&stage),
+            Err(e) => {
+                error!(record_id = %record_id, error = %e, "Failed to dead-letter record");
+                self.metrics.record_error(e.code());
+            }
+        }
+    }
+
+    /// Reprocess a dead-lettered record through the whole transform chain
+    ///
+    /// The entry is removed once the record succeeds or is filtered; a record
+    /// that fails again replaces its entry with the new failure.
+    pub async fn replay_dead_letter(&self, id: &Uuid) -> Result<ProcessingResult> {
+        let queue = self.require_dead_letter_queue()?;
+        let letter = queue
+            .get(id)
+            .await?
+            .ok_or_else(|| Error::not_found(format!("Dead letter '{}' not found", id)))?;
         
-        Ok(record)
+        info!(record_id = %id, stage = %letter.stage, "Replaying dead-lettered record");
+        let result = self.process(letter.record).await?;
+        if result.success {
+            queue.remove(id).await?;
+        }
+        Ok(result)
+    }
+
+    /// Reprocess every dead-lettered record, oldest first
+    pub async fn replay_dead_letters(&self) -> Result<Vec<ProcessingResult>> {
+        let letters = self.require_dead_letter_queue()?.list().await?;
+        let mut results = Vec::with_capacity(letters.len());
+        for letter in letters {
+            results.push(self.replay_dead_letter(&letter.id()).await?);
+        }
+        Ok(results)
+    }
+
+    fn require_dead_letter_queue(&self) -> Result<&DeadLetterQueue> {
+        self.dead_letters
+            .as_ref()
+            .ok_or_else(|| Error::invalid_state("No dead-letter queue configured"))
+    }
+
+    /// Register a transform at the end of the chain
+    ///
+    /// Fails if a transform with the same name is already registered; use
+    /// [`replace_transform`](Self::replace_transform) to swap one out.
+    pub fn register_transform(&self, transform: Arc<dyn Transform>) -> Result<()> {
+        info!(transform = transform.name(), "Registering transform");
+        self.transform_chain.insert_at(transform, None)
+    }
+
+    /// Insert a transform immediately before the named transform
+    pub fn insert_transform_before(
+        &self,
+        anchor: &str,
+        transform: Arc<dyn Transform>,
+    ) -> Result<()> {
+        info!(transform = transform.name(), before = anchor, "Inserting transform");
+        self.transform_chain.insert_at(transform, Some((anchor, 0)))
     }
 
-    /// Register a transform
-    pub fn register_transform(&self, transform: Arc<dyn Transform>) {
-        let name = transform.name().to_string();
-        info!(transform = %name, "Registering transform");
-        self.transform_registry.transforms.insert(name, transform);
+    /// Insert a transform immediately after the named transform
+    pub fn insert_transform_after(
+        &self,
+        anchor: &str,
+        transform: Arc<dyn Transform>,
+    ) -> Result<()> {
+        info!(transform = transform.name(), after = anchor, "Inserting transform");
+        self.transform_chain.insert_at(transform, Some((anchor, 1)))
+    }
+
+    /// Replace the transform with the same name, keeping its position
+    ///
+    /// Returns the transform that was replaced.
+    pub fn replace_transform(&self, transform: Arc<dyn Transform>) -> Result<Arc<dyn Transform>> {
+        let mut transforms = self.transform_chain.transforms.write();
+        let index = TransformChain::position(&transforms, transform.name()).ok_or_else(|| {
+            Error::not_found(format!("Transform '{}' not found", transform.name()))
+        })?;
+        info!(transform = transform.name(), "Replacing transform");
+        Ok(std::mem::replace(&mut transforms[index], transform))
+    }
+
+    /// Remove a transform from the chain by name
+    pub fn remove_transform(&self, name: &str) -> Option<Arc<dyn Transform>> {
+        let mut transforms = self.transform_chain.transforms.write();
+        let index = TransformChain::position(&transforms, name)?;
+        info!(transform = name, "Removing transform");
+        Some(transforms.remove(index))
+    }
+
+    /// List the names of registered transforms in application order
+    pub fn transform_names(&self) -> Vec<String> {
+        self.transform_chain
+            .transforms
+            .read()
+            .iter()
+            .map(|t| t.name().to_string())
+            .collect()
     }
 
     /// Get a record by ID
     pub fn get_record(&self, id: &Uuid) -> Option<Record> {
-        self.state.records.get(id).map(|r| r.clone())
+        self.state.records.get(id)
     }
 
     /// Get current number of active tasks
     pub async fn active_tasks(&self) -> u64 {
-        *self.state.active_tasks.read().await
+        *self.state.active_tasks.lock()
     }
 
     /// Get total number of stored records
@@ -234,18 +831,89 @@ impl Processor {
         self.state.records.clear();
     }
 
-    /// Get configuration
-    pub fn config(&self) -> &ProcessorConfig {
-        &self.config
+    /// Get the stored records with the given status, least recently updated first
+    ///
+    /// Useful for listing failed or in-flight records. Only records kept by
+    /// the `record_retention` policy are returned.
+    pub fn records_with_status(&self, status: RecordStatus) -> Vec<Record> {
+        self.prune_records();
+        self.state.records.with_status(status)
+    }
+
+    /// Count the stored records in each status
+    pub fn record_status_counts(&self) -> HashMap<RecordStatus, usize> {
+        self.prune_records();
+        self.state.records.status_counts()
+    }
+
+    /// Evict stored records beyond the `record_retention` limits
+    ///
+    /// Limits are applied whenever a record is stored; call this to also
+    /// expire records by age while the processor is idle.
+    pub fn prune_records(&self) {
+        self.state.records.prune(&self.config().record_retention);
+    }
+
+    /// Get the current configuration
+    pub fn config(&self) -> Arc<ProcessorConfig> {
+        Arc::clone(&self.state.config.read())
+    }
+
+    /// Apply a new configuration to the running processor
+    ///
+    /// `max_workers`, `max_batch_size`, `buffer_size`, `operation_timeout`,
+    /// `retry_config` and `record_retention` take effect atomically for records
+    /// that start after the call; records already in flight finish with the
+    /// settings they started with. Lowering `max_workers` retires worker slots as in-flight records
+    /// complete. Invalid configurations, and changes to `enable_metrics` or
+    /// `metrics_exporter` (which need a restart), are rejected and leave the
+    /// current configuration in place.
+    pub fn reload(&self, config: ProcessorConfig) -> Result<()> {
+        config.validate()?;
+        
+        let mut current = self.state.config.write();
+        if config.enable_metrics != current.enable_metrics
+            || config.metrics_exporter != current.metrics_exporter
+        {
+            return Err(Error::config(
+                "enable_metrics and metrics_exporter cannot be changed without a restart",
+            ));
+        }
+        
+        self.state.resize_workers(current.max_workers, config.max_workers);
+        if config.record_retention != current.record_retention {
+            self.state.records.set_retention(&config.record_retention);
+        }
+        info!(
+            max_workers = config.max_workers,
+            max_batch_size = config.max_batch_size,
+            operation_timeout_ms = config.operation_timeout.as_millis() as u64,
+            "Reloaded processor configuration"
+        );
+        *current = Arc::new(config);
+        Ok(())
+    }
+
+    /// Reload the configuration whenever the loader's files change
+    ///
+    /// The files are polled every `interval`. Each change is loaded with
+    /// [`ConfigLoader::load`] and applied with [`reload`](Self::reload); a
+    /// configuration that fails either step is logged and skipped. Must be
+    /// called from within a Tokio runtime; watching stops when the returned
+    /// handle is dropped.
+    pub fn watch_config(&self, loader: ConfigLoader, interval: Duration) -> ConfigWatcher {
+        ConfigWatcher::spawn(self.clone(), loader, interval)
     }
 }
 
 impl Clone for Processor {
     fn clone(&self) -> Self {
         Self {
-            config: self.config.clone(),
             state: Arc::clone(&self.state),
-            transform_registry: Arc::clone(&self.transform_registry),
+            transform_chain: Arc::clone(&self.transform_chain),
+            dead_letters: self.dead_letters.clone(),
+            flush_targets: Arc::clone(&self.flush_targets),
+            metrics: self.metrics.clone(),
         }
     }
 }
@@ -253,6 +921,7 @@ impl Clone for Processor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::RetentionConfig;
 
     #[tokio::test]
     async fn test_processor_creation() {
@@ -283,4 +952,742 @@ mod tests {
         assert_eq!(results.len(), 10);
         assert!(results.iter().all(|r| r.success));
     }
+
+    #[derive(Debug, Default)]
+    struct FlakyTransform {
+        failures_left: std::sync::atomic::AtomicU32,
+    }
+
+    #[async_trait]
+    impl Transform for FlakyTransform {
+        async fn transform(&self, record: Record) -> Result<Record> {
+            let left = self.failures_left.load(std::sync::atomic::Ordering::SeqCst);
+            if left > 0 {
+                self.failures_left.store(left - 1, std::sync::atomic::Ordering::SeqCst);
+                return Err(Error::timeout("transient"));
+            }
+            Ok(record)
+        }
+
+        fn name(&self) -> &str {
+            "flaky"
+        }
+    }
+
+    fn fast_retry_config() -> ProcessorConfig {
+        ProcessorConfig::builder()
+            .retry_config(crate::config::RetryConfig {
+                max_attempts: 3,
+                initial_backoff: std::time::Duration::from_millis(1),
+                max_backoff: std::time::Duration::from_millis(5),
+                backoff_multiplier: 2.0,
+                jitter: 0.0,
+            })
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_process_retries_transient_failures() {
+        let processor = Processor::new(fast_retry_config()).unwrap();
+        processor
+            .register_transform(Arc::new(FlakyTransform {
+                failures_left: 2.into(),
+            }))
+            .unwrap();
+
+        let result = processor.process(Record::new("key", "value")).await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.attempts, 3);
+        assert_eq!(result.record.metadata.failure_count, 2);
+        assert!(result.record.metadata.last_error.unwrap().contains("transient"));
+    }
+
+    #[derive(Debug)]
+    struct HangingTransform;
+
+    #[async_trait]
+    impl Transform for HangingTransform {
+        async fn transform(&self, record: Record) -> Result<Record> {
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            Ok(record)
+        }
+
+        fn name(&self) -> &str {
+            "hanging"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_times_out_hung_transform() {
+        let mut config = fast_retry_config();
+        config.operation_timeout = std::time::Duration::from_millis(20);
+        config.max_workers = 1;
+        let processor = Processor::new(config).unwrap();
+        processor.register_transform(Arc::new(HangingTransform)).unwrap();
+
+        let result = processor.process(Record::new("key", "value")).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("timed out"));
+        assert_eq!(result.attempts, 3);
+
+        // The permit was released, so the single worker slot is free again
+        assert_eq!(processor.active_tasks().await, 0);
+        assert_eq!(processor.state.semaphore.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_process_fails_after_retries_exhausted() {
+        let processor = Processor::new(fast_retry_config()).unwrap();
+        processor
+            .register_transform(Arc::new(FlakyTransform {
+                failures_left: 10.into(),
+            }))
+            .unwrap();
+
+        let record = Record::new("key", "value");
+        let id = record.id;
+        let result = processor.process(record).await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.attempts, 3);
+
+        let stored = processor.get_record(&id).unwrap();
+        assert_eq!(stored.metadata.status, RecordStatus::Failed);
+        assert_eq!(stored.metadata.failure_count, 3);
+        assert!(stored.metadata.last_error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_transform_chain_order() {
+        use crate::transform::MapTransform;
+        use serde_json::{json, Value};
+
+        let append = |name: &'static str| {
+            Arc::new(MapTransform::new(name, move |mut v: Value| {
+                if let Some(Value::Array(steps)) = v.get_mut("steps") {
+                    steps.push(json!(name));
+                }
+                v
+            }))
+        };
+
+        let processor = Processor::new(ProcessorConfig::default()).unwrap();
+        processor.register_transform(append("normalize")).unwrap();
+        processor.register_transform(append("enrich")).unwrap();
+        processor.insert_transform_before("normalize", append("parse")).unwrap();
+        processor.insert_transform_after("normalize", append("dedupe")).unwrap();
+
+        assert_eq!(
+            processor.transform_names(),
+            vec!["parse", "normalize", "dedupe", "enrich"]
+        );
+
+        for _ in 0..5 {
+            let result = processor
+                .process(Record::new("key", json!({"steps": []})))
+                .await
+                .unwrap();
+            assert_eq!(
+                result.record.value["steps"],
+                json!(["parse", "normalize", "dedupe", "enrich"])
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transform_chain_management() {
+        use crate::transform::EnrichTransform;
+        use serde_json::json;
+
+        let processor = Processor::new(ProcessorConfig::default()).unwrap();
+        processor
+            .register_transform(Arc::new(EnrichTransform::new("enrich", "v", json!(1))))
+            .unwrap();
+
+        // Duplicate names are rejected rather than silently replacing
+        assert!(processor
+            .register_transform(Arc::new(EnrichTransform::new("enrich", "v", json!(2))))
+            .is_err());
+        assert!(processor
+            .insert_transform_after("missing", Arc::new(EnrichTransform::new("x", "v", json!(0))))
+            .is_err());
+
+        processor
+            .replace_transform(Arc::new(EnrichTransform::new("enrich", "v", json!(2))))
+            .unwrap();
+        let result = processor.process(Record::new("key", json!({}))).await.unwrap();
+        assert_eq!(result.record.value["v"], json!(2));
+
+        assert!(processor.remove_transform("enrich").is_some());
+        assert!(processor.remove_transform("enrich").is_none());
+        assert!(processor.transform_names().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_split_records_continue_through_chain() {
+        use crate::transform::{EnrichTransform, SplitTransform};
+        use serde_json::json;
+
+        let processor = Processor::new(ProcessorConfig::default()).unwrap();
+        processor
+            .register_transform(Arc::new(SplitTransform::new("split", |r: Record| {
+                (0..3).map(|i| Record::new(&r.key, json!({"n": i}))).collect()
+            })))
+            .unwrap();
+        processor
+            .register_transform(Arc::new(EnrichTransform::new("enrich", "seen", json!(true))))
+            .unwrap();
+
+        let result = processor.process(Record::new("key", json!({}))).await.unwrap();
+        assert!(result.success && !result.filtered);
+        assert_eq!(result.outputs.len(), 3);
+        assert!(result.outputs.iter().all(|r| r.value["seen"] == json!(true)));
+        assert_eq!(result.record.id, result.outputs[0].id);
+
+        // The consumed input plus the three outputs
+        assert_eq!(processor.total_records(), 4);
+    }
+
+    #[test]
+    fn test_dropped_split_children_are_counted() {
+        use crate::{
+            metrics::tests::capture_metrics,
+            transform::{FilterTransform, SplitTransform},
+        };
+        use serde_json::json;
+
+        let (result, metrics) = capture_metrics(async {
+            let processor = Processor::new(ProcessorConfig::default()).unwrap();
+            processor
+                .register_transform(Arc::new(SplitTransform::new("split", |r: Record| {
+                    (0..3).map(|i| Record::new(&r.key, json!({"n": i}))).collect()
+                })))
+                .unwrap();
+            processor
+                .register_transform(Arc::new(FilterTransform::new("odd", |r: &Record| {
+                    r.value["n"] != json!(1)
+                })))
+                .unwrap();
+            processor.process(Record::new("key", json!({}))).await.unwrap()
+        });
+
+        assert_eq!(result.outputs.len(), 2);
+        assert_eq!(metrics.counter("records_filtered_total", &[("stage", "odd")]), 1);
+
+        // When every child is dropped the input itself is reported as filtered
+        let ((result, processor, input_id), metrics) = capture_metrics(async {
+            let processor = Processor::new(ProcessorConfig::default()).unwrap();
+            processor
+                .register_transform(Arc::new(SplitTransform::new("split", |r: Record| {
+                    (0..3).map(|i| Record::new(&r.key, json!({"n": i}))).collect()
+                })))
+                .unwrap();
+            processor
+                .register_transform(Arc::new(FilterTransform::new("none", |_: &Record| false)))
+                .unwrap();
+            let input = Record::new("key", json!({}));
+            let input_id = input.id;
+            (processor.process(input).await.unwrap(), processor, input_id)
+        });
+        assert_eq!(metrics.counter("records_filtered_total", &[("stage", "none")]), 3);
+        assert!(result.filtered);
+        assert_eq!(result.record.id, input_id);
+        let stored = processor.get_record(&input_id).unwrap();
+        assert_eq!(stored.metadata.status, RecordStatus::Filtered);
+    }
+
+    #[tokio::test]
+    async fn test_process_stream_ignores_batch_limit() {
+        use futures::StreamExt;
+
+        let config = ProcessorConfig::builder()
+            .max_batch_size(10)
+            .buffer_size(4)
+            .build();
+        let processor = Processor::new(config).unwrap();
+
+        let records = futures::stream::iter(
+            (0..200).map(|i| Record::new(format!("key_{}", i), i)),
+        );
+        let results: Vec<_> = processor.process_stream(records).collect().await;
+
+        assert_eq!(results.len(), 200);
+        assert!(results.iter().all(|r| r.as_ref().unwrap().success));
+        assert_eq!(results[199].as_ref().unwrap().record.key, "key_199");
+    }
+
+    #[tokio::test]
+    async fn test_process_stream_applies_backpressure() {
+        use futures::StreamExt;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let config = ProcessorConfig::builder()
+            .max_workers(2)
+            .buffer_size(3)
+            .build();
+        let processor = Processor::new(config).unwrap();
+
+        let pulled = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&pulled);
+        let records = futures::stream::iter((0..).map(move |i| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Record::new(format!("key_{}", i), i)
+        }));
+
+        let mut results = processor.process_stream(records);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        // Only the result buffer plus in-flight records were pulled from an
+        // endless source while nothing was consumed
+        assert!(pulled.load(Ordering::SeqCst) <= 3 + 2 + 1);
+
+        let first = results.next().await.unwrap().unwrap();
+        assert_eq!(first.record.key, "key_0");
+    }
+
+    #[tokio::test]
+    async fn test_stream_channel() {
+        use futures::StreamExt;
+
+        let processor = Processor::new(ProcessorConfig::default()).unwrap();
+        let (tx, results) = processor.stream_channel();
+
+        tokio::spawn(async move {
+            for i in 0..5 {
+                tx.send(Record::new(format!("key_{}", i), i)).await.unwrap();
+            }
+        });
+
+        let results: Vec<_> = results.collect().await;
+        assert_eq!(results.len(), 5);
+    }
+
+    #[test]
+    fn test_metrics_follow_enable_metrics() {
+        use crate::{metrics::tests::capture_metrics, transform::FilterTransform};
+
+        let run = |enable_metrics: bool| {
+            capture_metrics(async move {
+                let config = ProcessorConfig::builder().enable_metrics(enable_metrics).build();
+                let processor = Processor::new(config).unwrap();
+                processor
+                    .register_transform(Arc::new(FilterTransform::new("non_empty", |r: &Record| {
+                        !r.key.is_empty()
+                    })))
+                    .unwrap();
+
+                let records = vec![Record::new("a", 1), Record::new("b", 2), Record::new("", 3)];
+                processor.process_batch(records).await.unwrap();
+            })
+            .1
+        };
+
+        let metrics = run(true);
+        assert_eq!(metrics.counter("records_processed_success", &[]), 2);
+        assert_eq!(metrics.counter("records_filtered_total", &[("stage", "non_empty")]), 1);
+        assert_eq!(
+            metrics.counter("transforms_applied_success", &[("transform", "non_empty")]),
+            3
+        );
+        assert_eq!(metrics.counter("batches_processed_total", &[]), 1);
+
+        let metrics = run(false);
+        assert_eq!(metrics.counter("records_processed_total", &[]), 0);
+        assert_eq!(metrics.counter("transforms_applied_total", &[]), 0);
+    }
+
+    #[derive(Debug)]
+    struct SlowTransform(Duration);
+
+    #[async_trait]
+    impl Transform for SlowTransform {
+        async fn transform(&self, record: Record) -> Result<Record> {
+            tokio::time::sleep(self.0).await;
+            Ok(record)
+        }
+
+        fn name(&self) -> &str {
+            "slow"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reload_applies_to_new_records_only() {
+        let mut config = fast_retry_config();
+        config.operation_timeout = Duration::from_secs(5);
+        let processor = Processor::new(config.clone()).unwrap();
+        processor
+            .register_transform(Arc::new(SlowTransform(Duration::from_millis(100))))
+            .unwrap();
+
+        let in_flight = tokio::spawn({
+            let processor = processor.clone();
+            async move { processor.process(Record::new("before", 1)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        config.operation_timeout = Duration::from_millis(20);
+        config.retry_config.max_attempts = 1;
+        processor.reload(config).unwrap();
+        assert_eq!(processor.config().operation_timeout, Duration::from_millis(20));
+
+        let result = processor.process(Record::new("after", 2)).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("timed out"));
+        assert_eq!(result.attempts, 1);
+
+        assert!(in_flight.await.unwrap().unwrap().success);
+    }
+
+    #[tokio::test]
+    async fn test_reload_resizes_worker_pool() {
+        let config = ProcessorConfig::builder().max_workers(4).build();
+        let processor = Processor::new(config.clone()).unwrap();
+        processor
+            .register_transform(Arc::new(SlowTransform(Duration::from_millis(100))))
+            .unwrap();
+        let permits = || processor.state.semaphore.available_permits();
+
+        let handles: Vec<_> = (0..2)
+            .map(|i| {
+                let processor = processor.clone();
+                tokio::spawn(async move { processor.process(Record::new("key", i)).await })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(permits(), 2);
+
+        // Shrinking retires the idle slots now and one busy slot once it is released
+        processor.reload(ProcessorConfig { max_workers: 1, ..config.clone() }).unwrap();
+        assert_eq!(permits(), 0);
+        // Growing first cancels the pending retirement
+        processor.reload(ProcessorConfig { max_workers: 2, ..config.clone() }).unwrap();
+        assert_eq!(permits(), 0);
+
+        for handle in handles {
+            assert!(handle.await.unwrap().unwrap().success);
+        }
+        assert_eq!(permits(), 2);
+
+        processor.reload(ProcessorConfig { max_workers: 5, ..config }).unwrap();
+        assert_eq!(permits(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_reload_bounds_existing_records() {
+        let config = ProcessorConfig::default();
+        let processor = Processor::new(config.clone()).unwrap();
+        for i in 0..5 {
+            processor.process(Record::new(format!("key_{}", i), i)).await.unwrap();
+        }
+        assert_eq!(processor.total_records(), 5);
+
+        let retention = RetentionConfig::default().max_records(2);
+        processor.reload(ProcessorConfig { record_retention: retention, ..config }).unwrap();
+        assert_eq!(processor.total_records(), 2);
+
+        // New work is kept and the records held before the reload are evicted
+        let result = processor.process(Record::new("after", 5)).await.unwrap();
+        assert_eq!(processor.total_records(), 2);
+        assert!(processor.get_record(&result.record.id).is_some());
+        let completed = processor.records_with_status(RecordStatus::Completed);
+        let keys: Vec<_> = completed.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(keys, ["key_4", "after"]);
+    }
+
+    #[tokio::test]
+    async fn test_reload_rejects_invalid_config() {
+        let config = ProcessorConfig::default();
+        let processor = Processor::new(config.clone()).unwrap();
+
+        let invalid = ProcessorConfig { max_workers: 0, ..config.clone() };
+        assert!(matches!(processor.reload(invalid), Err(Error::Config(_))));
+        let restart = ProcessorConfig { enable_metrics: false, ..config.clone() };
+        assert!(matches!(processor.reload(restart), Err(Error::Config(_))));
+        assert_eq!(processor.config().max_workers, config.max_workers);
+        assert_eq!(processor.state.semaphore.available_permits(), config.max_workers);
+
+        processor.reload(ProcessorConfig { max_batch_size: 2, ..config }).unwrap();
+        let records = vec![Record::new("a", 1), Record::new("b", 2), Record::new("c", 3)];
+        assert!(processor.process_batch(records).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_watch_config_reloads_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("edp.toml");
+        std::fs::write(&path, "max_workers = 2\n").unwrap();
+        let loader = ConfigLoader::new().file(&path).no_env();
+        let processor = Processor::new(loader.load().unwrap()).unwrap();
+
+        let watcher = processor.watch_config(loader, Duration::from_millis(10));
+        let wait_for = |max_workers: usize| {
+            let processor = processor.clone();
+            async move {
+                while processor.config().max_workers != max_workers {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            }
+        };
+
+        // Invalid changes are skipped
+        std::fs::write(&path, "max_workers = 0 # invalid\n").unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(processor.config().max_workers, 2);
+
+        std::fs::write(&path, "max_workers = 12\n").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), wait_for(12)).await.unwrap();
+        assert_eq!(processor.state.semaphore.available_permits(), 12);
+
+        // Same-length edits are detected too
+        std::fs::write(&path, "max_workers = 13\n").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), wait_for(13)).await.unwrap();
+
+        watcher.stop();
+        std::fs::write(&path, "max_workers = 3\n").unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(processor.config().max_workers, 13);
+    }
+
+    #[derive(Debug)]
+    struct PanickingTransform;
+
+    #[async_trait]
+    impl Transform for PanickingTransform {
+        async fn transform(&self, record: Record) -> Result<Record> {
+            assert_ne!(record.key, "boom", "transform blew up");
+            Ok(record)
+        }
+
+        fn name(&self) -> &str {
+            "panicking"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_records_are_dead_lettered_and_replayed() {
+        let queue = DeadLetterQueue::in_memory();
+        let processor = Processor::new(fast_retry_config())
+            .unwrap()
+            .with_dead_letter_queue(queue.clone());
+        processor
+            .register_transform(Arc::new(FlakyTransform {
+                failures_left: 10.into(),
+            }))
+            .unwrap();
+
+        let record = Record::new("key", "value");
+        let id = record.id;
+        assert!(!processor.process(record).await.unwrap().success);
+
+        let letter = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(letter.code, "TIMEOUT");
+        assert_eq!(letter.stage, "flaky");
+        assert_eq!(letter.attempts.len(), 3);
+        assert_eq!(letter.attempts[2].attempt, 3);
+        assert_eq!(letter.record.metadata.status, RecordStatus::Failed);
+
+        // Replaying while the transform still fails keeps the entry
+        processor
+            .replace_transform(Arc::new(FlakyTransform {
+                failures_left: 3.into(),
+            }))
+            .unwrap();
+        let results = processor.replay_dead_letters().await.unwrap();
+        assert!(!results[0].success);
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().record.metadata.failure_count, 6);
+
+        let result = processor.replay_dead_letter(&id).await.unwrap();
+        assert!(result.success);
+        assert!(queue.is_empty().await.unwrap());
+        assert!(matches!(
+            processor.replay_dead_letter(&id).await,
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    /// Fails records whose `n` is 1 until `rejecting` is cleared
+    #[derive(Debug)]
+    struct RejectOne {
+        rejecting: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait]
+    impl Transform for RejectOne {
+        async fn transform(&self, record: Record) -> Result<Record> {
+            let rejecting = self.rejecting.load(std::sync::atomic::Ordering::SeqCst);
+            if rejecting && record.value["n"] == serde_json::json!(1) {
+                return Err(Error::processing("n = 1 rejected"));
+            }
+            Ok(record)
+        }
+
+        fn name(&self) -> &str {
+            "reject_one"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_split_child_dead_letters_the_input() {
+        use crate::transform::SplitTransform;
+        use serde_json::json;
+
+        let queue = DeadLetterQueue::in_memory();
+        let processor = Processor::new(fast_retry_config())
+            .unwrap()
+            .with_dead_letter_queue(queue.clone());
+        processor
+            .register_transform(Arc::new(SplitTransform::new("split", |r: Record| {
+                (0..3).map(|i| Record::new(&r.key, json!({"n": i}))).collect()
+            })))
+            .unwrap();
+        let reject = Arc::new(RejectOne { rejecting: true.into() });
+        processor.register_transform(reject.clone()).unwrap();
+
+        let input = Record::new("order", json!({}));
+        let id = input.id;
+        let result = processor.process(input).await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.record.id, id);
+        assert_eq!(processor.get_record(&id).unwrap().metadata.status, RecordStatus::Failed);
+
+        // The whole input is dead-lettered, not just the failing child
+        assert_eq!(queue.len().await.unwrap(), 1);
+        let letter = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(letter.stage, "reject_one");
+        assert_eq!(letter.record.value, json!({}));
+        assert_eq!(letter.record.metadata.failure_count, 1);
+
+        // Replay splits the input again and outputs every child
+        reject.rejecting.store(false, std::sync::atomic::Ordering::SeqCst);
+        let result = processor.replay_dead_letter(&id).await.unwrap();
+        assert!(result.success);
+        let values: Vec<_> = result.outputs.iter().map(|r| r.value["n"].clone()).collect();
+        assert_eq!(values, [json!(0), json!(1), json!(2)]);
+        assert!(queue.is_empty().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_batch_task_panics_are_dead_lettered() {
+        let queue = DeadLetterQueue::in_memory();
+        let processor = Processor::new(ProcessorConfig::default())
+            .unwrap()
+            .with_dead_letter_queue(queue.clone());
+        processor.register_transform(Arc::new(PanickingTransform)).unwrap();
+
+        let boom = Record::new("boom", 1);
+        let id = boom.id;
+        let results = processor.process_batch(vec![boom, Record::new("fine", 2)]).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(!results[0].success);
+        assert_eq!(results[0].record.id, id);
+        assert!(results[0].error.as_deref().unwrap().contains("panicked"));
+        assert_eq!(processor.get_record(&id).unwrap().metadata.status, RecordStatus::Failed);
+        assert!(results[1].success);
+
+        let letter = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(letter.stage, "process_batch");
+        assert_eq!(letter.code, "PROCESSING_ERROR");
+        assert!(letter.error.contains("panicked"));
+    }
+
+    #[tokio::test]
+    async fn test_batch_task_panics_without_dead_letter_queue() {
+        let processor = Processor::new(ProcessorConfig::default()).unwrap();
+        processor.register_transform(Arc::new(PanickingTransform)).unwrap();
+
+        let records = vec![Record::new("fine", 1), Record::new("boom", 2)];
+        let results = processor.process_batch(records).await.unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].success);
+        assert!(!results[1].success);
+        assert_eq!(results[1].record.key, "boom");
+        assert_eq!(processor.active_tasks().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_record_is_no_longer_active() {
+        let processor = Processor::new(ProcessorConfig::default()).unwrap();
+        processor
+            .register_transform(Arc::new(SlowTransform(Duration::from_secs(5))))
+            .unwrap();
+
+        let processing = processor.process(Record::new("slow", 1));
+        assert!(tokio::time::timeout(Duration::from_millis(20), processing).await.is_err());
+        assert_eq!(processor.active_tasks().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_stream_task_panics_yield_errors() {
+        use futures::StreamExt;
+
+        let processor = Processor::new(ProcessorConfig::default()).unwrap();
+        processor.register_transform(Arc::new(PanickingTransform)).unwrap();
+
+        let records = vec![Record::new("fine", 1), Record::new("boom", 2), Record::new("ok", 3)];
+        let results: Vec<_> = processor
+            .process_stream(futures::stream::iter(records))
+            .collect()
+            .await;
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].as_ref().unwrap().success);
+        assert!(matches!(&results[1], Err(Error::Processing(msg)) if msg.contains("panicked")));
+        assert!(results[2].as_ref().unwrap().success);
+    }
+
+    #[tokio::test]
+    async fn test_replay_requires_dead_letter_queue() {
+        let processor = Processor::new(ProcessorConfig::default()).unwrap();
+        assert!(processor.dead_letter_queue().is_none());
+        assert!(matches!(
+            processor.replay_dead_letters().await,
+            Err(Error::InvalidState(_))
+        ));
+    }
+
+    #[derive(Debug)]
+    struct RejectBadKeys;
+
+    #[async_trait]
+    impl Transform for RejectBadKeys {
+        async fn transform(&self, record: Record) -> Result<Record> {
+            if record.key.starts_with("bad") {
+                return Err(Error::processing("bad key"));
+            }
+            Ok(record)
+        }
+
+        fn name(&self) -> &str {
+            "reject_bad_keys"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_retention_and_status_queries() {
+        let config = ProcessorConfig::builder()
+            .record_retention(RetentionConfig::default().max_records(3).drop_completed(true))
+            .build();
+        let processor = Processor::new(config).unwrap();
+        processor.register_transform(Arc::new(RejectBadKeys)).unwrap();
+
+        let results = processor
+            .process_batch((0..5).map(|i| Record::new(format!("ok_{}", i), i)).collect())
+            .await
+            .unwrap();
+        assert!(results.iter().all(|r| r.success));
+        // Completed records are dropped straight away
+        assert_eq!(processor.total_records(), 0);
+
+        for i in 0..5 {
+            processor.process(Record::new(format!("bad_{}", i), i)).await.unwrap();
+        }
+
+        // Only the three most recently updated records are kept
+        let failed = processor.records_with_status(RecordStatus::Failed);
+        let keys: Vec<_> = failed.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(keys, vec!["bad_2", "bad_3", "bad_4"]);
+        assert_eq!(processor.record_status_counts().get(&RecordStatus::Failed), Some(&3));
+        assert!(processor.records_with_status(RecordStatus::Processing).is_empty());
+    }
 }
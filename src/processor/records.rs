@@ -0,0 +1,242 @@
+//! Record state kept by a processor, bounded by a retention policy
+
+use crate::{
+    config::RetentionConfig,
+    record::{Record, RecordStatus},
+};
+use chrono::Utc;
+use dashmap::DashMap;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::atomic::{AtomicU64, Ordering},
+};
+use uuid::Uuid;
+
+/// Records by ID, evicted according to a [`RetentionConfig`]
+///
+/// Every insert is stamped with a sequence number and, when retention is
+/// bounded, queued in insertion order. Eviction pops from the front of the
+/// queue, skipping entries whose record has since been re-inserted, so the
+/// least recently updated records go first.
+#[derive(Debug, Default)]
+pub(super) struct RecordStore {
+    records: DashMap<Uuid, StoredRecord>,
+    order: parking_lot::Mutex<VecDeque<(Uuid, u64)>>,
+    next_seq: AtomicU64,
+}
+
+#[derive(Debug)]
+struct StoredRecord {
+    record: Record,
+    seq: u64,
+}
+
+impl RecordStore {
+    /// Insert or replace a record, then apply the retention limits
+    pub(super) fn insert(&self, record: Record, retention: &RetentionConfig) {
+        let finished = matches!(
+            record.metadata.status,
+            RecordStatus::Completed | RecordStatus::Filtered
+        );
+        if retention.drop_completed && finished {
+            self.records.remove(&record.id);
+            return;
+        }
+
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        let id = record.id;
+        if retention.is_bounded() {
+            // Queue before inserting so eviction never sees an unqueued record
+            let mut order = self.order.lock();
+            order.push_back((id, seq));
+            self.records.insert(id, StoredRecord { record, seq });
+            self.evict_locked(&mut order, retention);
+        } else {
+            self.records.insert(id, StoredRecord { record, seq });
+        }
+    }
+
+    /// Evict records beyond the retention limits
+    pub(super) fn prune(&self, retention: &RetentionConfig) {
+        if retention.is_bounded() {
+            self.evict_locked(&mut self.order.lock(), retention);
+        }
+    }
+
+    /// Apply new retention limits to the records already held
+    ///
+    /// Records stored while retention was unbounded were never queued, so
+    /// the queue is rebuilt from the stored records before evicting.
+    pub(super) fn set_retention(&self, retention: &RetentionConfig) {
+        let mut order = self.order.lock();
+        if retention.drop_completed {
+            self.records.retain(|_, stored| {
+                !matches!(
+                    stored.record.metadata.status,
+                    RecordStatus::Completed | RecordStatus::Filtered
+                )
+            });
+        }
+
+        order.clear();
+        if retention.is_bounded() {
+            let mut entries: Vec<_> =
+                self.records.iter().map(|stored| (*stored.key(), stored.seq)).collect();
+            entries.sort_by_key(|&(_, seq)| seq);
+            order.extend(entries);
+            self.evict_locked(&mut order, retention);
+        }
+    }
+
+    fn evict_locked(&self, order: &mut VecDeque<(Uuid, u64)>, retention: &RetentionConfig) {
+        let cutoff = retention
+            .max_age
+            .and_then(|age| chrono::Duration::from_std(age).ok())
+            .map(|age| Utc::now() - age);
+
+        while let Some(&(id, seq)) = order.front() {
+            // Entries for records re-inserted since are stale; a later entry tracks them
+            let updated_at = self
+                .records
+                .get(&id)
+                .filter(|stored| stored.seq == seq)
+                .map(|stored| stored.record.metadata.updated_at);
+
+            if let Some(updated_at) = updated_at {
+                let expired = cutoff.is_some_and(|cutoff| updated_at < cutoff);
+                let over = retention.max_records.is_some_and(|max| self.records.len() > max);
+                if !expired && !over {
+                    break;
+                }
+                self.records.remove_if(&id, |_, stored| stored.seq == seq);
+            }
+            order.pop_front();
+        }
+
+        if self.records.is_empty() {
+            order.clear();
+        }
+    }
+
+    pub(super) fn get(&self, id: &Uuid) -> Option<Record> {
+        self.records.get(id).map(|stored| stored.record.clone())
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub(super) fn clear(&self) {
+        let mut order = self.order.lock();
+        self.records.clear();
+        order.clear();
+    }
+
+    /// Records with the given status, least recently updated first
+    pub(super) fn with_status(&self, status: RecordStatus) -> Vec<Record> {
+        let mut matching: Vec<_> = self
+            .records
+            .iter()
+            .filter(|stored| stored.record.metadata.status == status)
+            .map(|stored| (stored.seq, stored.record.clone()))
+            .collect();
+        matching.sort_by_key(|(seq, _)| *seq);
+        matching.into_iter().map(|(_, record)| record).collect()
+    }
+
+    /// Number of records in each status
+    pub(super) fn status_counts(&self) -> HashMap<RecordStatus, usize> {
+        let mut counts = HashMap::new();
+        for stored in self.records.iter() {
+            *counts.entry(stored.record.metadata.status).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn record(key: &str, status: RecordStatus) -> Record {
+        let mut record = Record::new(key, 1);
+        record.metadata.status = status;
+        record
+    }
+
+    #[test]
+    fn test_max_records_evicts_least_recently_updated() {
+        let store = RecordStore::default();
+        let retention = RetentionConfig::default().max_records(2);
+
+        let a = record("a", RecordStatus::Processing);
+        let b = record("b", RecordStatus::Processing);
+        store.insert(a.clone(), &retention);
+        store.insert(b.clone(), &retention);
+        // Updating `a` makes `b` the oldest
+        store.insert(updated(&a, RecordStatus::Failed), &retention);
+        store.insert(record("c", RecordStatus::Pending), &retention);
+
+        assert_eq!(store.len(), 2);
+        assert!(store.get(&b.id).is_none());
+        assert_eq!(store.get(&a.id).unwrap().metadata.status, RecordStatus::Failed);
+        assert_eq!(store.order.lock().len(), 2);
+    }
+
+    #[test]
+    fn test_max_age_and_drop_completed() {
+        let store = RecordStore::default();
+        let retention = RetentionConfig::default().max_age(Duration::from_secs(60));
+
+        let mut stale = record("stale", RecordStatus::Failed);
+        stale.metadata.updated_at = Utc::now() - chrono::Duration::minutes(5);
+        store.insert(stale.clone(), &retention);
+        store.insert(record("fresh", RecordStatus::Failed), &retention);
+        assert!(store.get(&stale.id).is_none());
+        assert_eq!(store.len(), 1);
+
+        let retention = RetentionConfig::default().drop_completed(true);
+        let done = record("done", RecordStatus::Processing);
+        store.insert(done.clone(), &retention);
+        assert!(store.get(&done.id).is_some());
+        store.insert(updated(&done, RecordStatus::Completed), &retention);
+        assert!(store.get(&done.id).is_none());
+
+        let counts = store.status_counts();
+        assert_eq!(counts.get(&RecordStatus::Failed), Some(&1));
+        assert_eq!(store.with_status(RecordStatus::Failed)[0].key, "fresh");
+    }
+
+    #[test]
+    fn test_set_retention_bounds_records_stored_while_unbounded() {
+        let store = RecordStore::default();
+        let unbounded = RetentionConfig::default();
+        let records: Vec<_> =
+            (0..4).map(|i| record(&format!("key_{}", i), RecordStatus::Failed)).collect();
+        for record in &records {
+            store.insert(record.clone(), &unbounded);
+        }
+        store.insert(record("done", RecordStatus::Completed), &unbounded);
+
+        let bounded = RetentionConfig::default().max_records(2).drop_completed(true);
+        store.set_retention(&bounded);
+        assert_eq!(store.len(), 2);
+        assert!(store.get(&records[1].id).is_none());
+        assert!(store.get(&records[3].id).is_some());
+
+        // New records evict the oldest held records rather than themselves
+        let new = record("new", RecordStatus::Processing);
+        store.insert(new.clone(), &bounded);
+        assert_eq!(store.len(), 2);
+        assert!(store.get(&new.id).is_some());
+        assert!(store.get(&records[2].id).is_none());
+    }
+
+    fn updated(original: &Record, status: RecordStatus) -> Record {
+        let mut record = original.clone();
+        record.metadata.status = status;
+        record.metadata.updated_at = Utc::now();
+        record
+    }
+}
@@ -0,0 +1,383 @@
+//! Graceful shutdown: stop intake, drain in-flight records and flush outputs
+
+use super::Processor;
+use crate::{record::Record, sink::Sink, storage::Storage, Error, Result};
+use dashmap::DashMap;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+/// Outcome of [`Processor::shutdown`]
+#[derive(Debug)]
+pub struct ShutdownReport {
+    /// Whether every in-flight record finished before the deadline
+    pub drained: bool,
+
+    /// Records still in flight at the deadline, as they were when they started
+    pub abandoned: Vec<Record>,
+
+    /// Errors from flushing sinks and storage
+    pub flush_errors: Vec<Error>,
+
+    /// How long the shutdown took
+    pub elapsed: Duration,
+}
+
+impl ShutdownReport {
+    /// Whether all records drained and everything flushed without error
+    pub fn is_clean(&self) -> bool {
+        self.drained && self.flush_errors.is_empty()
+    }
+}
+
+/// Output flushed when the processor shuts down
+#[derive(Debug, Clone)]
+pub(super) enum FlushTarget {
+    Sink(Arc<dyn Sink>),
+    Storage(Arc<dyn Storage>),
+}
+
+impl FlushTarget {
+    async fn flush(&self) -> Result<()> {
+        match self {
+            FlushTarget::Sink(sink) => sink.close().await,
+            FlushTarget::Storage(storage) => storage.flush().await,
+        }
+    }
+}
+
+/// Records currently inside [`Processor::process`]
+#[derive(Debug, Default)]
+pub(super) struct InFlight {
+    closed: AtomicBool,
+    records: DashMap<u64, Record>,
+    next_ticket: AtomicU64,
+    drained: Notify,
+    abort: CancellationToken,
+}
+
+impl InFlight {
+    /// Track a record until the returned guard is dropped
+    ///
+    /// Fails once the processor is shutting down.
+    pub(super) fn enter(&self, record: &Record) -> Result<InFlightGuard<'_>> {
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        // Register before checking, so shutdown either sees the record or refuses it
+        self.records.insert(ticket, record.clone());
+        let guard = InFlightGuard {
+            tracker: self,
+            ticket,
+        };
+        if self.is_closed() {
+            return Err(Error::invalid_state("Processor is shutting down"));
+        }
+        Ok(guard)
+    }
+
+    pub(super) fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Resolves once shutdown gives up on in-flight records
+    pub(super) async fn aborted(&self) {
+        self.abort.cancelled().await
+    }
+
+    async fn wait_drained(&self) {
+        loop {
+            let notified = self.drained.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.records.is_empty() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Stops tracking a record when dropped
+#[derive(Debug)]
+pub(super) struct InFlightGuard<'a> {
+    tracker: &'a InFlight,
+    ticket: u64,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.tracker.records.remove(&self.ticket);
+        if self.tracker.records.is_empty() {
+            self.tracker.drained.notify_waiters();
+        }
+    }
+}
+
+/// Error returned for records abandoned at shutdown
+pub(super) fn abandoned() -> Error {
+    Error::invalid_state("Record abandoned at shutdown")
+}
+
+/// Resolve when the process receives SIGTERM or Ctrl-C
+///
+/// On non-Unix platforms only Ctrl-C is observed.
+pub async fn shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            _ = terminate.recv() => Ok(()),
+            result = tokio::signal::ctrl_c() => Ok(result?),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        Ok(tokio::signal::ctrl_c().await?)
+    }
+}
+
+impl Processor {
+    /// Close this sink when the processor shuts down
+    pub fn with_shutdown_sink(mut self, sink: Arc<dyn Sink>) -> Self {
+        Arc::make_mut(&mut self.flush_targets).push(FlushTarget::Sink(sink));
+        self
+    }
+
+    /// Flush this storage when the processor shuts down
+    pub fn with_shutdown_storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        Arc::make_mut(&mut self.flush_targets).push(FlushTarget::Storage(storage));
+        self
+    }
+
+    /// Whether [`shutdown`](Self::shutdown) has been called
+    pub fn is_shutting_down(&self) -> bool {
+        self.state.in_flight.is_closed()
+    }
+
+    /// Number of records currently being processed or waiting for a worker
+    pub fn in_flight(&self) -> usize {
+        self.state.in_flight.len()
+    }
+
+    /// Shut the processor down gracefully
+    ///
+    /// New records are refused straight away. Records already in flight get
+    /// until `timeout` to finish; any still running are then abandoned, fail
+    /// with [`Error::InvalidState`] and are listed in the report. Finally the
+    /// registered sinks are closed and storage (including the dead-letter
+    /// queue) is flushed. Calling this again re-runs the drain and flush.
+    pub async fn shutdown(&self, timeout: Duration) -> ShutdownReport {
+        let started = Instant::now();
+        let in_flight = &self.state.in_flight;
+        in_flight.closed.store(true, Ordering::SeqCst);
+        info!(
+            in_flight = in_flight.len(),
+            timeout_ms = timeout.as_millis() as u64,
+            "Shutting down processor"
+        );
+
+        let drained = tokio::time::timeout(timeout, in_flight.wait_drained())
+            .await
+            .is_ok();
+        let abandoned: Vec<Record> = if drained {
+            Vec::new()
+        } else {
+            let records = in_flight.records.iter().map(|r| r.value().clone()).collect();
+            in_flight.abort.cancel();
+            records
+        };
+        if !abandoned.is_empty() {
+            warn!(count = abandoned.len(), "Abandoned records still in flight at shutdown");
+        }
+
+        let mut flush_errors = Vec::new();
+        for target in self.flush_targets.iter() {
+            if let Err(e) = target.flush().await {
+                error!(error = %e, "Failed to flush output at shutdown");
+                flush_errors.push(e);
+            }
+        }
+        if let Some(queue) = &self.dead_letters {
+            if let Err(e) = queue.flush().await {
+                error!(error = %e, "Failed to flush dead-letter queue at shutdown");
+                flush_errors.push(e);
+            }
+        }
+
+        let elapsed = started.elapsed();
+        info!(
+            drained,
+            abandoned = abandoned.len(),
+            flush_errors = flush_errors.len(),
+            elapsed_ms = elapsed.as_millis() as u64,
+            "Processor shut down"
+        );
+        ShutdownReport {
+            drained,
+            abandoned,
+            flush_errors,
+            elapsed,
+        }
+    }
+
+    /// Shut down once `token` is cancelled
+    pub async fn shutdown_on_cancel(
+        &self,
+        token: CancellationToken,
+        timeout: Duration,
+    ) -> ShutdownReport {
+        token.cancelled().await;
+        self.shutdown(timeout).await
+    }
+
+    /// Shut down once the process receives SIGTERM or Ctrl-C
+    ///
+    /// See [`shutdown_signal`]. Fails only if the signal handler cannot be
+    /// installed.
+    pub async fn shutdown_on_signal(&self, timeout: Duration) -> Result<ShutdownReport> {
+        shutdown_signal().await?;
+        Ok(self.shutdown(timeout).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        processor::{ProcessingResult, Transform},
+        ProcessorConfig,
+    };
+    use async_trait::async_trait;
+    use std::sync::atomic::AtomicU32;
+
+    #[derive(Debug)]
+    struct Sleep(Duration);
+
+    #[async_trait]
+    impl Transform for Sleep {
+        async fn transform(&self, record: Record) -> Result<Record> {
+            tokio::time::sleep(self.0).await;
+            Ok(record)
+        }
+
+        fn name(&self) -> &str {
+            "sleep"
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct ClosingSink {
+        closed: AtomicU32,
+    }
+
+    #[async_trait]
+    impl Sink for ClosingSink {
+        async fn write(&self, _record: &Record) -> Result<()> {
+            Ok(())
+        }
+
+        async fn flush(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn close(&self) -> Result<()> {
+            self.closed.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn name(&self) -> &str {
+            "closing"
+        }
+    }
+
+    fn processor(delay: Duration) -> Processor {
+        let processor = Processor::new(ProcessorConfig::builder().max_workers(2).build()).unwrap();
+        processor.register_transform(Arc::new(Sleep(delay))).unwrap();
+        processor
+    }
+
+    fn spawn_records(
+        processor: &Processor,
+        count: usize,
+    ) -> Vec<tokio::task::JoinHandle<Result<ProcessingResult>>> {
+        (0..count)
+            .map(|i| {
+                let processor = processor.clone();
+                let record = Record::new(format!("r{}", i), i);
+                tokio::spawn(async move { processor.process(record).await })
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_in_flight_records() {
+        let sink = Arc::new(ClosingSink::default());
+        let processor = processor(Duration::from_millis(50)).with_shutdown_sink(sink.clone());
+        // Three records with two workers: one is still waiting for a slot
+        let handles = spawn_records(&processor, 3);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(processor.in_flight(), 3);
+
+        let report = processor.shutdown(Duration::from_secs(5)).await;
+        assert!(report.is_clean());
+        assert!(report.abandoned.is_empty());
+        assert_eq!(sink.closed.load(Ordering::SeqCst), 1);
+        for handle in handles {
+            assert!(handle.await.unwrap().unwrap().success);
+        }
+
+        assert!(processor.is_shutting_down());
+        let refused = processor.process(Record::new("late", 0)).await;
+        assert!(matches!(refused, Err(Error::InvalidState(_))));
+        assert!(processor.process_batch(vec![Record::new("late", 0)]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_abandons_records_after_deadline() {
+        let processor = processor(Duration::from_secs(60));
+        let handles = spawn_records(&processor, 3);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let report = processor.shutdown(Duration::from_millis(20)).await;
+        assert!(!report.drained);
+        let mut keys: Vec<_> = report.abandoned.iter().map(|r| r.key.clone()).collect();
+        keys.sort();
+        assert_eq!(keys, vec!["r0", "r1", "r2"]);
+
+        for handle in handles {
+            let result = tokio::time::timeout(Duration::from_secs(1), handle).await.unwrap();
+            assert!(matches!(result.unwrap(), Err(Error::InvalidState(_))));
+        }
+        assert_eq!(processor.in_flight(), 0);
+        assert_eq!(processor.active_tasks().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_on_cancel() {
+        let processor = processor(Duration::from_millis(1));
+        let token = CancellationToken::new();
+        let shutdown = tokio::spawn({
+            let processor = processor.clone();
+            let token = token.clone();
+            async move { processor.shutdown_on_cancel(token, Duration::from_secs(1)).await }
+        });
+
+        assert!(processor.process(Record::new("before", 1)).await.unwrap().success);
+        token.cancel();
+        assert!(shutdown.await.unwrap().is_clean());
+        assert!(processor.process(Record::new("after", 2)).await.is_err());
+    }
+}
@@ -0,0 +1,77 @@
+//! Polling watcher that reloads a processor's configuration files
+
+use super::Processor;
+use crate::config::ConfigLoader;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::Path,
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Hash of a watched file's contents, if it exists
+///
+/// Contents are compared rather than modification times, which miss
+/// same-length edits made within the filesystem's timestamp granularity.
+type FileStamp = Option<u64>;
+
+/// Handle to a task reloading a [`Processor`] when its config files change
+///
+/// Created by [`Processor::watch_config`]. Watching stops when the handle is
+/// dropped.
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    task: JoinHandle<()>,
+}
+
+impl ConfigWatcher {
+    pub(super) fn spawn(processor: Processor, loader: ConfigLoader, interval: Duration) -> Self {
+        let task = tokio::spawn(async move {
+            let mut stamps = file_stamps(&loader).await;
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            ticker.tick().await;
+            
+            loop {
+                ticker.tick().await;
+                let current = file_stamps(&loader).await;
+                if current == stamps {
+                    continue;
+                }
+                stamps = current;
+                
+                match loader.load().and_then(|config| processor.reload(config)) {
+                    Ok(()) => info!("Applied changed configuration files"),
+                    Err(e) => warn!(error = %e, "Ignoring invalid configuration change"),
+                }
+            }
+        });
+        Self { task }
+    }
+
+    /// Stop watching
+    pub fn stop(self) {}
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn file_stamps(loader: &ConfigLoader) -> Vec<FileStamp> {
+    let mut stamps = Vec::new();
+    for path in loader.paths() {
+        stamps.push(file_stamp(path).await);
+    }
+    stamps
+}
+
+async fn file_stamp(path: &Path) -> FileStamp {
+    let contents = tokio::fs::read(path).await.ok()?;
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    Some(hasher.finish())
+}
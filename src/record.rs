@@ -1,5 +1,6 @@
 //! Core data record structure
 
+use crate::error::ValidationReport;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -37,6 +38,9 @@ pub struct RecordMetadata {
     pub source: String,
     
     /// Version number for optimistic locking
+    ///
+    /// Bumped by [`Record::update_value`] and by
+    /// [`Storage::update_if_version`](crate::storage::Storage::update_if_version).
     pub version: u64,
     
     /// Processing status
@@ -50,10 +54,14 @@ pub struct RecordMetadata {
     
     /// Last error message, if any
     pub last_error: Option<String>,
+    
+    /// Report attached by a reporting validation stage, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation: Option<Box<ValidationReport>>,
 }
 
 /// Processing status of a record
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum RecordStatus {
     /// Record is pending processing
     Pending,
@@ -67,6 +75,9 @@ pub enum RecordStatus {
     /// Record processing failed
     Failed,
     
+    /// Record was filtered out by a transform or stage
+    Filtered,
+    
     /// Record has been archived
     Archived,
 }
@@ -89,6 +100,7 @@ impl Record {
                 process_count: 0,
                 failure_count: 0,
                 last_error: None,
+                validation: None,
             },
             tags: HashMap::new(),
         }
@@ -114,15 +126,30 @@ impl Record {
     }
 
     /// Mark the record as completed
+    ///
+    /// `failure_count` and `last_error` are kept, so a record that succeeded
+    /// after retries still shows its failed attempts.
     pub fn mark_completed(&mut self) {
         self.metadata.status = RecordStatus::Completed;
         self.metadata.updated_at = Utc::now();
-        self.metadata.last_error = None;
+    }
+
+    /// Mark the record as filtered out
+    ///
+    /// Like [`mark_completed`](Self::mark_completed), this keeps the failure history.
+    pub fn mark_filtered(&mut self) {
+        self.metadata.status = RecordStatus::Filtered;
+        self.metadata.updated_at = Utc::now();
     }
 
     /// Mark the record as failed
     pub fn mark_failed(&mut self, error: impl Into<String>) {
         self.metadata.status = RecordStatus::Failed;
+        self.record_failure(error);
+    }
+
+    /// Record a failed attempt without changing the processing status
+    pub fn record_failure(&mut self, error: impl Into<String>) {
         self.metadata.updated_at = Utc::now();
         self.metadata.failure_count += 1;
         self.metadata.last_error = Some(error.into());
@@ -231,6 +258,7 @@ impl RecordBuilder {
                 process_count: 0,
                 failure_count: 0,
                 last_error: None,
+                validation: None,
             },
             tags: self.tags,
         })
@@ -283,6 +311,11 @@ mod tests {
         record.mark_failed("test error");
         assert_eq!(record.metadata.status, RecordStatus::Failed);
         assert_eq!(record.metadata.failure_count, 1);
+        
+        // A later success keeps the failure history
+        record.mark_completed();
+        assert_eq!(record.metadata.failure_count, 1);
+        assert_eq!(record.metadata.last_error.as_deref(), Some("test error"));
     }
 
     #[test]
@@ -0,0 +1,272 @@
+//! Retry policy for record operations
+
+use crate::{config::RetryConfig, deadline::Deadline, record::Record, Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use tracing::{debug, warn};
+
+/// Re-runs failing record operations according to a [`RetryConfig`]
+///
+/// Only errors for which [`Error::is_retryable`](crate::Error::is_retryable)
+/// returns `true` are retried.
+/// Every failed attempt is recorded on the record via
+/// [`Record::record_failure`], so `failure_count` and `last_error` reflect
+/// the full attempt history.
+#[derive(Debug, Clone, Default)]
+pub struct RetryPolicy {
+    config: RetryConfig,
+}
+
+/// Outcome of running an operation through a [`RetryPolicy`]
+#[derive(Debug)]
+pub struct RetryOutcome<T = Record> {
+    /// The input record with any failed attempts recorded in its metadata
+    pub record: Record,
+
+    /// Number of attempts made
+    pub attempts: u32,
+
+    /// Every failed attempt, oldest first
+    pub failures: Vec<FailedAttempt>,
+
+    /// Result of the final attempt
+    pub result: Result<T>,
+}
+
+/// A failed attempt at a record operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FailedAttempt {
+    /// Attempt number, starting at 1
+    pub attempt: u32,
+
+    /// Error code, from [`Error::code`]
+    pub code: String,
+
+    /// Error message
+    pub error: String,
+
+    /// When the attempt failed
+    pub failed_at: DateTime<Utc>,
+}
+
+impl FailedAttempt {
+    /// Describe a failed attempt
+    pub fn new(attempt: u32, error: &Error) -> Self {
+        Self {
+            attempt,
+            code: error.code().to_string(),
+            error: error.to_string(),
+            failed_at: Utc::now(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a new retry policy
+    pub fn new(config: RetryConfig) -> Self {
+        Self { config }
+    }
+
+    /// Create a policy that makes a single attempt and never retries
+    pub fn no_retry() -> Self {
+        Self {
+            config: RetryConfig {
+                max_attempts: 1,
+                ..RetryConfig::default()
+            },
+        }
+    }
+
+    /// Get the underlying retry configuration
+    pub fn config(&self) -> &RetryConfig {
+        &self.config
+    }
+
+    /// Run an operation on a record, retrying retryable errors
+    ///
+    /// `operation` names the transform or stage in logs and error history.
+    /// The operation receives a fresh clone of the record on every attempt.
+    pub async fn execute<T, F, Fut>(
+        &self,
+        operation: &str,
+        record: Record,
+        op: F,
+    ) -> RetryOutcome<T>
+    where
+        F: FnMut(Record) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.execute_within(operation, record, None, op).await
+    }
+
+    /// Run an operation on a record, retrying only while the deadline allows
+    ///
+    /// No retry is scheduled if its backoff would outlast the deadline; the
+    /// error from the last attempt is returned instead.
+    pub async fn execute_within<T, F, Fut>(
+        &self,
+        operation: &str,
+        mut record: Record,
+        deadline: Option<Deadline>,
+        mut op: F,
+    ) -> RetryOutcome<T>
+    where
+        F: FnMut(Record) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let max_attempts = self.config.max_attempts.max(1);
+        let mut attempt = 0;
+        let mut failures = Vec::new();
+
+        loop {
+            attempt += 1;
+
+            let err = match op(record.clone()).await {
+                Ok(output) => {
+                    return RetryOutcome {
+                        record,
+                        attempts: attempt,
+                        failures,
+                        result: Ok(output),
+                    }
+                }
+                Err(e) => e,
+            };
+
+            record.record_failure(format!("{}: {}", operation, err));
+            failures.push(FailedAttempt::new(attempt, &err));
+
+            if !err.is_retryable() || attempt >= max_attempts {
+                return RetryOutcome {
+                    record,
+                    attempts: attempt,
+                    failures,
+                    result: Err(err),
+                };
+            }
+
+            let backoff = self.config.calculate_backoff_with_jitter(attempt - 1);
+            if deadline.is_some_and(|d| d.remaining() <= backoff) {
+                debug!(record_id = %record.id, operation, attempt, "Deadline too close to retry");
+                return RetryOutcome {
+                    record,
+                    attempts: attempt,
+                    failures,
+                    result: Err(err),
+                };
+            }
+
+            warn!(
+                record_id = %record.id,
+                operation,
+                attempt,
+                max_attempts,
+                backoff_ms = backoff.as_millis() as u64,
+                error = %err,
+                "Retryable error, backing off"
+            );
+
+            tokio::time::sleep(backoff).await;
+            debug!(record_id = %record.id, operation, attempt = attempt + 1, "Retrying");
+        }
+    }
+}
+
+impl<T> RetryOutcome<T> {
+    /// Convert the outcome into a plain result
+    pub fn into_result(self) -> Result<T> {
+        self.result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use std::{
+        sync::atomic::{AtomicU32, Ordering},
+        time::Duration,
+    };
+
+    fn fast_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy::new(RetryConfig {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+            backoff_multiplier: 2.0,
+            jitter: 0.1,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let policy = fast_policy(3);
+        let calls = AtomicU32::new(0);
+
+        let outcome = policy
+            .execute("flaky", Record::new("test", "value"), |record| {
+                let call = calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if call < 2 {
+                        Err(Error::timeout("slow"))
+                    } else {
+                        Ok(record)
+                    }
+                }
+            })
+            .await;
+
+        assert_eq!(outcome.attempts, 3);
+        assert_eq!(outcome.failures.len(), 2);
+        assert_eq!(outcome.failures[1].attempt, 2);
+        assert_eq!(outcome.failures[1].code, "TIMEOUT");
+        let record = outcome.into_result().unwrap();
+        assert_eq!(record.metadata.failure_count, 2);
+        assert!(record.metadata.last_error.unwrap().contains("flaky"));
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_error_fails_fast() {
+        let policy = fast_policy(5);
+
+        let outcome = policy
+            .execute("broken", Record::new("test", "value"), |_| async {
+                Err::<Record, _>(Error::processing("bad input"))
+            })
+            .await;
+
+        assert_eq!(outcome.attempts, 1);
+        assert_eq!(outcome.record.metadata.failure_count, 1);
+        assert!(outcome.into_result().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retries_exhausted() {
+        let policy = fast_policy(2);
+
+        let outcome = policy
+            .execute("always_slow", Record::new("test", "value"), |_| async {
+                Err::<Record, _>(Error::timeout("slow"))
+            })
+            .await;
+
+        assert_eq!(outcome.attempts, 2);
+        assert_eq!(outcome.record.metadata.failure_count, 2);
+        assert!(matches!(outcome.result, Err(Error::Timeout(_))));
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_past_deadline() {
+        let policy = fast_policy(5);
+        let deadline = Deadline::after(Duration::ZERO);
+
+        let outcome = policy
+            .execute_within("slow", Record::new("test", "value"), Some(deadline), |_| async {
+                Err::<Record, _>(Error::timeout("slow"))
+            })
+            .await;
+
+        assert_eq!(outcome.attempts, 1);
+    }
+}
@@ -0,0 +1,647 @@
+//! Record sinks for writing output
+//!
+//! A [`Sink`] is a write-only destination for records. The built-in
+//! [`JsonLinesSink`] and [`CsvSink`] write into a directory, rolling over to a
+//! new file by size or age. Files are written under an `.inprogress` suffix
+//! and only renamed to their final name once complete, so readers never see
+//! a partially written file. Files can be compressed as they are written,
+//! which adds a `.gz` or `.zst` extension to their names.
+
+use crate::{
+    compression::{Compression, FileWriter},
+    metrics::MetricsRecorder,
+    record::Record,
+    Error, Result,
+};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+/// Suffix of files that are still being written
+pub const IN_PROGRESS_SUFFIX: &str = "inprogress";
+
+/// Trait for record sinks
+#[async_trait]
+pub trait Sink: Send + Sync + fmt::Debug {
+    /// Write a record
+    async fn write(&self, record: &Record) -> Result<()>;
+
+    /// Write several records
+    async fn write_batch(&self, records: &[Record]) -> Result<()> {
+        for record in records {
+            self.write(record).await?;
+        }
+        Ok(())
+    }
+
+    /// Flush buffered output to durable storage
+    async fn flush(&self) -> Result<()>;
+
+    /// Flush and finalise all output
+    ///
+    /// Call this on shutdown. Finalising needs async I/O, so dropping a sink
+    /// without closing it leaves its current output unfinalised.
+    async fn close(&self) -> Result<()>;
+
+    /// Name of the sink
+    fn name(&self) -> &str;
+}
+
+/// When a rolling sink starts a new file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RollingPolicy {
+    /// Roll once a file reaches this many bytes
+    pub max_bytes: Option<u64>,
+
+    /// Roll once a file has been open this long
+    pub max_age: Option<Duration>,
+}
+
+impl RollingPolicy {
+    /// Create a policy that never rolls
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Roll once a file reaches the given size
+    ///
+    /// The record that crosses the limit is still written to the old file.
+    pub fn max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Roll once a file has been open for the given duration
+    ///
+    /// Files are finalised when they expire even if nothing is written.
+    pub fn max_age(mut self, age: Duration) -> Self {
+        self.max_age = Some(age);
+        self
+    }
+
+    fn size_exceeded(&self, bytes: u64) -> bool {
+        self.max_bytes.is_some_and(|max| bytes >= max)
+    }
+
+    fn age_exceeded(&self, opened_at: Instant) -> bool {
+        self.max_age.is_some_and(|max| opened_at.elapsed() >= max)
+    }
+}
+
+/// A file that is currently being written
+#[derive(Debug)]
+struct OpenFile {
+    writer: FileWriter,
+    temp_path: PathBuf,
+    final_path: PathBuf,
+    sequence: u64,
+    bytes: u64,
+    opened_at: Instant,
+}
+
+/// Files of a rolling writer, shared with its age timers
+#[derive(Debug, Default)]
+struct RollingState {
+    sequence: u64,
+    current: Option<OpenFile>,
+    completed: Vec<PathBuf>,
+}
+
+impl RollingState {
+    /// Sync the current file and rename it to its final name
+    async fn finalize(&mut self, metrics: &MetricsRecorder) -> Result<()> {
+        let Some(open) = self.current.take() else {
+            return Ok(());
+        };
+
+        let codec = open.writer.codec();
+        let file_bytes = open.writer.finish().await?;
+        tokio::fs::rename(&open.temp_path, &open.final_path).await?;
+        if let Some(codec) = codec {
+            metrics.record_compression(codec.name(), open.bytes, file_bytes);
+        }
+
+        info!(file = %open.final_path.display(), bytes = open.bytes, "Finalised sink file");
+        self.completed.push(open.final_path);
+        Ok(())
+    }
+}
+
+/// Writes encoded rows into a directory of rolling files
+///
+/// With a maximum age, a timer finalises each file once it expires, so an
+/// idle sink still rolls. The timer stops when the writer is dropped.
+#[derive(Debug)]
+struct RollingWriter {
+    dir: PathBuf,
+    prefix: String,
+    extension: &'static str,
+    policy: RollingPolicy,
+    compression: Option<Compression>,
+    metrics: MetricsRecorder,
+    state: Arc<Mutex<RollingState>>,
+}
+
+impl RollingWriter {
+    fn new(dir: PathBuf, prefix: String, extension: &'static str) -> Self {
+        Self {
+            dir,
+            prefix,
+            extension,
+            policy: RollingPolicy::default(),
+            compression: None,
+            metrics: MetricsRecorder::default(),
+            state: Arc::default(),
+        }
+    }
+
+    /// Append one encoded row, opening a new file first if needed
+    ///
+    /// `header` is only called when a file is opened; its bytes start the file.
+    async fn append(
+        &self,
+        row: &[u8],
+        header: impl FnOnce() -> Result<Option<Vec<u8>>> + Send,
+    ) -> Result<()> {
+        let mut state = self.state.lock().await;
+        self.roll_if_expired(&mut state).await?;
+        if state.current.is_none() {
+            self.open(&mut state, header()?.as_deref()).await?;
+        }
+
+        let Some(open) = state.current.as_mut() else {
+            return Err(Error::invalid_state("No sink file is open"));
+        };
+        open.writer.write_all(row).await?;
+        open.bytes += row.len() as u64;
+
+        if self.policy.size_exceeded(open.bytes) {
+            state.finalize(&self.metrics).await?;
+        }
+        Ok(())
+    }
+
+    /// Open a new file, writing `header` first if given
+    async fn open(&self, state: &mut RollingState, header: Option<&[u8]>) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+
+        state.sequence += 1;
+        let mut name = format!(
+            "{}-{}-{:06}.{}",
+            self.prefix,
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%3f"),
+            state.sequence,
+            self.extension
+        );
+        if let Some(compression) = &self.compression {
+            name = format!("{}.{}", name, compression.codec.extension());
+        }
+        let final_path = self.dir.join(&name);
+        let temp_path = self.dir.join(format!("{}.{}", name, IN_PROGRESS_SUFFIX));
+
+        let mut open = OpenFile {
+            writer: FileWriter::create(&temp_path, self.compression).await?,
+            temp_path,
+            final_path,
+            sequence: state.sequence,
+            bytes: 0,
+            opened_at: Instant::now(),
+        };
+
+        if let Some(header) = header {
+            open.writer.write_all(header).await?;
+            open.bytes += header.len() as u64;
+        }
+
+        debug!(file = %open.temp_path.display(), "Opened sink file");
+        state.current = Some(open);
+        if let Some(max_age) = self.policy.max_age {
+            self.spawn_age_timer(state.sequence, max_age);
+        }
+        Ok(())
+    }
+
+    /// Finalise file `sequence` once it reaches `max_age`, unless it rolled already
+    fn spawn_age_timer(&self, sequence: u64, max_age: Duration) {
+        let state = Arc::downgrade(&self.state);
+        let metrics = self.metrics.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(max_age).await;
+            let Some(state) = state.upgrade() else {
+                return;
+            };
+            let mut state = state.lock().await;
+            if state.current.as_ref().is_some_and(|open| open.sequence == sequence) {
+                if let Err(e) = state.finalize(&metrics).await {
+                    warn!(error = %e, "Failed to roll expired sink file");
+                }
+            }
+        });
+    }
+
+    /// Finalise the current file if it is older than the rolling policy allows
+    async fn roll_if_expired(&self, state: &mut RollingState) -> Result<()> {
+        let expired = state
+            .current
+            .as_ref()
+            .is_some_and(|open| self.policy.age_exceeded(open.opened_at));
+        if expired {
+            state.finalize(&self.metrics).await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        self.roll_if_expired(&mut state).await?;
+        if let Some(open) = state.current.as_mut() {
+            open.writer.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn finalize(&self) -> Result<()> {
+        self.state.lock().await.finalize(&self.metrics).await
+    }
+
+    async fn completed(&self) -> Vec<PathBuf> {
+        self.state.lock().await.completed.clone()
+    }
+}
+
+impl Drop for RollingWriter {
+    fn drop(&mut self) {
+        // Finalising needs async I/O, so an unclosed file can only be reported
+        if let Ok(state) = self.state.try_lock() {
+            if let Some(open) = &state.current {
+                warn!(
+                    file = %open.temp_path.display(),
+                    "Sink dropped without close; file left in progress"
+                );
+            }
+        }
+    }
+}
+
+/// Sink writing newline-delimited JSON (JSON Lines)
+///
+/// By default each line holds the record value; with
+/// [`include_metadata`](Self::include_metadata) the whole record is written.
+#[derive(Debug)]
+pub struct JsonLinesSink {
+    name: String,
+    include_metadata: bool,
+    writer: RollingWriter,
+}
+
+impl JsonLinesSink {
+    /// Create a sink writing `<prefix>-<timestamp>-<sequence>.ndjson` files into `dir`
+    pub fn new(dir: impl Into<PathBuf>, prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+        Self {
+            name: format!("ndjson:{}", prefix),
+            include_metadata: false,
+            writer: RollingWriter::new(dir.into(), prefix, "ndjson"),
+        }
+    }
+
+    /// Set the rolling policy
+    pub fn rolling(mut self, policy: RollingPolicy) -> Self {
+        self.writer.policy = policy;
+        self
+    }
+
+    /// Write whole records, including key and metadata, instead of values
+    pub fn include_metadata(mut self, include: bool) -> Self {
+        self.include_metadata = include;
+        self
+    }
+
+    /// Compress files as they are written, adding the codec's extension
+    ///
+    /// [`RollingPolicy::max_bytes`] still counts uncompressed bytes.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.writer.compression = Some(compression);
+        self
+    }
+
+    /// Set the metrics recorder that receives compression statistics
+    pub fn with_metrics(mut self, metrics: MetricsRecorder) -> Self {
+        self.writer.metrics = metrics;
+        self
+    }
+
+    /// Files that have been finalised so far
+    pub async fn completed_files(&self) -> Vec<PathBuf> {
+        self.writer.completed().await
+    }
+}
+
+#[async_trait]
+impl Sink for JsonLinesSink {
+    async fn write(&self, record: &Record) -> Result<()> {
+        let mut line = if self.include_metadata {
+            serde_json::to_vec(record)?
+        } else {
+            serde_json::to_vec(&record.value)?
+        };
+        line.push(b'\n');
+
+        self.writer.append(&line, || Ok(None)).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.writer.flush().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.writer.finalize().await
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Sink writing comma-separated values
+///
+/// Each record value must be a JSON object. Columns are either configured up
+/// front or taken from the first record written; fields not in the column
+/// list are ignored and missing fields are left empty. Nested arrays and
+/// objects are written as JSON text. Every file starts with a header row.
+#[derive(Debug)]
+pub struct CsvSink {
+    name: String,
+    delimiter: u8,
+    columns: OnceLock<Vec<String>>,
+    writer: RollingWriter,
+}
+
+impl CsvSink {
+    /// Create a sink writing `<prefix>-<timestamp>-<sequence>.csv` files into `dir`
+    pub fn new(dir: impl Into<PathBuf>, prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+        Self {
+            name: format!("csv:{}", prefix),
+            delimiter: b',',
+            columns: OnceLock::new(),
+            writer: RollingWriter::new(dir.into(), prefix, "csv"),
+        }
+    }
+
+    /// Set the rolling policy
+    pub fn rolling(mut self, policy: RollingPolicy) -> Self {
+        self.writer.policy = policy;
+        self
+    }
+
+    /// Set the column names and order
+    pub fn columns(mut self, columns: Vec<String>) -> Self {
+        self.columns = OnceLock::from(columns);
+        self
+    }
+
+    /// Set the field delimiter
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Compress files as they are written, adding the codec's extension
+    ///
+    /// [`RollingPolicy::max_bytes`] still counts uncompressed bytes.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.writer.compression = Some(compression);
+        self
+    }
+
+    /// Set the metrics recorder that receives compression statistics
+    pub fn with_metrics(mut self, metrics: MetricsRecorder) -> Self {
+        self.writer.metrics = metrics;
+        self
+    }
+
+    /// Files that have been finalised so far
+    pub async fn completed_files(&self) -> Vec<PathBuf> {
+        self.writer.completed().await
+    }
+
+    fn encode<I, T>(&self, fields: I) -> Result<Vec<u8>>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .from_writer(Vec::new());
+        writer
+            .write_record(fields)
+            .map_err(|e| Error::processing(format!("CSV encoding failed: {}", e)))?;
+        writer
+            .into_inner()
+            .map_err(|e| Error::processing(format!("CSV encoding failed: {}", e)))
+    }
+}
+
+#[async_trait]
+impl Sink for CsvSink {
+    async fn write(&self, record: &Record) -> Result<()> {
+        let Value::Object(object) = &record.value else {
+            return Err(Error::processing(format!(
+                "Record {} cannot be written as CSV: value is not an object",
+                record.id
+            )));
+        };
+
+        let columns = self.columns.get_or_init(|| object.keys().cloned().collect());
+        let row = self.encode(columns.iter().map(|column| match object.get(column) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
+        }))?;
+
+        self.writer.append(&row, || self.encode(columns).map(Some)).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.writer.flush().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.writer.finalize().await
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Check whether a path is a sink file that has not been finalised
+pub fn is_in_progress(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == IN_PROGRESS_SUFFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{CsvSource, JsonLinesSource, Source};
+    use serde_json::json;
+
+    fn list_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[tokio::test]
+    async fn test_json_lines_sink_finalises_on_close() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = JsonLinesSink::new(dir.path(), "events");
+
+        sink.write(&Record::new("a", json!({"n": 1}))).await.unwrap();
+        sink.write(&Record::new("b", json!({"n": 2}))).await.unwrap();
+        sink.flush().await.unwrap();
+
+        let files = list_files(dir.path());
+        assert_eq!(files.len(), 1);
+        assert!(is_in_progress(&files[0]));
+
+        sink.close().await.unwrap();
+        let files = list_files(dir.path());
+        assert_eq!(files.len(), 1);
+        assert!(!is_in_progress(&files[0]));
+        assert_eq!(sink.completed_files().await, files);
+
+        let mut source = JsonLinesSource::new(dir.path());
+        let first = source.next_record().await.unwrap().unwrap();
+        assert_eq!(first.value, json!({"n": 1}));
+    }
+
+    #[tokio::test]
+    async fn test_size_based_rolling() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = JsonLinesSink::new(dir.path(), "events")
+            .rolling(RollingPolicy::new().max_bytes(20));
+
+        for i in 0..5 {
+            sink.write(&Record::new("k", json!({"value": i}))).await.unwrap();
+        }
+        sink.close().await.unwrap();
+
+        // Each 12-byte line fills more than half a file, so files hold two records
+        let files = list_files(dir.path());
+        assert_eq!(files.len(), 3);
+        assert!(files.iter().all(|f| !is_in_progress(f)));
+    }
+
+    #[tokio::test]
+    async fn test_time_based_rolling() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = JsonLinesSink::new(dir.path(), "events")
+            .rolling(RollingPolicy::new().max_age(Duration::from_millis(20)));
+
+        sink.write(&Record::new("a", json!(1))).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        sink.flush().await.unwrap();
+
+        assert_eq!(sink.completed_files().await.len(), 1);
+
+        sink.write(&Record::new("b", json!(2))).await.unwrap();
+        sink.close().await.unwrap();
+        assert_eq!(sink.completed_files().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_idle_files_roll_when_they_expire() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = CsvSink::new(dir.path(), "orders")
+            .rolling(RollingPolicy::new().max_age(Duration::from_millis(20)));
+
+        sink.write(&Record::new("o1", json!({"id": 1}))).await.unwrap();
+        assert!(is_in_progress(&list_files(dir.path())[0]));
+
+        // Nothing is written or flushed; the timer alone finalises the file
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let files = list_files(dir.path());
+        assert_eq!(files.len(), 1);
+        assert!(!is_in_progress(&files[0]));
+        assert_eq!(sink.completed_files().await, files);
+    }
+
+    #[tokio::test]
+    async fn test_csv_sink_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = CsvSink::new(dir.path(), "orders")
+            .columns(vec!["id".to_string(), "amount".to_string(), "tags".to_string()]);
+
+        sink.write(&Record::new("o1", json!({"id": "o1", "amount": 12.5, "tags": ["a"]})))
+            .await
+            .unwrap();
+        sink.write(&Record::new("o2", json!({"id": "o2, \"quoted\"", "extra": true})))
+            .await
+            .unwrap();
+        assert!(sink.write(&Record::new("bad", json!(3))).await.is_err());
+        sink.close().await.unwrap();
+
+        let mut source = CsvSource::new(dir.path());
+        let first = source.next_record().await.unwrap().unwrap();
+        let second = source.next_record().await.unwrap().unwrap();
+        assert_eq!(first.value, json!({"id": "o1", "amount": 12.5, "tags": "[\"a\"]"}));
+        assert_eq!(second.value, json!({"id": "o2, \"quoted\"", "amount": null, "tags": null}));
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn test_compressed_files_round_trip() {
+        use crate::metrics::tests::capture_metrics;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (_, metrics) = capture_metrics(async {
+            let events = JsonLinesSink::new(dir.path(), "events").compression(Compression::gzip());
+            let orders = CsvSink::new(dir.path(), "orders").compression(Compression::zstd());
+            for i in 0..50 {
+                events.write(&Record::new("e", json!({"n": i}))).await.unwrap();
+                orders.write(&Record::new("o", json!({"id": i, "item": "widget"}))).await.unwrap();
+            }
+            events.flush().await.unwrap();
+            events.close().await.unwrap();
+            orders.close().await.unwrap();
+
+            let mut source = JsonLinesSource::new(dir.path());
+            let mut count = 0;
+            while let Some(record) = source.next_record().await.unwrap() {
+                assert_eq!(record.value, json!({"n": count}));
+                count += 1;
+            }
+            assert_eq!(count, 50);
+
+            let mut source = CsvSource::new(dir.path());
+            let first = source.next_record().await.unwrap().unwrap();
+            assert_eq!(first.value, json!({"id": 0, "item": "widget"}));
+        });
+
+        let names: Vec<_> = list_files(dir.path())
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names.len(), 2);
+        assert!(names[0].ends_with(".ndjson.gz"), "{:?}", names);
+        assert!(names[1].ends_with(".csv.zst"), "{:?}", names);
+
+        for codec in ["gzip", "zstd"] {
+            let raw = metrics.counter("compression_input_bytes_total", &[("codec", codec)]);
+            let compressed = metrics.counter("compression_output_bytes_total", &[("codec", codec)]);
+            assert!(compressed > 0 && compressed < raw, "{}: {} -> {}", codec, raw, compressed);
+        }
+    }
+}
@@ -0,0 +1,895 @@
+//! Record sources for ingesting data
+//!
+//! A [`Source`] yields records one at a time and reports a [`SourceOffset`]
+//! that can be persisted and passed back to resume reading later. The
+//! built-in [`JsonLinesSource`] and [`CsvSource`] read a single file or every
+//! matching file in a directory, in lexicographic order.
+//!
+//! Files compressed with gzip or zstd are decompressed as they are read; they
+//! are recognised by content, and in a directory `.gz` and `.zst` files are
+//! picked up alongside uncompressed ones (`orders.ndjson.gz`, say). Offsets
+//! into compressed files count decompressed bytes, so resuming one has to
+//! decompress it up to the saved offset.
+
+use crate::{
+    compression::{Codec, FileReader},
+    record::Record,
+    Error, Result,
+};
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tracing::{debug, warn};
+
+/// Trait for record sources
+#[async_trait]
+pub trait Source: Send + fmt::Debug {
+    /// Read the next record, or `None` once the source is exhausted
+    ///
+    /// A malformed entry produces an error but does not end the source;
+    /// reading continues with the following entry on the next call.
+    async fn next_record(&mut self) -> Result<Option<Record>>;
+
+    /// Position just after the last record returned
+    fn offset(&self) -> SourceOffset;
+
+    /// Name of the source
+    fn name(&self) -> &str;
+
+    /// Convert the source into a stream of records
+    ///
+    /// The stream ends after the source is exhausted or after an I/O error.
+    fn into_stream(self) -> BoxStream<'static, Result<Record>>
+    where
+        Self: Sized + 'static,
+    {
+        Box::pin(stream::unfold(Some(self), |source| async move {
+            let mut source = source?;
+            match source.next_record().await {
+                Ok(Some(record)) => Some((Ok(record), Some(source))),
+                Ok(None) => None,
+                Err(e @ Error::Io(_)) => Some((Err(e), None)),
+                Err(e) => Some((Err(e), Some(source))),
+            }
+        }))
+    }
+}
+
+/// Resumable position within a file-based source
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceOffset {
+    /// File currently being read, if any file has been opened
+    pub file: Option<PathBuf>,
+
+    /// Byte offset within the file, counted after decompression
+    pub byte: u64,
+
+    /// Number of lines consumed within the file
+    pub line: u64,
+}
+
+/// Function deriving a record key from its value
+pub type KeyFn = Arc<dyn Fn(&Value) -> Option<String> + Send + Sync>;
+
+/// Strategy for deriving a record key from its value
+#[derive(Clone, Default)]
+pub enum KeyExtractor {
+    /// Use the string form of a top-level field
+    Field(String),
+
+    /// Use `<file name>:<line number>` of the entry
+    #[default]
+    Offset,
+
+    /// Use a custom function of the value
+    Custom(KeyFn),
+}
+
+impl fmt::Debug for KeyExtractor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyExtractor::Field(field) => f.debug_tuple("Field").field(field).finish(),
+            KeyExtractor::Offset => f.write_str("Offset"),
+            KeyExtractor::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+impl KeyExtractor {
+    fn extract(&self, value: &Value, file: &Path, line: u64) -> Result<String> {
+        let key = match self {
+            KeyExtractor::Field(field) => value.get(field).and_then(|v| match v {
+                Value::String(s) => Some(s.clone()),
+                Value::Null => None,
+                other => Some(other.to_string()),
+            }),
+            KeyExtractor::Offset => Some(format!("{}:{}", file_label(file), line)),
+            KeyExtractor::Custom(extract) => extract(value),
+        };
+
+        key.ok_or_else(|| {
+            Error::processing(format!(
+                "{}:{}: could not extract record key using {:?}",
+                file.display(),
+                line,
+                self
+            ))
+        })
+    }
+}
+
+fn file_label(file: &Path) -> String {
+    file.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file.display().to_string())
+}
+
+/// Cursor over the lines of one file or a directory of files
+#[derive(Debug)]
+struct FileCursor {
+    root: PathBuf,
+    extensions: Vec<String>,
+    files: Option<Vec<PathBuf>>,
+    index: usize,
+    reader: Option<FileReader>,
+    offset: SourceOffset,
+    resume: Option<SourceOffset>,
+}
+
+impl FileCursor {
+    fn new(root: PathBuf, extensions: &[&str]) -> Self {
+        Self {
+            root,
+            extensions: extensions.iter().map(|ext| ext.to_string()).collect(),
+            files: None,
+            index: 0,
+            reader: None,
+            offset: SourceOffset::default(),
+            resume: None,
+        }
+    }
+
+    /// List the files to read, skipping those before the resume offset
+    async fn discover(&mut self) -> Result<()> {
+        let mut files = Vec::new();
+        if tokio::fs::metadata(&self.root).await?.is_dir() {
+            let mut entries = tokio::fs::read_dir(&self.root).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                let matches = data_extension(&path)
+                    .is_some_and(|ext| self.extensions.iter().any(|e| e == ext));
+                if matches && entry.file_type().await?.is_file() {
+                    files.push(path);
+                }
+            }
+            files.sort();
+        } else {
+            files.push(self.root.clone());
+        }
+
+        if let Some(file) = self.resume.as_ref().and_then(|r| r.file.as_ref()) {
+            self.index = files.partition_point(|f| f < file);
+        }
+
+        debug!(root = %self.root.display(), files = files.len(), "Discovered source files");
+        self.files = Some(files);
+        Ok(())
+    }
+
+    /// Open the current file if needed; returns `false` once all files are read
+    async fn ensure_open(&mut self) -> Result<bool> {
+        if self.files.is_none() {
+            self.discover().await?;
+        }
+        if self.reader.is_some() {
+            return Ok(true);
+        }
+
+        let Some(path) = self.files.as_ref().and_then(|f| f.get(self.index)).cloned() else {
+            return Ok(false);
+        };
+
+        let mut offset = SourceOffset {
+            file: Some(path.clone()),
+            byte: 0,
+            line: 0,
+        };
+        if let Some(resume) = self.resume.take() {
+            if resume.file.as_ref() == Some(&path) {
+                offset.byte = resume.byte;
+                offset.line = resume.line;
+            }
+        }
+
+        let reader = FileReader::open(&path, offset.byte).await?;
+        debug!(
+            file = %path.display(),
+            byte = offset.byte,
+            codec = ?reader.codec(),
+            "Opened source file"
+        );
+        self.reader = Some(reader);
+        self.offset = offset;
+        Ok(true)
+    }
+
+    /// Read one line of the current file, or `None` at its end
+    async fn read_line(&mut self) -> Result<Option<String>> {
+        let Some(reader) = self.reader.as_mut() else {
+            return Ok(None);
+        };
+
+        let mut line = String::new();
+        let read = reader.read_line(&mut line).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+
+        self.offset.byte += read as u64;
+        self.offset.line += 1;
+        while line.ends_with('\n') || line.ends_with('\r') {
+            line.pop();
+        }
+        Ok(Some(line))
+    }
+
+    /// Move on to the next file
+    fn finish_file(&mut self) {
+        self.reader = None;
+        self.index += 1;
+    }
+
+    fn current_file(&self) -> &Path {
+        self.offset.file.as_deref().unwrap_or(&self.root)
+    }
+}
+
+/// Extension of a file's uncompressed content, looking past `.gz` or `.zst`
+fn data_extension(path: &Path) -> Option<&str> {
+    let ext = path.extension()?.to_str()?;
+    if Codec::from_extension(ext).is_some() {
+        Path::new(path.file_stem()?).extension()?.to_str()
+    } else {
+        Some(ext)
+    }
+}
+
+/// Options shared by the file-based sources
+#[derive(Debug, Clone, Default)]
+struct RecordOptions {
+    key: KeyExtractor,
+    source_name: Option<String>,
+}
+
+impl RecordOptions {
+    fn build(&self, value: Value, file: &Path, line: u64) -> Result<Record> {
+        let key = self.key.extract(&value, file, line)?;
+        let source = self
+            .source_name
+            .clone()
+            .unwrap_or_else(|| file.display().to_string());
+
+        Record::builder().key(key).value(value).source(source).build()
+    }
+}
+
+/// Source reading newline-delimited JSON (JSON Lines)
+///
+/// Reads a single file, or every `.ndjson` and `.jsonl` file in a directory.
+/// Plain `.json` files usually hold a single document rather than one per
+/// line, so they are only read from a directory when opted in with
+/// [`extensions`](Self::extensions). Blank lines are skipped.
+#[derive(Debug)]
+pub struct JsonLinesSource {
+    name: String,
+    cursor: FileCursor,
+    options: RecordOptions,
+}
+
+impl JsonLinesSource {
+    /// Create a source for a file or directory
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self {
+            name: format!("ndjson:{}", path.display()),
+            cursor: FileCursor::new(path, &["ndjson", "jsonl"]),
+            options: RecordOptions::default(),
+        }
+    }
+
+    /// Set the file extensions read from a directory, without the leading dot
+    ///
+    /// Defaults to `ndjson` and `jsonl`.
+    pub fn extensions<I, S>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.cursor.extensions = extensions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set how record keys are derived
+    pub fn key(mut self, key: KeyExtractor) -> Self {
+        self.options.key = key;
+        self
+    }
+
+    /// Set the `source` metadata value of produced records
+    ///
+    /// Defaults to the path of the file each record was read from.
+    pub fn source_name(mut self, name: impl Into<String>) -> Self {
+        self.options.source_name = Some(name.into());
+        self
+    }
+
+    /// Resume reading from a previously saved offset
+    pub fn resume_from(mut self, offset: SourceOffset) -> Self {
+        self.cursor.resume = Some(offset);
+        self
+    }
+}
+
+#[async_trait]
+impl Source for JsonLinesSource {
+    async fn next_record(&mut self) -> Result<Option<Record>> {
+        while self.cursor.ensure_open().await? {
+            let Some(line) = self.cursor.read_line().await? else {
+                self.cursor.finish_file();
+                continue;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let file = self.cursor.current_file();
+            let line_number = self.cursor.offset.line;
+            let value: Value = serde_json::from_str(&line).map_err(|e| {
+                warn!(file = %file.display(), line = line_number, error = %e, "Malformed line");
+                Error::processing(format!("{}:{}: {}", file.display(), line_number, e))
+            })?;
+
+            return self.options.build(value, file, line_number).map(Some);
+        }
+
+        Ok(None)
+    }
+
+    fn offset(&self) -> SourceOffset {
+        self.cursor.offset.clone()
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// How a CSV source determines column names
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CsvHeaders {
+    /// Treat the first row as a header unless it contains numbers, booleans
+    /// or empty fields
+    #[default]
+    Infer,
+
+    /// The first row is always a header
+    Present,
+
+    /// There is no header; columns are named `column_0`, `column_1`, ...
+    Absent,
+
+    /// There is no header; use the given column names
+    Names(Vec<String>),
+}
+
+/// Source reading comma-separated values
+///
+/// Reads a single file, or every `.csv` file in a directory. Each row becomes
+/// a JSON object keyed by column name. Quoted fields may span lines.
+#[derive(Debug)]
+pub struct CsvSource {
+    name: String,
+    cursor: FileCursor,
+    options: RecordOptions,
+    headers: CsvHeaders,
+    delimiter: u8,
+    infer_types: bool,
+    /// Column names for the current file, once loaded
+    columns: Option<Vec<String>>,
+    /// Inferred type of each column in the current file, once a value is seen
+    column_kinds: Vec<Option<ValueKind>>,
+}
+
+impl CsvSource {
+    /// Create a source for a file or directory
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self {
+            name: format!("csv:{}", path.display()),
+            cursor: FileCursor::new(path, &["csv"]),
+            options: RecordOptions::default(),
+            headers: CsvHeaders::default(),
+            delimiter: b',',
+            infer_types: true,
+            columns: None,
+            column_kinds: Vec::new(),
+        }
+    }
+
+    /// Set how record keys are derived
+    pub fn key(mut self, key: KeyExtractor) -> Self {
+        self.options.key = key;
+        self
+    }
+
+    /// Set the `source` metadata value of produced records
+    ///
+    /// Defaults to the path of the file each record was read from.
+    pub fn source_name(mut self, name: impl Into<String>) -> Self {
+        self.options.source_name = Some(name.into());
+        self
+    }
+
+    /// Set how column names are determined
+    pub fn headers(mut self, headers: CsvHeaders) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Set the field delimiter
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Enable or disable inference of numbers, booleans and nulls
+    ///
+    /// Each column takes the type of its first non-empty value in a file, and
+    /// later values that do not fit that type are kept as strings. Numbers
+    /// are only inferred from plain decimals that convert losslessly, so
+    /// values such as `007`, `+1` or `1e5` stay strings. When disabled every
+    /// field is kept as a string.
+    pub fn infer_types(mut self, enabled: bool) -> Self {
+        self.infer_types = enabled;
+        self
+    }
+
+    /// Resume reading from a previously saved offset
+    pub fn resume_from(mut self, offset: SourceOffset) -> Self {
+        self.cursor.resume = Some(offset);
+        self
+    }
+
+    /// Read one CSV row from the current file, joining lines inside quotes
+    async fn read_row(&mut self) -> Result<Option<(Vec<String>, u64)>> {
+        let Some(mut text) = self.cursor.read_line().await? else {
+            return Ok(None);
+        };
+        let start_line = self.cursor.offset.line;
+
+        while text.matches('"').count() % 2 == 1 {
+            match self.cursor.read_line().await? {
+                Some(next) => {
+                    text.push('\n');
+                    text.push_str(&next);
+                }
+                None => break,
+            }
+        }
+
+        Ok(Some((self.parse_row(&text, start_line)?, start_line)))
+    }
+
+    fn parse_row(&self, text: &str, line: u64) -> Result<Vec<String>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(self.delimiter)
+            .from_reader(text.as_bytes());
+
+        match reader.records().next() {
+            Some(Ok(row)) => Ok(row.iter().map(str::to_string).collect()),
+            Some(Err(e)) => Err(Error::processing(format!(
+                "{}:{}: {}",
+                self.cursor.current_file().display(),
+                line,
+                e
+            ))),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Read the first row of the current file without moving the cursor
+    async fn read_first_row(&mut self) -> Result<Option<Vec<String>>> {
+        let mut reader = FileReader::open(self.cursor.current_file(), 0).await?;
+        let mut text = String::new();
+
+        loop {
+            let read = reader.read_line(&mut text).await?;
+            if read == 0 || text.matches('"').count() % 2 == 0 {
+                break;
+            }
+        }
+
+        let text = text.trim_end_matches(['\n', '\r']);
+        if text.is_empty() {
+            return Ok(None);
+        }
+        self.parse_row(text, 1).map(Some)
+    }
+
+    /// Determine column names for a newly opened file
+    ///
+    /// Returns the first row if it turned out to be data rather than a
+    /// header, so it can still be emitted.
+    async fn load_columns(&mut self) -> Result<Option<(Vec<String>, u64)>> {
+        let resumed = self.cursor.offset.byte > 0;
+
+        let (columns, pending) = match &self.headers {
+            CsvHeaders::Names(names) => (names.clone(), None),
+            CsvHeaders::Absent => (Vec::new(), None),
+            CsvHeaders::Present | CsvHeaders::Infer if resumed => {
+                let first = self.read_first_row().await?.unwrap_or_default();
+                if self.headers == CsvHeaders::Present || looks_like_header(&first) {
+                    (first, None)
+                } else {
+                    (Vec::new(), None)
+                }
+            }
+            CsvHeaders::Present | CsvHeaders::Infer => match self.read_row().await? {
+                Some((first, line)) => {
+                    if self.headers == CsvHeaders::Present || looks_like_header(&first) {
+                        (first, None)
+                    } else {
+                        (Vec::new(), Some((first, line)))
+                    }
+                }
+                None => (Vec::new(), None),
+            },
+        };
+
+        self.columns = Some(columns);
+        self.column_kinds.clear();
+        Ok(pending)
+    }
+
+    fn row_to_value(&mut self, row: Vec<String>) -> Value {
+        let columns = self.columns.as_deref().unwrap_or_default();
+        let mut object = Map::with_capacity(row.len());
+
+        for (idx, field) in row.into_iter().enumerate() {
+            let name = columns
+                .get(idx)
+                .cloned()
+                .unwrap_or_else(|| format!("column_{}", idx));
+            let value = if self.infer_types {
+                infer_column_value(&mut self.column_kinds, idx, field)
+            } else {
+                Value::String(field)
+            };
+            object.insert(name, value);
+        }
+
+        Value::Object(object)
+    }
+}
+
+#[async_trait]
+impl Source for CsvSource {
+    async fn next_record(&mut self) -> Result<Option<Record>> {
+        while self.cursor.ensure_open().await? {
+            let mut row = None;
+            if self.columns.is_none() {
+                row = self.load_columns().await?;
+            }
+
+            let row = match row {
+                Some(row) => row,
+                None => match self.read_row().await? {
+                    Some(row) => row,
+                    None => {
+                        self.cursor.finish_file();
+                        self.columns = None;
+                        continue;
+                    }
+                },
+            };
+
+            let (fields, line) = row;
+            if fields.iter().all(|f| f.is_empty()) {
+                continue;
+            }
+
+            let value = self.row_to_value(fields);
+            return self
+                .options
+                .build(value, self.cursor.current_file(), line)
+                .map(Some);
+        }
+
+        Ok(None)
+    }
+
+    fn offset(&self) -> SourceOffset {
+        self.cursor.offset.clone()
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A row looks like a header if every field is a non-empty, non-numeric,
+/// non-boolean string
+fn looks_like_header(row: &[String]) -> bool {
+    !row.is_empty() && row.iter().all(|f| matches!(infer_value(f), Value::String(_)))
+}
+
+/// Type inferred for a CSV column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValueKind {
+    Bool,
+    Number,
+    String,
+}
+
+impl ValueKind {
+    /// Kind of an inferred value, or `None` for nulls, which fit any column
+    fn of(value: &Value) -> Option<Self> {
+        match value {
+            Value::Null => None,
+            Value::Bool(_) => Some(Self::Bool),
+            Value::Number(_) => Some(Self::Number),
+            _ => Some(Self::String),
+        }
+    }
+}
+
+/// Infer a field's value, keeping each column to a single kind
+///
+/// A column takes the kind of its first non-null value; later values of a
+/// different kind are kept as strings.
+fn infer_column_value(kinds: &mut Vec<Option<ValueKind>>, column: usize, field: String) -> Value {
+    let value = infer_value(&field);
+    let Some(kind) = ValueKind::of(&value) else {
+        return value;
+    };
+
+    if kinds.len() <= column {
+        kinds.resize(column + 1, None);
+    }
+    if *kinds[column].get_or_insert(kind) == kind {
+        value
+    } else {
+        Value::String(field)
+    }
+}
+
+/// Infer a JSON value from a CSV field
+///
+/// Numbers are only inferred from fields that are written the way the number
+/// prints, so identifiers such as `007`, `+1` or `1e5` and integers too large
+/// to represent exactly stay strings.
+fn infer_value(field: &str) -> Value {
+    if field.is_empty() {
+        return Value::Null;
+    }
+    if let Ok(b) = field.parse::<bool>() {
+        return Value::Bool(b);
+    }
+    if let Ok(i) = field.parse::<i64>() {
+        if i.to_string() == field {
+            return Value::from(i);
+        }
+    } else if let Ok(u) = field.parse::<u64>() {
+        if u.to_string() == field {
+            return Value::from(u);
+        }
+    } else if field.contains('.') {
+        if let Ok(f) = field.parse::<f64>() {
+            // Trailing zeros in the fraction do not change the value
+            let trimmed = field.trim_end_matches('0').trim_end_matches('.');
+            if f.is_finite() && f.to_string() == trimmed {
+                return Value::from(f);
+            }
+        }
+    }
+    Value::String(field.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use serde_json::json;
+    use std::io::Write;
+
+    fn write_file(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = dir.join(name);
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        path
+    }
+
+    async fn read_all(source: &mut dyn Source) -> Vec<Record> {
+        let mut records = Vec::new();
+        while let Some(record) = source.next_record().await.unwrap() {
+            records.push(record);
+        }
+        records
+    }
+
+    #[tokio::test]
+    async fn test_json_lines_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(
+            dir.path(),
+            "users.ndjson",
+            "{\"id\": \"u1\", \"age\": 30}\n\n{\"id\": \"u2\", \"age\": 40}\n",
+        );
+
+        let mut source = JsonLinesSource::new(&path)
+            .key(KeyExtractor::Field("id".to_string()))
+            .source_name("partner_a");
+        let records = read_all(&mut source).await;
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].key, "u1");
+        assert_eq!(records[1].value["age"], json!(40));
+        assert_eq!(records[1].metadata.source, "partner_a");
+    }
+
+    #[tokio::test]
+    async fn test_json_lines_malformed_line_is_skippable() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(dir.path(), "data.jsonl", "{\"a\": 1}\nnot json\n{\"a\": 3}\n");
+
+        let results: Vec<_> = JsonLinesSource::new(&path).into_stream().collect().await;
+        assert_eq!(results.len(), 3);
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap().key, "data.jsonl:3");
+    }
+
+    #[tokio::test]
+    async fn test_directory_and_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "b.ndjson", "{\"n\": 3}\n{\"n\": 4}\n");
+        write_file(dir.path(), "a.ndjson", "{\"n\": 1}\n{\"n\": 2}\n");
+        write_file(dir.path(), "ignored.txt", "{\"n\": 99}\n");
+        write_file(dir.path(), "document.json", "{\n  \"n\": 99\n}\n");
+
+        let mut source = JsonLinesSource::new(dir.path());
+        let first = source.next_record().await.unwrap().unwrap();
+        let second = source.next_record().await.unwrap().unwrap();
+        let third = source.next_record().await.unwrap().unwrap();
+        assert_eq!(first.value["n"], json!(1));
+        assert_eq!(second.value["n"], json!(2));
+        assert_eq!(third.value["n"], json!(3));
+
+        let offset = source.offset();
+        assert!(offset.file.as_ref().unwrap().ends_with("b.ndjson"));
+
+        let mut resumed = JsonLinesSource::new(dir.path()).resume_from(offset);
+        let rest = read_all(&mut resumed).await;
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].value["n"], json!(4));
+
+        // `.json` files are only read once opted in
+        std::fs::remove_file(dir.path().join("document.json")).unwrap();
+        write_file(dir.path(), "c.json", "{\"n\": 5}\n");
+        let mut source = JsonLinesSource::new(dir.path()).extensions(["ndjson", "json"]);
+        let records = read_all(&mut source).await;
+        let values: Vec<_> = records.iter().map(|r| r.value["n"].clone()).collect();
+        assert_eq!(values, [json!(1), json!(2), json!(3), json!(4), json!(5)]);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "compression")]
+    async fn test_compressed_files_and_resume() {
+        use crate::compression::{compress, Compression};
+
+        let dir = tempfile::tempdir().unwrap();
+        let lines = b"{\"n\": 1}\n{\"n\": 2}\n{\"n\": 3}\n";
+        let packed = compress(lines, Compression::zstd()).unwrap();
+        std::fs::write(dir.path().join("a.ndjson.zst"), packed).unwrap();
+        write_file(dir.path(), "b.ndjson", "{\"n\": 4}\n");
+        let gzipped = compress(b"{\"n\": 99}\n", Compression::gzip()).unwrap();
+        std::fs::write(dir.path().join("ignored.txt.gz"), gzipped).unwrap();
+
+        let mut source = JsonLinesSource::new(dir.path());
+        source.next_record().await.unwrap().unwrap();
+        let second = source.next_record().await.unwrap().unwrap();
+        assert_eq!(second.value["n"], json!(2));
+        let offset = source.offset();
+        assert_eq!(offset.byte, 18);
+
+        let mut resumed = JsonLinesSource::new(dir.path()).resume_from(offset);
+        let rest = read_all(&mut resumed).await;
+        let values: Vec<_> = rest.iter().map(|r| r.value["n"].clone()).collect();
+        assert_eq!(values, vec![json!(3), json!(4)]);
+    }
+
+    #[tokio::test]
+    async fn test_csv_header_and_type_inference() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(
+            dir.path(),
+            "orders.csv",
+            "id,amount,paid,note\no1,12.5,true,\"multi\nline\"\no2,3,false,\n",
+        );
+
+        let mut source = CsvSource::new(&path).key(KeyExtractor::Field("id".to_string()));
+        let records = read_all(&mut source).await;
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].key, "o1");
+        assert_eq!(records[0].value["amount"], json!(12.5));
+        assert_eq!(records[0].value["paid"], json!(true));
+        assert_eq!(records[0].value["note"], json!("multi\nline"));
+        assert_eq!(records[1].value["amount"], json!(3));
+        assert_eq!(records[1].value["note"], Value::Null);
+    }
+
+    #[test]
+    fn test_infer_value_keeps_identifiers_as_strings() {
+        for field in ["007", "00501", "+1", "1e5", "-0", ".5", "00.5", "99999999999999999999"] {
+            assert_eq!(infer_value(field), json!(field), "{}", field);
+        }
+        assert_eq!(infer_value("-12"), json!(-12));
+        assert_eq!(infer_value("0.5"), json!(0.5));
+        assert_eq!(infer_value("1.50"), json!(1.5));
+        assert_eq!(infer_value("18446744073709551615"), json!(u64::MAX));
+    }
+
+    #[tokio::test]
+    async fn test_csv_zip_codes_keep_one_type_per_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(
+            dir.path(),
+            "places.csv",
+            "zip,count\n00501,1\n10001,\n02134,3\nn/a,4\n",
+        );
+
+        let records = read_all(&mut CsvSource::new(&path)).await;
+        let zips: Vec<_> = records.iter().map(|r| r.value["zip"].clone()).collect();
+        assert_eq!(zips, [json!("00501"), json!("10001"), json!("02134"), json!("n/a")]);
+        let counts: Vec<_> = records.iter().map(|r| r.value["count"].clone()).collect();
+        assert_eq!(counts, [json!(1), Value::Null, json!(3), json!(4)]);
+    }
+
+    #[tokio::test]
+    async fn test_csv_without_header_and_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(dir.path(), "raw.csv", "1,a\n2,b\n3,c\n");
+
+        let mut source = CsvSource::new(&path);
+        let first = source.next_record().await.unwrap().unwrap();
+        assert_eq!(first.value, json!({"column_0": 1, "column_1": "a"}));
+
+        let mut resumed = CsvSource::new(&path)
+            .headers(CsvHeaders::Names(vec!["n".to_string(), "s".to_string()]))
+            .resume_from(source.offset());
+        let rest = read_all(&mut resumed).await;
+        assert_eq!(rest.len(), 2);
+        assert_eq!(rest[0].value, json!({"n": 2, "s": "b"}));
+    }
+
+    #[tokio::test]
+    async fn test_csv_resume_keeps_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(dir.path(), "people.csv", "name,age\nann,30\nbob,40\n");
+
+        let mut source = CsvSource::new(&path);
+        source.next_record().await.unwrap().unwrap();
+
+        let mut resumed = CsvSource::new(&path).resume_from(source.offset());
+        let rest = read_all(&mut resumed).await;
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].value, json!({"name": "bob", "age": 40}));
+    }
+}
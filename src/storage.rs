@@ -1,14 +1,39 @@
 //! Storage abstraction module
 
-use crate::{record::Record, Result};
+use crate::{
+    config::RetryConfig, deadline::with_timeout, metrics::MetricsRecorder, record::Record,
+    Error, Result,
+};
 use async_trait::async_trait;
 use dashmap::DashMap;
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::debug;
 use uuid::Uuid;
 
+mod cache;
+mod file;
+#[cfg(feature = "cloud")]
+mod object;
+mod query;
+
+pub use cache::{CacheConfig, CacheStats, CachedStorage, EvictionPolicy};
+pub use file::{FileStorage, FileStorageConfig, FileStorageStats, SyncPolicy};
+pub use query::{
+    Cursor, FieldFilter, Predicate, Query, QueryPage, SortKey, SortOrder, TimeRange,
+};
+#[cfg(feature = "cloud")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cloud")))]
+pub use object::{
+    CloudObjectStore, FsObjectStore, Object, ObjectMeta, ObjectStore, ObjectStoreStorage,
+    PutCondition, S3Config,
+};
+
 /// Trait for storage backends
 #[async_trait]
-pub trait Storage: Send + Sync {
+pub trait Storage: Send + Sync + std::fmt::Debug {
     /// Store a record
     async fn store(&self, record: &Record) -> Result<()>;
     
@@ -29,6 +54,169 @@ pub trait Storage: Send + Sync {
     
     /// Clear all records
     async fn clear(&self) -> Result<()>;
+
+    /// Make buffered writes durable
+    ///
+    /// The default does nothing, for backends that write through.
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Update a record only if the stored copy has the expected version
+    ///
+    /// Compare-and-swap on [`RecordMetadata::version`](crate::record::RecordMetadata::version):
+    /// the record is written with version `expected_version + 1` and returned
+    /// as stored. Fails with [`Error::Conflict`] if the stored version differs
+    /// and [`Error::NotFound`] if nothing is stored.
+    ///
+    /// The default reads and writes in separate calls, so a concurrent write
+    /// can slip in between; the built-in backends override it with an atomic
+    /// check.
+    async fn update_if_version(&self, record: &Record, expected_version: u64) -> Result<Record> {
+        let stored = self.get(&record.id).await?.ok_or_else(|| missing(&record.id))?;
+        check_version(&stored, expected_version)?;
+        let updated = next_version(record, expected_version);
+        self.update(&updated).await?;
+        Ok(updated)
+    }
+
+    /// Find the records matching a query, one page at a time
+    ///
+    /// The default scans every record through [`list`](Self::list) and
+    /// [`get`](Self::get); backends that can iterate or index their records
+    /// more cheaply should override it, finishing with [`Query::page`].
+    async fn query(&self, query: &Query) -> Result<QueryPage> {
+        let mut matches = Vec::new();
+        for id in self.list().await? {
+            // Records deleted since listing are skipped
+            if let Some(record) = self.get(&id).await? {
+                if query.matches(&record) {
+                    matches.push(record);
+                }
+            }
+        }
+        Ok(query.page(matches))
+    }
+}
+
+/// Read-modify-write helpers, available on every [`Storage`]
+#[async_trait]
+pub trait StorageExt: Storage {
+    /// Change a stored record, retrying if another writer gets there first
+    ///
+    /// Reads the record, applies `modify` and writes the result back with
+    /// [`update_if_version`](Storage::update_if_version). On
+    /// [`Error::Conflict`] it starts over from a fresh copy, so `modify` may
+    /// run several times. Gives up with the conflict after a handful of
+    /// attempts with short, jittered backoffs; errors from `modify` are
+    /// returned straight away.
+    async fn modify<F>(&self, id: &Uuid, modify: F) -> Result<Record>
+    where
+        F: FnMut(&mut Record) -> Result<()> + Send,
+    {
+        let retry = RetryConfig {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(50),
+            backoff_multiplier: 2.0,
+            jitter: 0.5,
+        };
+        self.modify_with(id, &retry, modify).await
+    }
+
+    /// Like [`modify`](Self::modify), with the given attempts and backoff
+    async fn modify_with<F>(&self, id: &Uuid, retry: &RetryConfig, mut modify: F) -> Result<Record>
+    where
+        F: FnMut(&mut Record) -> Result<()> + Send,
+    {
+        let max_attempts = retry.max_attempts.max(1);
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let mut record = self.get(id).await?.ok_or_else(|| missing(id))?;
+            let expected = record.metadata.version;
+            modify(&mut record)?;
+
+            match self.update_if_version(&record, expected).await {
+                Err(Error::Conflict(reason)) if attempt < max_attempts => {
+                    let backoff = retry.calculate_backoff_with_jitter(attempt - 1);
+                    debug!(record_id = %id, attempt, %reason, "Version conflict, retrying");
+                    tokio::time::sleep(backoff).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<S: Storage + ?Sized> StorageExt for S {}
+
+/// Shared storage, so wrappers such as [`MeteredStorage`] can hold an `Arc<dyn Storage>`
+#[async_trait]
+impl<S: Storage + ?Sized> Storage for Arc<S> {
+    async fn store(&self, record: &Record) -> Result<()> {
+        (**self).store(record).await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Option<Record>> {
+        (**self).get(id).await
+    }
+
+    async fn update(&self, record: &Record) -> Result<()> {
+        (**self).update(record).await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<bool> {
+        (**self).delete(id).await
+    }
+
+    async fn list(&self) -> Result<Vec<Uuid>> {
+        (**self).list().await
+    }
+
+    async fn count(&self) -> Result<usize> {
+        (**self).count().await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        (**self).clear().await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        (**self).flush().await
+    }
+
+    async fn update_if_version(&self, record: &Record, expected_version: u64) -> Result<Record> {
+        (**self).update_if_version(record, expected_version).await
+    }
+
+    async fn query(&self, query: &Query) -> Result<QueryPage> {
+        (**self).query(query).await
+    }
+}
+
+/// Error for a record that is not stored
+pub(crate) fn missing(id: &Uuid) -> Error {
+    Error::not_found(format!("Record with ID {} not found", id))
+}
+
+/// Fail with [`Error::Conflict`] unless the stored record has the expected version
+pub(crate) fn check_version(stored: &Record, expected_version: u64) -> Result<()> {
+    if stored.metadata.version == expected_version {
+        Ok(())
+    } else {
+        Err(Error::conflict(format!(
+            "Record {} is at version {}, expected {}",
+            stored.id, stored.metadata.version, expected_version
+        )))
+    }
+}
+
+/// The record as written by a successful [`Storage::update_if_version`]
+pub(crate) fn next_version(record: &Record, expected_version: u64) -> Record {
+    let mut updated = record.clone();
+    updated.metadata.version = expected_version + 1;
+    updated
 }
 
 /// In-memory storage implementation
@@ -75,6 +263,24 @@ impl Storage for InMemoryStorage {
         }
     }
 
+    async fn update_if_version(&self, record: &Record, expected_version: u64) -> Result<Record> {
+        let mut stored = self.records.get_mut(&record.id).ok_or_else(|| missing(&record.id))?;
+        check_version(&stored, expected_version)?;
+        let updated = next_version(record, expected_version);
+        *stored = updated.clone();
+        Ok(updated)
+    }
+
+    async fn query(&self, query: &Query) -> Result<QueryPage> {
+        let matches = self
+            .records
+            .iter()
+            .filter(|entry| query.matches(entry.value()))
+            .map(|entry| entry.value().clone())
+            .collect();
+        Ok(query.page(matches))
+    }
+
     async fn delete(&self, id: &Uuid) -> Result<bool> {
         Ok(self.records.remove(id).is_some())
     }
@@ -93,85 +299,140 @@ impl Storage for InMemoryStorage {
     }
 }
 
-/// Storage with caching layer
+/// Storage wrapper that bounds every call with a timeout
+///
+/// Calls that do not finish in time fail with [`Error::Timeout`](crate::Error::Timeout).
 #[derive(Debug)]
-pub struct CachedStorage<S: Storage> {
+pub struct TimeoutStorage<S: Storage> {
     inner: S,
-    cache: Arc<DashMap<Uuid, Record>>,
-    cache_size: usize,
+    timeout: Duration,
 }
 
-impl<S: Storage> CachedStorage<S> {
-    /// Create a new cached storage
-    pub fn new(inner: S, cache_size: usize) -> Self {
-        Self {
-            inner,
-            cache: Arc::new(DashMap::new()),
-            cache_size,
-        }
-    }
-
-    /// Evict oldest entries if cache is full
-    fn evict_if_needed(&self) {
-        if self.cache.len() >= self.cache_size {
-            // Simple eviction: remove first entry
-            // In production, use LRU or similar
-            if let Some(entry) = self.cache.iter().next() {
-                let key = *entry.key();
-                drop(entry);
-                self.cache.remove(&key);
-            }
-        }
+impl<S: Storage> TimeoutStorage<S> {
+    /// Create a new timeout storage wrapper
+    pub fn new(inner: S, timeout: Duration) -> Self {
+        Self { inner, timeout }
     }
 }
 
 #[async_trait]
-impl<S: Storage> Storage for CachedStorage<S> {
+impl<S: Storage> Storage for TimeoutStorage<S> {
     async fn store(&self, record: &Record) -> Result<()> {
-        self.evict_if_needed();
-        self.cache.insert(record.id, record.clone());
-        self.inner.store(record).await
+        with_timeout("storage.store", self.timeout, self.inner.store(record)).await
     }
 
     async fn get(&self, id: &Uuid) -> Result<Option<Record>> {
-        // Check cache first
-        if let Some(record) = self.cache.get(id) {
-            return Ok(Some(record.clone()));
-        }
+        with_timeout("storage.get", self.timeout, self.inner.get(id)).await
+    }
 
-        // Fetch from storage
-        let record = self.inner.get(id).await?;
-        
-        // Update cache
-        if let Some(ref r) = record {
-            self.evict_if_needed();
-            self.cache.insert(*id, r.clone());
-        }
+    async fn update(&self, record: &Record) -> Result<()> {
+        with_timeout("storage.update", self.timeout, self.inner.update(record)).await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<bool> {
+        with_timeout("storage.delete", self.timeout, self.inner.delete(id)).await
+    }
+
+    async fn list(&self) -> Result<Vec<Uuid>> {
+        with_timeout("storage.list", self.timeout, self.inner.list()).await
+    }
+
+    async fn count(&self) -> Result<usize> {
+        with_timeout("storage.count", self.timeout, self.inner.count()).await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        with_timeout("storage.clear", self.timeout, self.inner.clear()).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        with_timeout("storage.flush", self.timeout, self.inner.flush()).await
+    }
+
+    async fn update_if_version(&self, record: &Record, expected_version: u64) -> Result<Record> {
+        let call = self.inner.update_if_version(record, expected_version);
+        with_timeout("storage.update_if_version", self.timeout, call).await
+    }
+
+    async fn query(&self, query: &Query) -> Result<QueryPage> {
+        with_timeout("storage.query", self.timeout, self.inner.query(query)).await
+    }
+}
+
+/// Storage wrapper that records metrics for every call
+///
+/// Each call is counted and timed through
+/// [`MetricsRecorder::record_storage_operation`], labelled by operation.
+#[derive(Debug)]
+pub struct MeteredStorage<S: Storage> {
+    inner: S,
+    metrics: MetricsRecorder,
+}
+
+impl<S: Storage> MeteredStorage<S> {
+    /// Create a new metered storage wrapper
+    pub fn new(inner: S, metrics: MetricsRecorder) -> Self {
+        Self { inner, metrics }
+    }
+
+    /// Run a storage call and record its duration and outcome
+    async fn observe<T>(
+        &self,
+        operation: &str,
+        call: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let start = Instant::now();
+        let result = call.await;
+        self.metrics.record_storage_operation(
+            operation,
+            start.elapsed().as_millis() as u64,
+            result.is_ok(),
+        );
+        result
+    }
+}
 
-        Ok(record)
+#[async_trait]
+impl<S: Storage> Storage for MeteredStorage<S> {
+    async fn store(&self, record: &Record) -> Result<()> {
+        self.observe("store", self.inner.store(record)).await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Option<Record>> {
+        self.observe("get", self.inner.get(id)).await
     }
 
     async fn update(&self, record: &Record) -> Result<()> {
-        self.cache.insert(record.id, record.clone());
-        self.inner.update(record).await
+        self.observe("update", self.inner.update(record)).await
     }
 
     async fn delete(&self, id: &Uuid) -> Result<bool> {
-        self.cache.remove(id);
-        self.inner.delete(id).await
+        self.observe("delete", self.inner.delete(id)).await
     }
 
     async fn list(&self) -> Result<Vec<Uuid>> {
-        self.inner.list().await
+        self.observe("list", self.inner.list()).await
     }
 
     async fn count(&self) -> Result<usize> {
-        self.inner.count().await
+        self.observe("count", self.inner.count()).await
     }
 
     async fn clear(&self) -> Result<()> {
-        self.cache.clear();
-        self.inner.clear().await
+        self.observe("clear", self.inner.clear()).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.observe("flush", self.inner.flush()).await
+    }
+
+    async fn update_if_version(&self, record: &Record, expected_version: u64) -> Result<Record> {
+        let call = self.inner.update_if_version(record, expected_version);
+        self.observe("update_if_version", call).await
+    }
+
+    async fn query(&self, query: &Query) -> Result<QueryPage> {
+        self.observe("query", self.inner.query(query)).await
     }
 }
 
@@ -179,6 +440,82 @@ impl<S: Storage> Storage for CachedStorage<S> {
 mod tests {
     use super::*;
 
+    /// Exercise the behaviour every `Storage` implementation must provide
+    pub(crate) async fn check_storage_contract(storage: &dyn Storage) {
+        let record = Record::new("test_key", "test_value");
+        let id = record.id;
+
+        // Store
+        storage.store(&record).await.unwrap();
+        assert_eq!(storage.count().await.unwrap(), 1);
+        assert_eq!(storage.list().await.unwrap(), vec![id]);
+
+        // Get
+        let retrieved = storage.get(&id).await.unwrap();
+        assert!(retrieved.is_some());
+        assert_eq!(retrieved.unwrap().key, "test_key");
+        assert!(storage.get(&Uuid::new_v4()).await.unwrap().is_none());
+
+        // Update
+        let mut updated = record.clone();
+        updated.update_value("new_value");
+        storage.update(&updated).await.unwrap();
+        let retrieved = storage.get(&id).await.unwrap().unwrap();
+        assert_eq!(retrieved.value, serde_json::json!("new_value"));
+
+        let missing = Record::new("missing", "value");
+        let err = storage.update(&missing).await.unwrap_err();
+        assert!(matches!(err, crate::Error::NotFound(_)));
+
+        // Versioned update
+        let stored = storage.get(&id).await.unwrap().unwrap();
+        let version = stored.metadata.version;
+        let mut checked = stored.clone();
+        checked.add_tag("checked", "yes");
+        let written = storage.update_if_version(&checked, version).await.unwrap();
+        assert_eq!(written.metadata.version, version + 1);
+        let retrieved = storage.get(&id).await.unwrap().unwrap();
+        assert_eq!(retrieved.metadata.version, version + 1);
+        assert!(retrieved.has_tag("checked"));
+
+        let err = storage.update_if_version(&stored, version).await.unwrap_err();
+        assert!(matches!(err, crate::Error::Conflict(_)), "{}", err);
+        assert!(storage.get(&id).await.unwrap().unwrap().has_tag("checked"));
+        let err = storage.update_if_version(&missing, 1).await.unwrap_err();
+        assert!(matches!(err, crate::Error::NotFound(_)));
+
+        // Delete
+        let deleted = storage.delete(&id).await.unwrap();
+        assert!(deleted);
+        assert!(!storage.delete(&id).await.unwrap());
+        assert_eq!(storage.count().await.unwrap(), 0);
+
+        // Clear
+        for i in 0..3 {
+            let record = Record::new(format!("key_{}", i), serde_json::json!({"n": i}));
+            storage.store(&record).await.unwrap();
+        }
+        assert_eq!(storage.count().await.unwrap(), 3);
+
+        // Query
+        let query = Query::new()
+            .key_prefix("key_")
+            .field("n", Predicate::Lte(serde_json::json!(1)))
+            .sort_by(SortKey::Key, SortOrder::Descending)
+            .limit(1);
+        let first = storage.query(&query).await.unwrap();
+        assert_eq!(first.records[0].key, "key_1");
+        let cursor = first.next_cursor.unwrap();
+        let second = storage.query(&query.clone().after(cursor)).await.unwrap();
+        assert_eq!(second.records[0].key, "key_0");
+        assert!(second.next_cursor.is_none());
+        assert!(storage.query(&Query::new().key_prefix("other")).await.unwrap().records.is_empty());
+
+        storage.clear().await.unwrap();
+        assert_eq!(storage.count().await.unwrap(), 0);
+        assert!(storage.list().await.unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn test_in_memory_storage() {
         let storage = InMemoryStorage::new();
@@ -205,6 +542,11 @@ mod tests {
         assert_eq!(storage.count().await.unwrap(), 0);
     }
 
+    #[tokio::test]
+    async fn test_in_memory_storage_contract() {
+        check_storage_contract(&InMemoryStorage::new()).await;
+    }
+
     #[tokio::test]
     async fn test_cached_storage() {
         let inner = InMemoryStorage::new();
@@ -223,4 +565,136 @@ mod tests {
         let retrieved2 = storage.get(&id).await.unwrap();
         assert!(retrieved2.is_some());
     }
+
+    fn increment(record: &mut Record) -> Result<()> {
+        let count = record.value.as_i64().unwrap_or_default();
+        record.update_value(count + 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_modify_retries_on_conflict() {
+        let storage = InMemoryStorage::new();
+        let record = Record::new("counter", 0);
+        storage.store(&record).await.unwrap();
+
+        // Another writer gets in between the first read and write
+        let mut calls = 0;
+        let updated = storage
+            .modify(&record.id, |current| {
+                calls += 1;
+                if calls == 1 {
+                    let mut other = storage.records.get_mut(&record.id).unwrap();
+                    other.update_value(10);
+                }
+                increment(current)
+            })
+            .await
+            .unwrap();
+        assert_eq!(calls, 2);
+        assert_eq!(updated.value, serde_json::json!(11));
+        assert_eq!(storage.get(&record.id).await.unwrap().unwrap().value, updated.value);
+
+        // Errors from the closure and missing records are not retried
+        let err = storage
+            .modify(&record.id, |_| Err(crate::Error::processing("bad input")))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, crate::Error::Processing(_)));
+        let err = storage.modify(&Uuid::new_v4(), increment).await.unwrap_err();
+        assert!(matches!(err, crate::Error::NotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_modify_gives_up_after_max_attempts() {
+        let storage = InMemoryStorage::new();
+        let record = Record::new("counter", 0);
+        storage.store(&record).await.unwrap();
+
+        let retry = RetryConfig {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            ..RetryConfig::default()
+        };
+        let mut calls = 0;
+        let err = storage
+            .modify_with(&record.id, &retry, |current| {
+                calls += 1;
+                // Lose the race every time
+                storage.records.get_mut(&record.id).unwrap().update_value(calls);
+                increment(current)
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, crate::Error::Conflict(_)), "{}", err);
+        assert_eq!(calls, 3);
+    }
+
+    #[derive(Debug, Default)]
+    struct SlowStorage {
+        inner: InMemoryStorage,
+    }
+
+    #[async_trait]
+    impl Storage for SlowStorage {
+        async fn store(&self, record: &Record) -> Result<()> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            self.inner.store(record).await
+        }
+
+        async fn get(&self, id: &Uuid) -> Result<Option<Record>> {
+            self.inner.get(id).await
+        }
+
+        async fn update(&self, record: &Record) -> Result<()> {
+            self.inner.update(record).await
+        }
+
+        async fn delete(&self, id: &Uuid) -> Result<bool> {
+            self.inner.delete(id).await
+        }
+
+        async fn list(&self) -> Result<Vec<Uuid>> {
+            self.inner.list().await
+        }
+
+        async fn count(&self) -> Result<usize> {
+            self.inner.count().await
+        }
+
+        async fn clear(&self) -> Result<()> {
+            self.inner.clear().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timeout_storage() {
+        let storage = TimeoutStorage::new(SlowStorage::default(), Duration::from_millis(10));
+        let record = Record::new("test_key", "test_value");
+
+        let err = storage.store(&record).await.unwrap_err();
+        assert!(matches!(err, crate::Error::Timeout(_)));
+        assert_eq!(storage.count().await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_metered_storage() {
+        use crate::metrics::tests::capture_metrics;
+
+        let (_, metrics) = capture_metrics(async {
+            let metrics = MetricsRecorder::new(true).with_label("storage", "primary");
+            let storage = MeteredStorage::new(InMemoryStorage::new(), metrics);
+            let record = Record::new("test_key", "test_value");
+
+            storage.store(&record).await.unwrap();
+            storage.get(&record.id).await.unwrap();
+            storage.update(&Record::new("missing", "value")).await.unwrap_err();
+        });
+
+        let store = [("storage", "primary"), ("operation", "store")];
+        assert_eq!(metrics.counter("storage_operations_total", &store), 1);
+        assert_eq!(metrics.counter("storage_operations_success", &[("operation", "get")]), 1);
+        assert_eq!(metrics.counter("storage_operations_failed", &[("operation", "update")]), 1);
+        assert_eq!(metrics.histogram_samples("storage_operation_duration_ms", &[]), 3);
+    }
 }
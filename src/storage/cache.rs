@@ -0,0 +1,794 @@
+//! Caching layer for storage backends
+
+use super::{Query, QueryPage, Storage};
+use crate::{metrics::MetricsRecorder, record::Record, Error, Result};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Strategy for choosing which entry to evict when the cache is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Evict the least recently used entry
+    #[default]
+    Lru,
+
+    /// Evict the least frequently used entry, oldest first on ties
+    Lfu,
+
+    /// LRU eviction guarded by a TinyLFU admission filter
+    ///
+    /// A new entry only replaces the LRU victim if it has been read or written
+    /// more often recently, which keeps one-off scans from flushing the cache.
+    TinyLfu,
+}
+
+/// Configuration for [`CachedStorage`]
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// Maximum number of cached entries, including negative entries
+    pub capacity: usize,
+
+    /// Eviction strategy
+    pub policy: EvictionPolicy,
+
+    /// Time after which a cached record expires
+    pub ttl: Option<Duration>,
+
+    /// Time for which a `get` miss is remembered, or `None` to disable the negative cache
+    pub negative_ttl: Option<Duration>,
+
+    /// Name used to label cache metrics
+    pub name: String,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1000,
+            policy: EvictionPolicy::default(),
+            ttl: None,
+            negative_ttl: None,
+            name: "storage".to_string(),
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Create a configuration with the given capacity
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Self::default()
+        }
+    }
+
+    /// Set the eviction policy
+    pub fn policy(mut self, policy: EvictionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Set the time-to-live of cached records
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Remember `get` misses for the given duration
+    pub fn negative_ttl(mut self, ttl: Duration) -> Self {
+        self.negative_ttl = Some(ttl);
+        self
+    }
+
+    /// Set the name used to label cache metrics
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+}
+
+/// Snapshot of cache statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups answered with a cached record
+    pub hits: u64,
+
+    /// Lookups answered by the negative cache without querying the inner store
+    pub negative_hits: u64,
+
+    /// Lookups that had to query the inner store
+    pub misses: u64,
+
+    /// Entries evicted to make room
+    pub evictions: u64,
+
+    /// Entries dropped because their TTL expired
+    pub expirations: u64,
+
+    /// Entries currently cached
+    pub entries: usize,
+}
+
+impl CacheStats {
+    /// Fraction of lookups served from the cache
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.negative_hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            (self.hits + self.negative_hits) as f64 / lookups as f64
+        }
+    }
+}
+
+/// Result of a cache lookup
+#[derive(Debug)]
+enum Lookup {
+    Hit(Box<Record>),
+    NegativeHit,
+    Miss,
+}
+
+/// A cached record, or a remembered absence when `record` is `None`
+#[derive(Debug)]
+struct Entry {
+    record: Option<Record>,
+    expires_at: Option<Instant>,
+    rank: (u64, u64),
+}
+
+/// Count-min sketch estimating recent access frequency for TinyLFU
+#[derive(Debug)]
+struct FrequencySketch {
+    counters: Vec<u8>,
+    mask: usize,
+    additions: usize,
+    sample_size: usize,
+}
+
+impl FrequencySketch {
+    const DEPTH: usize = 4;
+    const SEEDS: [u64; 4] = [
+        0x9e37_79b9_7f4a_7c15,
+        0xc2b2_ae3d_27d4_eb4f,
+        0x1656_67b1_9e37_79f9,
+        0x27d4_eb2f_1656_67c5,
+    ];
+    const MAX_COUNT: u8 = 15;
+
+    fn new(capacity: usize) -> Self {
+        let width = capacity.saturating_mul(8).max(256).next_power_of_two();
+        Self {
+            counters: vec![0; width * Self::DEPTH],
+            mask: width - 1,
+            additions: 0,
+            sample_size: width * 10,
+        }
+    }
+
+    fn slots(&self, id: &Uuid) -> [usize; 4] {
+        let (high, low) = id.as_u64_pair();
+        let hash = high ^ low.rotate_left(32);
+        let width = self.mask + 1;
+        let mut slots = [0; 4];
+        for (row, seed) in Self::SEEDS.iter().enumerate() {
+            let column = (hash.wrapping_mul(*seed) >> 32) as usize & self.mask;
+            slots[row] = row * width + column;
+        }
+        slots
+    }
+
+    fn increment(&mut self, id: &Uuid) {
+        for slot in self.slots(id) {
+            if self.counters[slot] < Self::MAX_COUNT {
+                self.counters[slot] += 1;
+            }
+        }
+
+        // Periodically halve all counters so old popularity fades
+        self.additions += 1;
+        if self.additions >= self.sample_size {
+            for counter in &mut self.counters {
+                *counter /= 2;
+            }
+            self.additions /= 2;
+        }
+    }
+
+    fn estimate(&self, id: &Uuid) -> u8 {
+        self.slots(id)
+            .iter()
+            .map(|slot| self.counters[*slot])
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+/// Cache contents, eviction order and statistics
+#[derive(Debug)]
+struct CacheState {
+    entries: HashMap<Uuid, Entry>,
+    /// Entries ordered by eviction priority; the first entry is evicted first
+    order: BTreeMap<(u64, u64), Uuid>,
+    frequencies: HashMap<Uuid, u64>,
+    sketch: Option<FrequencySketch>,
+    tick: u64,
+    /// Incremented on every write, so a slow miss cannot cache stale data
+    generation: u64,
+    stats: CacheStats,
+}
+
+impl CacheState {
+    fn new(config: &CacheConfig) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            frequencies: HashMap::new(),
+            sketch: (config.policy == EvictionPolicy::TinyLfu)
+                .then(|| FrequencySketch::new(config.capacity)),
+            tick: 0,
+            generation: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Compute the eviction rank of an entry after an access
+    fn next_rank(&mut self, policy: EvictionPolicy, id: &Uuid) -> (u64, u64) {
+        self.tick += 1;
+        match policy {
+            EvictionPolicy::Lfu => {
+                let frequency = self.frequencies.entry(*id).or_insert(0);
+                *frequency += 1;
+                (*frequency, self.tick)
+            }
+            EvictionPolicy::Lru | EvictionPolicy::TinyLfu => (0, self.tick),
+        }
+    }
+
+    fn touch(&mut self, policy: EvictionPolicy, id: &Uuid) {
+        let rank = self.next_rank(policy, id);
+        if let Some(entry) = self.entries.get_mut(id) {
+            self.order.remove(&entry.rank);
+            entry.rank = rank;
+            self.order.insert(rank, *id);
+        }
+    }
+
+    fn remove(&mut self, id: &Uuid) -> Option<Entry> {
+        self.frequencies.remove(id);
+        let entry = self.entries.remove(id)?;
+        self.order.remove(&entry.rank);
+        Some(entry)
+    }
+
+    fn lookup(&mut self, config: &CacheConfig, id: &Uuid) -> Lookup {
+        if let Some(sketch) = self.sketch.as_mut() {
+            sketch.increment(id);
+        }
+
+        let expired = match self.entries.get(id) {
+            None => {
+                self.stats.misses += 1;
+                return Lookup::Miss;
+            }
+            Some(entry) => entry.expires_at.is_some_and(|at| at <= Instant::now()),
+        };
+
+        if expired {
+            self.remove(id);
+            self.stats.expirations += 1;
+            self.stats.misses += 1;
+            return Lookup::Miss;
+        }
+
+        self.touch(config.policy, id);
+        match self.entries.get(id).and_then(|e| e.record.clone()) {
+            Some(record) => {
+                self.stats.hits += 1;
+                Lookup::Hit(Box::new(record))
+            }
+            None => {
+                self.stats.negative_hits += 1;
+                Lookup::NegativeHit
+            }
+        }
+    }
+
+    /// Cache a record, or an absence if `record` is `None`
+    ///
+    /// Returns the number of entries evicted to make room.
+    fn insert(&mut self, config: &CacheConfig, id: Uuid, record: Option<Record>) -> u64 {
+        if config.capacity == 0 {
+            return 0;
+        }
+
+        let ttl = if record.is_some() {
+            config.ttl
+        } else {
+            config.negative_ttl
+        };
+        let expires_at = ttl.map(|ttl| Instant::now() + ttl);
+
+        if let Some(entry) = self.entries.get_mut(&id) {
+            entry.record = record;
+            entry.expires_at = expires_at;
+            self.touch(config.policy, &id);
+            return 0;
+        }
+
+        let mut evicted = 0;
+        while self.entries.len() >= config.capacity {
+            let Some((_, victim)) = self.order.first_key_value().map(|(r, v)| (*r, *v)) else {
+                break;
+            };
+
+            if let Some(sketch) = &self.sketch {
+                if sketch.estimate(&id) <= sketch.estimate(&victim) {
+                    // The candidate is not popular enough to displace the victim
+                    return evicted;
+                }
+            }
+
+            self.remove(&victim);
+            self.stats.evictions += 1;
+            evicted += 1;
+        }
+
+        let rank = self.next_rank(config.policy, &id);
+        self.order.insert(rank, id);
+        self.entries.insert(
+            id,
+            Entry {
+                record,
+                expires_at,
+                rank,
+            },
+        );
+        evicted
+    }
+}
+
+/// Storage with caching layer
+///
+/// Reads are served from a bounded cache in front of the inner store.
+/// Writes go to the inner store first and then update the cache.
+#[derive(Debug)]
+pub struct CachedStorage<S: Storage> {
+    inner: S,
+    config: CacheConfig,
+    state: Mutex<CacheState>,
+    metrics: MetricsRecorder,
+}
+
+impl<S: Storage> CachedStorage<S> {
+    /// Create a new LRU cached storage holding up to `cache_size` records
+    pub fn new(inner: S, cache_size: usize) -> Self {
+        Self::with_config(inner, CacheConfig::new(cache_size))
+    }
+
+    /// Create a new cached storage with the given configuration
+    pub fn with_config(inner: S, config: CacheConfig) -> Self {
+        Self {
+            inner,
+            state: Mutex::new(CacheState::new(&config)),
+            config,
+            metrics: MetricsRecorder::default(),
+        }
+    }
+
+    /// Set the metrics recorder that receives cache statistics
+    pub fn with_metrics(mut self, metrics: MetricsRecorder) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Get the cache configuration
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    /// Get a snapshot of cache statistics
+    pub fn stats(&self) -> CacheStats {
+        let state = self.state.lock();
+        CacheStats {
+            entries: state.entries.len(),
+            ..state.stats
+        }
+    }
+
+    /// Drop every cached entry without touching the inner store
+    pub fn invalidate_all(&self) {
+        let mut state = self.state.lock();
+        state.entries.clear();
+        state.order.clear();
+        state.frequencies.clear();
+        state.generation += 1;
+    }
+
+    /// Cache the result of a write
+    fn cache_write(&self, id: Uuid, record: Option<Record>) {
+        let evicted = {
+            let mut state = self.state.lock();
+            state.generation += 1;
+            // Writes count as accesses, so written records can win admission
+            if let (Some(sketch), Some(_)) = (state.sketch.as_mut(), &record) {
+                sketch.increment(&id);
+            }
+            if record.is_none() && self.config.negative_ttl.is_none() {
+                state.remove(&id);
+                0
+            } else {
+                state.insert(&self.config, id, record)
+            }
+        };
+        self.record_evictions(evicted);
+    }
+
+    /// Drop the cached entry for a record, which may be stale
+    fn invalidate(&self, id: &Uuid) {
+        let mut state = self.state.lock();
+        state.generation += 1;
+        state.remove(id);
+    }
+
+    fn record_evictions(&self, evicted: u64) {
+        for _ in 0..evicted {
+            self.metrics.record_cache_eviction(&self.config.name, "capacity");
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Storage> Storage for CachedStorage<S> {
+    async fn store(&self, record: &Record) -> Result<()> {
+        self.inner.store(record).await?;
+        self.cache_write(record.id, Some(record.clone()));
+        Ok(())
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Option<Record>> {
+        let (lookup, expirations, generation) = {
+            let mut state = self.state.lock();
+            let before = state.stats.expirations;
+            let lookup = state.lookup(&self.config, id);
+            (lookup, state.stats.expirations - before, state.generation)
+        };
+
+        if expirations > 0 {
+            self.metrics.record_cache_eviction(&self.config.name, "expired");
+        }
+
+        match lookup {
+            Lookup::Hit(record) => {
+                self.metrics.record_cache_access(&self.config.name, true);
+                return Ok(Some(*record));
+            }
+            Lookup::NegativeHit => {
+                self.metrics.record_cache_access(&self.config.name, true);
+                return Ok(None);
+            }
+            Lookup::Miss => self.metrics.record_cache_access(&self.config.name, false),
+        }
+
+        let record = self.inner.get(id).await?;
+        if record.is_some() || self.config.negative_ttl.is_some() {
+            let evicted = {
+                let mut state = self.state.lock();
+                // Skip caching if a write happened while the inner store was queried
+                if state.generation == generation {
+                    state.insert(&self.config, *id, record.clone())
+                } else {
+                    0
+                }
+            };
+            self.record_evictions(evicted);
+        }
+
+        Ok(record)
+    }
+
+    async fn update(&self, record: &Record) -> Result<()> {
+        self.inner.update(record).await?;
+        self.cache_write(record.id, Some(record.clone()));
+        Ok(())
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<bool> {
+        match self.inner.delete(id).await {
+            Ok(deleted) => {
+                self.cache_write(*id, None);
+                Ok(deleted)
+            }
+            Err(e) => {
+                // The record may still exist, so it must not be cached as absent
+                self.invalidate(id);
+                Err(e)
+            }
+        }
+    }
+
+    async fn list(&self) -> Result<Vec<Uuid>> {
+        self.inner.list().await
+    }
+
+    async fn count(&self) -> Result<usize> {
+        self.inner.count().await
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.invalidate_all();
+        let cleared = self.inner.clear().await;
+        // Drop anything a concurrent `get` cached while the inner store was clearing
+        self.invalidate_all();
+        cleared
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.inner.flush().await
+    }
+
+    async fn update_if_version(&self, record: &Record, expected_version: u64) -> Result<Record> {
+        match self.inner.update_if_version(record, expected_version).await {
+            Ok(updated) => {
+                self.cache_write(updated.id, Some(updated.clone()));
+                Ok(updated)
+            }
+            Err(e) => {
+                // The caller probably read a stale copy from the cache
+                if matches!(e, Error::Conflict(_) | Error::NotFound(_)) {
+                    self.invalidate(&record.id);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn query(&self, query: &Query) -> Result<QueryPage> {
+        // Scans go straight to the inner store so they do not flush the cache
+        self.inner.query(query).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{tests::check_storage_contract, InMemoryStorage, StorageExt};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_cached_storage_contract() {
+        let config = CacheConfig::new(2).negative_ttl(Duration::from_secs(60));
+        check_storage_contract(&CachedStorage::with_config(InMemoryStorage::new(), config)).await;
+    }
+
+    /// Inner store that counts `get` calls and can be made to fail deletes
+    #[derive(Debug, Default)]
+    struct CountingStorage {
+        inner: InMemoryStorage,
+        gets: AtomicUsize,
+        fail_deletes: AtomicBool,
+    }
+
+    #[async_trait]
+    impl Storage for CountingStorage {
+        async fn store(&self, record: &Record) -> Result<()> {
+            self.inner.store(record).await
+        }
+
+        async fn get(&self, id: &Uuid) -> Result<Option<Record>> {
+            self.gets.fetch_add(1, Ordering::SeqCst);
+            self.inner.get(id).await
+        }
+
+        async fn update(&self, record: &Record) -> Result<()> {
+            self.inner.update(record).await
+        }
+
+        async fn delete(&self, id: &Uuid) -> Result<bool> {
+            if self.fail_deletes.load(Ordering::SeqCst) {
+                return Err(Error::storage("delete failed"));
+            }
+            self.inner.delete(id).await
+        }
+
+        async fn list(&self) -> Result<Vec<Uuid>> {
+            self.inner.list().await
+        }
+
+        async fn count(&self) -> Result<usize> {
+            self.inner.count().await
+        }
+
+        async fn clear(&self) -> Result<()> {
+            self.inner.clear().await
+        }
+    }
+
+    async fn store_all(storage: &impl Storage, count: usize) -> Vec<Record> {
+        let mut records = Vec::new();
+        for i in 0..count {
+            let record = Record::new(format!("key_{}", i), i);
+            storage.store(&record).await.unwrap();
+            records.push(record);
+        }
+        records
+    }
+
+    #[tokio::test]
+    async fn test_lru_eviction() {
+        let storage = CachedStorage::new(CountingStorage::default(), 2);
+        let records = store_all(&storage, 2).await;
+
+        // Touch the first record so the second becomes least recently used
+        storage.get(&records[0].id).await.unwrap();
+        storage.store(&Record::new("key_2", 2)).await.unwrap();
+
+        storage.get(&records[0].id).await.unwrap();
+        assert_eq!(storage.inner.gets.load(Ordering::SeqCst), 0);
+        storage.get(&records[1].id).await.unwrap();
+        assert_eq!(storage.inner.gets.load(Ordering::SeqCst), 1);
+
+        // The evicted record was a miss and is cached again, evicting `key_2`
+        storage.get(&records[1].id).await.unwrap();
+        let stats = storage.stats();
+        assert_eq!((stats.hits, stats.misses), (3, 1));
+        assert_eq!(stats.evictions, 2);
+    }
+
+    #[tokio::test]
+    async fn test_lfu_eviction() {
+        let config = CacheConfig::new(2).policy(EvictionPolicy::Lfu);
+        let storage = CachedStorage::with_config(CountingStorage::default(), config);
+        let records = store_all(&storage, 2).await;
+
+        // The second record is used more often, even though the first is more recent
+        for _ in 0..3 {
+            storage.get(&records[1].id).await.unwrap();
+        }
+        storage.get(&records[0].id).await.unwrap();
+        storage.store(&Record::new("key_2", 2)).await.unwrap();
+
+        storage.get(&records[1].id).await.unwrap();
+        assert_eq!(storage.inner.gets.load(Ordering::SeqCst), 0);
+        storage.get(&records[0].id).await.unwrap();
+        assert_eq!(storage.inner.gets.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_tiny_lfu_resists_scans() {
+        let config = CacheConfig::new(4).policy(EvictionPolicy::TinyLfu);
+        let storage = CachedStorage::with_config(CountingStorage::default(), config);
+        let hot = store_all(&storage, 4).await;
+        for _ in 0..5 {
+            for record in &hot {
+                storage.get(&record.id).await.unwrap();
+            }
+        }
+
+        // A one-off scan over many cold records must not displace the hot set
+        let cold = store_all(&storage.inner, 50).await;
+        for record in &cold {
+            storage.get(&record.id).await.unwrap();
+        }
+
+        let before = storage.inner.gets.load(Ordering::SeqCst);
+        for record in &hot {
+            storage.get(&record.id).await.unwrap();
+        }
+        assert_eq!(storage.inner.gets.load(Ordering::SeqCst), before);
+    }
+
+    #[tokio::test]
+    async fn test_tiny_lfu_admits_frequently_written_records() {
+        let config = CacheConfig::new(2).policy(EvictionPolicy::TinyLfu);
+        let storage = CachedStorage::with_config(CountingStorage::default(), config);
+        let hot = store_all(&storage, 2).await;
+        for record in &hot {
+            storage.get(&record.id).await.unwrap();
+        }
+
+        // Each write counts, so a record written often enough displaces a victim
+        let mut record = Record::new("written", 0);
+        storage.store(&record).await.unwrap();
+        for i in 1..3 {
+            record.update_value(i);
+            storage.update(&record).await.unwrap();
+        }
+
+        let before = storage.inner.gets.load(Ordering::SeqCst);
+        assert_eq!(storage.get(&record.id).await.unwrap().unwrap().value, 2);
+        assert_eq!(storage.inner.gets.load(Ordering::SeqCst), before);
+    }
+
+    #[tokio::test]
+    async fn test_ttl_expiry() {
+        let config = CacheConfig::new(10).ttl(Duration::from_millis(20));
+        let storage = CachedStorage::with_config(CountingStorage::default(), config);
+        let records = store_all(&storage, 1).await;
+
+        storage.get(&records[0].id).await.unwrap();
+        assert_eq!(storage.inner.gets.load(Ordering::SeqCst), 0);
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(storage.get(&records[0].id).await.unwrap().is_some());
+        assert_eq!(storage.inner.gets.load(Ordering::SeqCst), 1);
+        assert_eq!(storage.stats().expirations, 1);
+    }
+
+    #[tokio::test]
+    async fn test_modify_refreshes_stale_entries() {
+        let storage = CachedStorage::new(CountingStorage::default(), 10);
+        let record = Record::new("counter", 1);
+        storage.store(&record).await.unwrap();
+
+        // A writer bypassing the cache leaves the cached copy a version behind
+        let mut newer = record.clone();
+        newer.update_value(5);
+        storage.inner.update(&newer).await.unwrap();
+
+        let mut seen = Vec::new();
+        let updated = storage
+            .modify(&record.id, |current| {
+                seen.push(current.value.clone());
+                current.update_value(current.value.as_i64().unwrap() * 10);
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(seen, vec![serde_json::json!(1), serde_json::json!(5)]);
+        assert_eq!(updated.value, serde_json::json!(50));
+        assert_eq!(updated.metadata.version, newer.metadata.version + 1);
+
+        // The written record is cached
+        let gets = storage.inner.gets.load(Ordering::SeqCst);
+        assert_eq!(storage.get(&record.id).await.unwrap().unwrap().value, updated.value);
+        assert_eq!(storage.inner.gets.load(Ordering::SeqCst), gets);
+    }
+
+    #[tokio::test]
+    async fn test_negative_cache() {
+        let config = CacheConfig::new(10).negative_ttl(Duration::from_secs(60));
+        let storage = CachedStorage::with_config(CountingStorage::default(), config);
+        let absent = Uuid::new_v4();
+
+        for _ in 0..3 {
+            assert!(storage.get(&absent).await.unwrap().is_none());
+        }
+        assert_eq!(storage.inner.gets.load(Ordering::SeqCst), 1);
+
+        let stats = storage.stats();
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.negative_hits, 2);
+
+        // A store replaces the negative entry
+        let mut record = Record::new("key", "value");
+        record.id = absent;
+        storage.store(&record).await.unwrap();
+        assert!(storage.get(&absent).await.unwrap().is_some());
+
+        let stats = storage.stats();
+        assert_eq!((stats.hits, stats.negative_hits, stats.misses), (1, 2, 1));
+        assert_eq!(stats.hit_ratio(), 0.75);
+    }
+
+    #[tokio::test]
+    async fn test_failed_delete_is_not_cached_as_absent() {
+        let config = CacheConfig::new(10).negative_ttl(Duration::from_secs(60));
+        let storage = CachedStorage::with_config(CountingStorage::default(), config);
+        let records = store_all(&storage, 1).await;
+        let id = records[0].id;
+
+        storage.inner.fail_deletes.store(true, Ordering::SeqCst);
+        assert!(storage.delete(&id).await.is_err());
+
+        // The entry is dropped and the next get reads the record from the inner store
+        assert!(storage.get(&id).await.unwrap().is_some());
+        assert_eq!(storage.inner.gets.load(Ordering::SeqCst), 1);
+        assert_eq!(storage.stats().negative_hits, 0);
+    }
+}
@@ -0,0 +1,903 @@
+//! Durable file-backed storage
+//!
+//! Records are kept in an append-only log. Every store, update and delete
+//! appends an entry; an in-memory index maps each live record ID to the
+//! offset of its latest entry. On open the index is rebuilt by scanning the
+//! log. Damaged entries, such as a torn tail left by a crash, are moved to a
+//! quarantine file and scanning resumes at the next intact entry.
+//! Superseded entries are reclaimed by compaction, which rewrites the live
+//! records into a fresh log and atomically swaps it in.
+//!
+//! Each entry is laid out as a 9-byte header (payload length and CRC-32,
+//! both little-endian `u32`, then an operation byte) followed by the payload:
+//! the 16-byte record ID, plus the JSON-encoded record for puts. With
+//! [`FileStorageConfig::compression`] set the JSON is compressed; entries are
+//! recognised on read either way, so compression can be turned on or off for
+//! an existing log.
+
+use super::{check_version, missing, next_version, Storage};
+use crate::{
+    compression::{self, Compression},
+    metrics::MetricsRecorder,
+    record::Record,
+    Error, Result,
+};
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWriteExt},
+    sync::Mutex,
+};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+const LOG_FILE: &str = "data.log";
+const COMPACT_FILE: &str = "data.log.compact";
+const QUARANTINE_FILE: &str = "data.log.quarantine";
+const HEADER_LEN: u64 = 9;
+const ID_LEN: usize = 16;
+const OP_PUT: u8 = 1;
+const OP_DELETE: u8 = 2;
+
+/// When appended log entries are synced to disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Sync after every write; no acknowledged write is lost on a crash
+    #[default]
+    Always,
+
+    /// Sync on the first write after the interval has elapsed
+    Interval(Duration),
+
+    /// Leave syncing to the operating system and explicit [`FileStorage::sync`] calls
+    Never,
+}
+
+/// Configuration for [`FileStorage`]
+#[derive(Debug, Clone)]
+pub struct FileStorageConfig {
+    /// When appended entries are synced to disk
+    pub sync_policy: SyncPolicy,
+
+    /// Fraction of the log taken up by superseded entries that triggers compaction
+    pub compaction_ratio: f64,
+
+    /// Minimum number of superseded bytes before automatic compaction runs
+    pub compaction_min_bytes: u64,
+
+    /// Compression applied to stored records, if any
+    pub compression: Option<Compression>,
+}
+
+impl Default for FileStorageConfig {
+    fn default() -> Self {
+        Self {
+            sync_policy: SyncPolicy::default(),
+            compaction_ratio: 0.5,
+            compaction_min_bytes: 1024 * 1024,
+            compression: None,
+        }
+    }
+}
+
+impl FileStorageConfig {
+    /// Set the sync policy
+    pub fn sync_policy(mut self, policy: SyncPolicy) -> Self {
+        self.sync_policy = policy;
+        self
+    }
+
+    /// Set the superseded fraction of the log that triggers compaction
+    pub fn compaction_ratio(mut self, ratio: f64) -> Self {
+        self.compaction_ratio = ratio;
+        self
+    }
+
+    /// Set the minimum superseded bytes before automatic compaction runs
+    pub fn compaction_min_bytes(mut self, bytes: u64) -> Self {
+        self.compaction_min_bytes = bytes;
+        self
+    }
+
+    /// Disable automatic compaction
+    pub fn no_auto_compaction(mut self) -> Self {
+        self.compaction_min_bytes = u64::MAX;
+        self
+    }
+
+    /// Compress stored records
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+}
+
+/// Size statistics for a [`FileStorage`] log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStorageStats {
+    /// Number of live records
+    pub records: usize,
+
+    /// Total size of the log in bytes
+    pub log_bytes: u64,
+
+    /// Bytes taken up by superseded or deleted entries
+    pub garbage_bytes: u64,
+}
+
+/// Location of a record's latest entry in the log
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    offset: u64,
+    len: u64,
+}
+
+/// Mutable state of an open log
+#[derive(Debug)]
+struct LogState {
+    file: File,
+    index: HashMap<Uuid, Location>,
+    len: u64,
+    garbage: u64,
+    last_sync: Instant,
+    unsynced: bool,
+}
+
+/// Durable storage backed by an append-only log in a directory
+#[derive(Debug)]
+pub struct FileStorage {
+    dir: PathBuf,
+    config: FileStorageConfig,
+    state: Mutex<LogState>,
+    metrics: MetricsRecorder,
+}
+
+impl FileStorage {
+    /// Open or create storage in the given directory with default configuration
+    pub async fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        Self::open_with_config(dir, FileStorageConfig::default()).await
+    }
+
+    /// Open or create storage in the given directory
+    ///
+    /// Rebuilds the index from the log. Incomplete or corrupt entries are
+    /// copied to `data.log.quarantine` in the same directory and dropped from
+    /// the log; intact entries after them are kept.
+    pub async fn open_with_config(
+        dir: impl Into<PathBuf>,
+        config: FileStorageConfig,
+    ) -> Result<Self> {
+        if !(0.0..=1.0).contains(&config.compaction_ratio) {
+            return Err(Error::config("compaction_ratio must be between 0 and 1"));
+        }
+        if let Some(compression) = &config.compression {
+            compression.validate()?;
+        }
+
+        let dir = dir.into();
+        tokio::fs::create_dir_all(&dir).await?;
+
+        // A leftover compaction file means compaction never completed
+        let compact_path = dir.join(COMPACT_FILE);
+        if tokio::fs::try_exists(&compact_path).await? {
+            warn!(path = %compact_path.display(), "Removing incomplete compaction file");
+            tokio::fs::remove_file(&compact_path).await?;
+        }
+
+        let file = open_log(&dir.join(LOG_FILE)).await?;
+        let mut state = LogState {
+            file,
+            index: HashMap::new(),
+            len: 0,
+            garbage: 0,
+            last_sync: Instant::now(),
+            unsynced: false,
+        };
+        let skipped = recover(&mut state, &dir.join(QUARANTINE_FILE)).await?;
+
+        info!(
+            dir = %dir.display(),
+            records = state.index.len(),
+            log_bytes = state.len,
+            "Opened file storage"
+        );
+
+        let storage = Self {
+            dir,
+            config,
+            state: Mutex::new(state),
+            metrics: MetricsRecorder::default(),
+        };
+        // Rewrite the log so quarantined entries are not found again on the next open
+        if skipped > 0 {
+            storage.compact().await?;
+        }
+        Ok(storage)
+    }
+
+    /// Set the metrics recorder that receives compression statistics
+    pub fn with_metrics(mut self, metrics: MetricsRecorder) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Directory holding the log
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get size statistics for the log
+    pub async fn stats(&self) -> FileStorageStats {
+        let state = self.state.lock().await;
+        FileStorageStats {
+            records: state.index.len(),
+            log_bytes: state.len,
+            garbage_bytes: state.garbage,
+        }
+    }
+
+    /// Sync all appended entries to disk
+    pub async fn sync(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        sync_state(&mut state).await
+    }
+
+    /// Rewrite the log keeping only live records
+    pub async fn compact(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        self.compact_locked(&mut state).await
+    }
+
+    async fn compact_locked(&self, state: &mut LogState) -> Result<()> {
+        let before = state.len;
+        let compact_path = self.dir.join(COMPACT_FILE);
+        let mut compacted = File::create(&compact_path).await?;
+
+        let mut live: Vec<_> = state.index.iter().map(|(id, loc)| (*id, *loc)).collect();
+        live.sort_by_key(|(_, loc)| loc.offset);
+
+        let mut index = HashMap::with_capacity(live.len());
+        let mut offset = 0;
+        for (id, loc) in live {
+            let entry = read_entry_bytes(&mut state.file, loc).await?;
+            compacted.write_all(&entry).await?;
+            index.insert(id, Location { offset, len: loc.len });
+            offset += loc.len;
+        }
+
+        compacted.flush().await?;
+        compacted.sync_all().await?;
+        drop(compacted);
+
+        let log_path = self.dir.join(LOG_FILE);
+        tokio::fs::rename(&compact_path, &log_path).await?;
+        sync_dir(&self.dir).await?;
+
+        state.file = open_log(&log_path).await?;
+        state.index = index;
+        state.len = offset;
+        state.garbage = 0;
+        state.unsynced = false;
+
+        info!(dir = %self.dir.display(), before, after = offset, "Compacted file storage log");
+        Ok(())
+    }
+
+    /// Serialize a record into an entry body, compressing it if configured
+    fn encode_record(&self, record: &Record) -> Result<Vec<u8>> {
+        let json = serde_json::to_vec(record)?;
+        let Some(compression) = self.config.compression else {
+            return Ok(json);
+        };
+
+        let body = compression::compress(&json, compression)?;
+        self.metrics
+            .record_compression(compression.codec.name(), json.len() as u64, body.len() as u64);
+        Ok(body)
+    }
+
+    /// Append an entry and apply the sync and compaction policies
+    async fn append(&self, state: &mut LogState, op: u8, id: Uuid, body: &[u8]) -> Result<()> {
+        let entry = encode_entry(op, id, body)?;
+        let offset = state.len;
+
+        if let Err(e) = write_entry(&mut state.file, &entry).await {
+            // Drop whatever part of the entry made it to disk
+            state.file.set_len(offset).await?;
+            return Err(e);
+        }
+
+        let len = entry.len() as u64;
+        state.len += len;
+        state.unsynced = true;
+
+        let previous = if op == OP_PUT {
+            state.index.insert(id, Location { offset, len })
+        } else {
+            state.garbage += len;
+            state.index.remove(&id)
+        };
+        if let Some(previous) = previous {
+            state.garbage += previous.len;
+        }
+
+        let sync_due = match self.config.sync_policy {
+            SyncPolicy::Always => true,
+            SyncPolicy::Interval(interval) => state.last_sync.elapsed() >= interval,
+            SyncPolicy::Never => false,
+        };
+        if sync_due {
+            sync_state(state).await?;
+        }
+
+        if state.garbage >= self.config.compaction_min_bytes
+            && state.garbage as f64 >= state.len as f64 * self.config.compaction_ratio
+        {
+            // The entry is already durable, so a failed compaction must not fail the write
+            if let Err(e) = self.compact_locked(state).await {
+                warn!(dir = %self.dir.display(), error = %e, "File storage compaction failed");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Storage for FileStorage {
+    async fn store(&self, record: &Record) -> Result<()> {
+        let body = self.encode_record(record)?;
+        let mut state = self.state.lock().await;
+        self.append(&mut state, OP_PUT, record.id, &body).await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Option<Record>> {
+        let mut state = self.state.lock().await;
+        read_record(&mut state, id).await
+    }
+
+    async fn update(&self, record: &Record) -> Result<()> {
+        let body = self.encode_record(record)?;
+        let mut state = self.state.lock().await;
+        if !state.index.contains_key(&record.id) {
+            return Err(Error::not_found(format!(
+                "Record with ID {} not found",
+                record.id
+            )));
+        }
+        self.append(&mut state, OP_PUT, record.id, &body).await
+    }
+
+    async fn delete(&self, id: &Uuid) -> Result<bool> {
+        let mut state = self.state.lock().await;
+        if !state.index.contains_key(id) {
+            return Ok(false);
+        }
+        self.append(&mut state, OP_DELETE, *id, &[]).await?;
+        Ok(true)
+    }
+
+    async fn list(&self) -> Result<Vec<Uuid>> {
+        Ok(self.state.lock().await.index.keys().copied().collect())
+    }
+
+    async fn count(&self) -> Result<usize> {
+        Ok(self.state.lock().await.index.len())
+    }
+
+    async fn clear(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        state.file.set_len(0).await?;
+        state.file.sync_all().await?;
+        state.index.clear();
+        state.len = 0;
+        state.garbage = 0;
+        state.unsynced = false;
+        state.last_sync = Instant::now();
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.sync().await
+    }
+
+    async fn update_if_version(&self, record: &Record, expected_version: u64) -> Result<Record> {
+        let updated = next_version(record, expected_version);
+        let body = self.encode_record(&updated)?;
+        let mut state = self.state.lock().await;
+        let stored = read_record(&mut state, &record.id)
+            .await?
+            .ok_or_else(|| missing(&record.id))?;
+        check_version(&stored, expected_version)?;
+        self.append(&mut state, OP_PUT, record.id, &body).await?;
+        Ok(updated)
+    }
+}
+
+async fn open_log(path: &Path) -> Result<File> {
+    Ok(OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)
+        .await?)
+}
+
+async fn sync_state(state: &mut LogState) -> Result<()> {
+    if state.unsynced {
+        state.file.sync_data().await?;
+        state.unsynced = false;
+    }
+    state.last_sync = Instant::now();
+    Ok(())
+}
+
+/// Sync a directory so a rename within it is durable
+async fn sync_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    File::open(dir).await?.sync_all().await?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+fn encode_entry(op: u8, id: Uuid, body: &[u8]) -> Result<Vec<u8>> {
+    let payload_len = u32::try_from(ID_LEN + body.len())
+        .map_err(|_| Error::storage("Record too large for file storage"))?;
+
+    let mut entry = Vec::with_capacity(HEADER_LEN as usize + payload_len as usize);
+    entry.extend_from_slice(&payload_len.to_le_bytes());
+    entry.extend_from_slice(&[0; 4]);
+    entry.push(op);
+    entry.extend_from_slice(id.as_bytes());
+    entry.extend_from_slice(body);
+
+    let checksum = crc32fast::hash(&entry[8..]);
+    entry[4..8].copy_from_slice(&checksum.to_le_bytes());
+    Ok(entry)
+}
+
+/// Read and decode the latest entry for a record
+async fn read_record(state: &mut LogState, id: &Uuid) -> Result<Option<Record>> {
+    let Some(loc) = state.index.get(id).copied() else {
+        return Ok(None);
+    };
+
+    let entry = read_entry_bytes(&mut state.file, loc).await?;
+    let body = compression::decompress(&entry[HEADER_LEN as usize + ID_LEN..])?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+async fn write_entry(file: &mut File, entry: &[u8]) -> Result<()> {
+    file.write_all(entry).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+async fn read_entry_bytes(file: &mut File, loc: Location) -> Result<Vec<u8>> {
+    let mut entry = vec![0; loc.len as usize];
+    file.seek(SeekFrom::Start(loc.offset)).await?;
+    file.read_exact(&mut entry).await?;
+    Ok(entry)
+}
+
+/// Rebuild the index by scanning the log, skipping damaged entries
+///
+/// Damaged bytes are appended to `quarantine` before being dropped: entries
+/// up to the next intact entry are skipped, and a damaged tail is truncated.
+/// Returns the number of damaged bytes left inside the log.
+async fn recover(state: &mut LogState, quarantine: &Path) -> Result<u64> {
+    let file_len = state.file.metadata().await?.len();
+    let mut reader = tokio::io::BufReader::new(&mut state.file);
+    reader.seek(SeekFrom::Start(0)).await?;
+
+    let mut offset = 0;
+    let mut damaged = Vec::new();
+    while offset + HEADER_LEN <= file_len {
+        let Some((op, payload)) = read_entry(&mut reader, offset, file_len).await? else {
+            let next = find_next_entry(&mut reader, offset + 1, file_len).await?;
+            damaged.push((offset, next.unwrap_or(file_len)));
+            match next {
+                Some(next) => {
+                    reader.seek(SeekFrom::Start(next)).await?;
+                    offset = next;
+                    continue;
+                }
+                None => break,
+            }
+        };
+
+        let id = Uuid::from_slice(&payload[..ID_LEN])
+            .map_err(|e| Error::storage(format!("Invalid record ID in log: {}", e)))?;
+        let len = HEADER_LEN + payload.len() as u64;
+
+        let previous = if op == OP_PUT {
+            state.index.insert(id, Location { offset, len })
+        } else {
+            state.garbage += len;
+            state.index.remove(&id)
+        };
+        if let Some(previous) = previous {
+            state.garbage += previous.len;
+        }
+        offset += len;
+    }
+    // A tail shorter than a header cannot hold an entry
+    if offset < file_len && damaged.last().map_or(true, |&(_, end)| end < file_len) {
+        damaged.push((offset, file_len));
+    }
+
+    let mut skipped = 0;
+    for &(start, end) in &damaged {
+        warn!(
+            offset = start,
+            bytes = end - start,
+            quarantine = %quarantine.display(),
+            "Quarantining damaged storage log entries"
+        );
+        let mut bytes = vec![0; (end - start) as usize];
+        reader.seek(SeekFrom::Start(start)).await?;
+        reader.read_exact(&mut bytes).await?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(quarantine)
+            .await?;
+        file.write_all(&bytes).await?;
+        file.sync_all().await?;
+
+        if end < file_len {
+            skipped += end - start;
+        }
+    }
+
+    let len = match damaged.last() {
+        Some(&(start, end)) if end == file_len => start,
+        _ => file_len,
+    };
+    if len < file_len {
+        state.file.set_len(len).await?;
+        state.file.sync_all().await?;
+    }
+
+    debug!(records = state.index.len(), "Recovered storage index");
+    state.len = len;
+    state.garbage += skipped;
+    Ok(skipped)
+}
+
+/// Read the entry at `offset`, where the reader is positioned, if it is intact
+///
+/// The reader's position is unspecified afterwards if the entry is damaged.
+async fn read_entry<R>(reader: &mut R, offset: u64, file_len: u64) -> Result<Option<(u8, Vec<u8>)>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut header = [0u8; HEADER_LEN as usize];
+    reader.read_exact(&mut header).await?;
+    let Some((payload_len, checksum, op)) = parse_header(&header, offset, file_len) else {
+        return Ok(None);
+    };
+
+    let mut payload = vec![0; payload_len as usize];
+    reader.read_exact(&mut payload).await?;
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&[op]);
+    hasher.update(&payload);
+    Ok((hasher.finalize() == checksum).then_some((op, payload)))
+}
+
+/// Payload length, checksum and operation of a header that could start an entry
+fn parse_header(header: &[u8], offset: u64, file_len: u64) -> Option<(u64, u32, u8)> {
+    let payload_len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as u64;
+    let checksum = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    let op = header[8];
+
+    let fits = offset + HEADER_LEN + payload_len <= file_len;
+    (payload_len >= ID_LEN as u64 && fits && matches!(op, OP_PUT | OP_DELETE))
+        .then_some((payload_len, checksum, op))
+}
+
+/// Find the offset of the next intact entry at or after `from`
+async fn find_next_entry<R>(reader: &mut R, from: u64, file_len: u64) -> Result<Option<u64>>
+where
+    R: AsyncRead + AsyncSeek + Unpin,
+{
+    const WINDOW: u64 = 64 * 1024;
+
+    // Scan a window at a time, only reading whole entries for plausible headers
+    let mut base = from;
+    while base + HEADER_LEN <= file_len {
+        let mut window = vec![0; (file_len - base).min(WINDOW) as usize];
+        reader.seek(SeekFrom::Start(base)).await?;
+        reader.read_exact(&mut window).await?;
+
+        for (start, header) in window.windows(HEADER_LEN as usize).enumerate() {
+            let offset = base + start as u64;
+            if parse_header(header, offset, file_len).is_none() {
+                continue;
+            }
+            reader.seek(SeekFrom::Start(offset)).await?;
+            if read_entry(reader, offset, file_len).await?.is_some() {
+                return Ok(Some(offset));
+            }
+        }
+        base += window.len() as u64 - HEADER_LEN + 1;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::check_storage_contract;
+    use serde_json::json;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn test_file_storage_contract() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FileStorage::open(dir.path()).await.unwrap();
+        check_storage_contract(&storage).await;
+    }
+
+    #[tokio::test]
+    async fn test_records_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let kept = Record::new("kept", json!({"n": 1}));
+        let mut updated = Record::new("updated", json!({"n": 2}));
+        let deleted = Record::new("deleted", json!({"n": 3}));
+
+        {
+            let storage = FileStorage::open(dir.path()).await.unwrap();
+            storage.store(&kept).await.unwrap();
+            storage.store(&updated).await.unwrap();
+            storage.store(&deleted).await.unwrap();
+            updated.update_value(json!({"n": 20}));
+            storage.update(&updated).await.unwrap();
+            storage.delete(&deleted.id).await.unwrap();
+        }
+
+        let storage = FileStorage::open(dir.path()).await.unwrap();
+        assert_eq!(storage.count().await.unwrap(), 2);
+        assert_eq!(storage.get(&kept.id).await.unwrap().unwrap().value, json!({"n": 1}));
+        assert_eq!(storage.get(&updated.id).await.unwrap().unwrap().value, json!({"n": 20}));
+        assert!(storage.get(&deleted.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_recovery_truncates_torn_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = Record::new("key", "value");
+
+        let valid_len = {
+            let storage = FileStorage::open(dir.path()).await.unwrap();
+            storage.store(&record).await.unwrap();
+            storage.stats().await.log_bytes
+        };
+
+        // Simulate a crash part-way through appending the next entry
+        let mut log = std::fs::OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(LOG_FILE))
+            .unwrap();
+        log.write_all(&[40, 0, 0, 0, 1, 2, 3, 4, OP_PUT, 9, 9]).unwrap();
+        drop(log);
+
+        let storage = FileStorage::open(dir.path()).await.unwrap();
+        assert_eq!(storage.count().await.unwrap(), 1);
+        assert_eq!(storage.stats().await.log_bytes, valid_len);
+        let quarantined = std::fs::read(dir.path().join(QUARANTINE_FILE)).unwrap();
+        assert_eq!(quarantined, [40, 0, 0, 0, 1, 2, 3, 4, OP_PUT, 9, 9]);
+        assert_eq!(storage.get(&record.id).await.unwrap().unwrap().key, "key");
+
+        let another = Record::new("another", "value");
+        storage.store(&another).await.unwrap();
+        drop(storage);
+
+        let storage = FileStorage::open(dir.path()).await.unwrap();
+        assert_eq!(storage.count().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_recovery_quarantines_corrupt_entry_and_resumes() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = Record::new("first", "value");
+        let second = Record::new("second", "value");
+
+        let first_len = {
+            let storage = FileStorage::open(dir.path()).await.unwrap();
+            storage.store(&first).await.unwrap();
+            let len = storage.stats().await.log_bytes;
+            storage.store(&second).await.unwrap();
+            len
+        };
+
+        // Flip a byte inside the second entry's payload
+        let path = dir.path().join(LOG_FILE);
+        let mut bytes = std::fs::read(&path).unwrap();
+        let idx = first_len as usize + HEADER_LEN as usize + ID_LEN + 2;
+        bytes[idx] ^= 0xff;
+        std::fs::write(&path, bytes).unwrap();
+
+        let storage = FileStorage::open(dir.path()).await.unwrap();
+        assert!(storage.get(&first.id).await.unwrap().is_some());
+        assert!(storage.get(&second.id).await.unwrap().is_none());
+        assert!(dir.path().join(QUARANTINE_FILE).exists());
+
+        // Recovery resumes after the quarantined entry, so new writes land normally
+        let third = Record::new("third", "value");
+        storage.store(&third).await.unwrap();
+        let storage = FileStorage::open(dir.path()).await.unwrap();
+        assert!(storage.get(&third.id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_recovery_skips_corrupt_entry_in_the_middle() {
+        let dir = tempfile::tempdir().unwrap();
+        let records: Vec<_> = ["first", "second", "third"]
+            .iter()
+            .map(|key| Record::new(*key, "value"))
+            .collect();
+
+        let offsets = {
+            let storage = FileStorage::open(dir.path()).await.unwrap();
+            let mut offsets = Vec::new();
+            for record in &records {
+                offsets.push(storage.stats().await.log_bytes);
+                storage.store(record).await.unwrap();
+            }
+            offsets
+        };
+
+        // Flip a byte inside the second entry's payload
+        let path = dir.path().join(LOG_FILE);
+        let mut bytes = std::fs::read(&path).unwrap();
+        let idx = offsets[1] as usize + HEADER_LEN as usize + ID_LEN + 2;
+        bytes[idx] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let storage = FileStorage::open(dir.path()).await.unwrap();
+        assert!(storage.get(&records[0].id).await.unwrap().is_some());
+        assert!(storage.get(&records[1].id).await.unwrap().is_none());
+        assert_eq!(storage.get(&records[2].id).await.unwrap().unwrap().key, "third");
+
+        // The damaged entry was moved aside and compacted out of the log
+        let quarantined = std::fs::read(dir.path().join(QUARANTINE_FILE)).unwrap();
+        assert_eq!(quarantined, bytes[offsets[1] as usize..offsets[2] as usize]);
+        assert_eq!(storage.stats().await.garbage_bytes, 0);
+        drop(storage);
+
+        let storage = FileStorage::open(dir.path()).await.unwrap();
+        assert_eq!(storage.count().await.unwrap(), 2);
+        let quarantined_again = std::fs::read(dir.path().join(QUARANTINE_FILE)).unwrap();
+        assert_eq!(quarantined_again, quarantined);
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn test_compressed_records_mix_with_uncompressed() {
+        use crate::{compression::Compression, metrics::tests::capture_metrics};
+
+        let dir = tempfile::tempdir().unwrap();
+        let value = json!({"text": "repetitive ".repeat(50)});
+        let plain = Record::new("plain", value.clone());
+        let packed = Record::new("packed", value.clone());
+
+        let (_, metrics) = capture_metrics(async {
+            let storage = FileStorage::open(dir.path()).await.unwrap();
+            storage.store(&plain).await.unwrap();
+            let plain_bytes = storage.stats().await.log_bytes;
+            drop(storage);
+
+            let config = FileStorageConfig::default().compression(Compression::zstd().level(9));
+            let storage = FileStorage::open_with_config(dir.path(), config).await.unwrap();
+            storage.store(&packed).await.unwrap();
+            let packed_bytes = storage.stats().await.log_bytes - plain_bytes;
+            assert!(packed_bytes < plain_bytes / 2);
+            drop(storage);
+
+            // Either kind of entry reads back whatever the configuration
+            let storage = FileStorage::open(dir.path()).await.unwrap();
+            assert_eq!(storage.get(&plain.id).await.unwrap().unwrap().value, value);
+            assert_eq!(storage.get(&packed.id).await.unwrap().unwrap().value, value);
+        });
+
+        let raw = metrics.counter("compression_input_bytes_total", &[("codec", "zstd")]);
+        let compressed = metrics.counter("compression_output_bytes_total", &[("codec", "zstd")]);
+        assert!(compressed > 0 && compressed < raw);
+        assert_eq!(metrics.counter("compression_input_bytes_total", &[("codec", "gzip")]), 0);
+    }
+
+    #[tokio::test]
+    async fn test_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = FileStorageConfig::default().no_auto_compaction();
+        let storage = FileStorage::open_with_config(dir.path(), config).await.unwrap();
+
+        let mut record = Record::new("key", json!({"version": 0}));
+        storage.store(&record).await.unwrap();
+        for version in 1..10 {
+            record.update_value(json!({"version": version}));
+            storage.update(&record).await.unwrap();
+        }
+        let removed = Record::new("removed", "value");
+        storage.store(&removed).await.unwrap();
+        storage.delete(&removed.id).await.unwrap();
+
+        let before = storage.stats().await;
+        assert!(before.garbage_bytes > 0);
+
+        storage.compact().await.unwrap();
+        let after = storage.stats().await;
+        assert_eq!(after.records, 1);
+        assert_eq!(after.garbage_bytes, 0);
+        assert!(after.log_bytes < before.log_bytes);
+        assert_eq!(
+            storage.get(&record.id).await.unwrap().unwrap().value,
+            json!({"version": 9})
+        );
+        drop(storage);
+
+        let storage = FileStorage::open(dir.path()).await.unwrap();
+        assert_eq!(storage.stats().await, after);
+    }
+
+    #[tokio::test]
+    async fn test_automatic_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = FileStorageConfig::default()
+            .sync_policy(SyncPolicy::Never)
+            .compaction_min_bytes(1024)
+            .compaction_ratio(0.5);
+        let storage = FileStorage::open_with_config(dir.path(), config).await.unwrap();
+
+        let mut record = Record::new("key", json!({"version": 0}));
+        storage.store(&record).await.unwrap();
+        for version in 1..100 {
+            record.update_value(json!({"version": version}));
+            storage.update(&record).await.unwrap();
+        }
+
+        let stats = storage.stats().await;
+        assert!(stats.log_bytes < 100 * 200);
+        assert!(stats.garbage_bytes < 1024);
+        storage.sync().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_compaction_keeps_the_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = FileStorageConfig::default()
+            .compaction_min_bytes(1)
+            .compaction_ratio(0.1);
+        let storage = FileStorage::open_with_config(dir.path(), config).await.unwrap();
+
+        // A directory in the way of the compaction file makes compaction fail
+        std::fs::create_dir(dir.path().join(COMPACT_FILE)).unwrap();
+
+        let mut record = Record::new("key", 0);
+        storage.store(&record).await.unwrap();
+        record.update_value(1);
+        storage.update(&record).await.unwrap();
+
+        assert!(storage.stats().await.garbage_bytes > 0);
+        assert_eq!(storage.get(&record.id).await.unwrap().unwrap().value, 1);
+    }
+
+    #[tokio::test]
+    async fn test_leftover_compaction_file_is_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(COMPACT_FILE), b"partial").unwrap();
+
+        let storage = FileStorage::open(dir.path()).await.unwrap();
+        assert_eq!(storage.count().await.unwrap(), 0);
+        assert!(!dir.path().join(COMPACT_FILE).exists());
+    }
+}
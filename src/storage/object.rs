@@ -0,0 +1,474 @@
+//! Storage on S3-compatible object stores
+//!
+//! [`ObjectStoreStorage`] keeps each record as a JSON object named
+//! `<prefix>/<id>.json` in an [`ObjectStore`]. Two stores are provided:
+//! [`CloudObjectStore`], which talks to S3 (or any backend of the
+//! `object_store` crate), and [`FsObjectStore`], an emulator keeping objects
+//! as files in a local directory for offline tests and local development.
+//!
+//! Writes can be made conditional with a [`PutCondition`], mirroring the
+//! `If-None-Match` and `If-Match` preconditions of S3. An [`ObjectStore`]
+//! reports a failed precondition as [`Error::Concurrency`];
+//! [`ObjectStoreStorage::update_if_version`](Storage::update_if_version)
+//! treats one as a version conflict and reports [`Error::Conflict`] once its
+//! retries are used up, so [`StorageExt::modify`](super::StorageExt::modify)
+//! can re-read and try again.
+
+use super::{check_version, missing, next_version, Storage};
+use crate::{record::Record, Error, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{fmt, path::PathBuf, sync::Arc};
+use uuid::Uuid;
+
+mod fs;
+mod s3;
+
+pub use fs::FsObjectStore;
+pub use s3::{CloudObjectStore, S3Config};
+
+/// Suffix of record object names
+const RECORD_SUFFIX: &str = ".json";
+
+/// Times an update retries when the object changes between reading and writing it
+const MAX_UPDATE_ATTEMPTS: usize = 8;
+
+/// Precondition for writing an object
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum PutCondition {
+    /// Write unconditionally, replacing any existing object
+    #[default]
+    None,
+
+    /// Write only if no object exists under the key
+    IfAbsent,
+
+    /// Write only if the existing object has this entity tag
+    IfMatch(String),
+}
+
+/// Description of a stored object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectMeta {
+    /// Full key of the object
+    pub key: String,
+
+    /// Size in bytes
+    pub size: u64,
+
+    /// Entity tag, which changes whenever the object's content does
+    pub e_tag: String,
+
+    /// When the object was last written
+    pub last_modified: DateTime<Utc>,
+}
+
+/// An object's content and description
+#[derive(Debug, Clone)]
+pub struct Object {
+    /// Description of the object
+    pub meta: ObjectMeta,
+
+    /// Content of the object
+    pub body: Vec<u8>,
+}
+
+/// Minimal interface to an S3-style object store
+///
+/// Keys are `/`-separated paths. Failed [`PutCondition`]s are reported as
+/// [`Error::Concurrency`], except that [`PutCondition::IfMatch`] on a missing
+/// object may also fail with [`Error::NotFound`].
+#[async_trait]
+pub trait ObjectStore: Send + Sync + fmt::Debug {
+    /// Write an object if the condition holds
+    async fn put(&self, key: &str, body: Vec<u8>, condition: PutCondition) -> Result<()>;
+
+    /// Read an object
+    async fn get(&self, key: &str) -> Result<Option<Object>>;
+
+    /// Describe an object without reading its content
+    async fn head(&self, key: &str) -> Result<Option<ObjectMeta>>;
+
+    /// Delete an object, returning whether it existed
+    ///
+    /// Deleting a missing object succeeds. Stores that cannot tell whether
+    /// the object existed, such as S3, return `true`.
+    async fn delete(&self, key: &str) -> Result<bool>;
+
+    /// List the objects whose keys start with `prefix`, in key order
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectMeta>>;
+}
+
+/// Storage keeping records as JSON objects in an [`ObjectStore`]
+///
+/// Several storages can share a store by using different prefixes.
+/// [`update`](Storage::update) only replaces an existing object, using a
+/// conditional put so that a concurrent delete is not undone, and
+/// [`create`](Self::create) only writes new ones.
+#[derive(Debug, Clone)]
+pub struct ObjectStoreStorage {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+}
+
+impl ObjectStoreStorage {
+    /// Create storage keeping records under `prefix` in the given store
+    ///
+    /// Leading and trailing slashes of the prefix are ignored; an empty
+    /// prefix stores records at the root.
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: impl AsRef<str>) -> Self {
+        let prefix = prefix.as_ref().trim_matches('/');
+        Self {
+            store,
+            prefix: if prefix.is_empty() {
+                String::new()
+            } else {
+                format!("{}/", prefix)
+            },
+        }
+    }
+
+    /// Create storage in a filesystem emulator rooted at `dir`
+    pub async fn local(dir: impl Into<PathBuf>, prefix: impl AsRef<str>) -> Result<Self> {
+        let store = FsObjectStore::open(dir).await?;
+        Ok(Self::new(Arc::new(store), prefix))
+    }
+
+    /// Create storage in an S3 bucket
+    pub fn s3(config: &S3Config, prefix: impl AsRef<str>) -> Result<Self> {
+        Ok(Self::new(Arc::new(CloudObjectStore::s3(config)?), prefix))
+    }
+
+    /// The underlying object store
+    pub fn object_store(&self) -> &Arc<dyn ObjectStore> {
+        &self.store
+    }
+
+    /// Prefix of record object keys, including the trailing slash
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Store a record only if no record with its ID exists
+    ///
+    /// Fails with [`Error::Concurrency`] if the record already exists.
+    pub async fn create(&self, record: &Record) -> Result<()> {
+        let body = serde_json::to_vec(record)?;
+        match self.store.put(&self.key(&record.id), body, PutCondition::IfAbsent).await {
+            Err(Error::Concurrency(_)) => Err(Error::concurrency(format!(
+                "Record with ID {} already exists",
+                record.id
+            ))),
+            result => result,
+        }
+    }
+
+    fn key(&self, id: &Uuid) -> String {
+        format!("{}{}{}", self.prefix, id, RECORD_SUFFIX)
+    }
+
+    /// Parse the record ID from an object key directly under the prefix
+    fn id_from_key(&self, key: &str) -> Option<Uuid> {
+        let name = key.strip_prefix(&self.prefix)?.strip_suffix(RECORD_SUFFIX)?;
+        Uuid::parse_str(name).ok()
+    }
+
+    async fn record_objects(&self) -> Result<Vec<(Uuid, ObjectMeta)>> {
+        Ok(self
+            .store
+            .list(&self.prefix)
+            .await?
+            .into_iter()
+            .filter_map(|meta| Some((self.id_from_key(&meta.key)?, meta)))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl Storage for ObjectStoreStorage {
+    async fn store(&self, record: &Record) -> Result<()> {
+        let body = serde_json::to_vec(record)?;
+        self.store.put(&self.key(&record.id), body, PutCondition::None).await
+    }
+
+    async fn get(&self, id: &Uuid) -> Result<Option<Record>> {
+        match self.store.get(&self.key(id)).await? {
+            Some(object) => Ok(Some(serde_json::from_slice(&object.body)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn update(&self, record: &Record) -> Result<()> {
+        let key = self.key(&record.id);
+        let body = serde_json::to_vec(record)?;
+        let mut attempts = 0;
+        loop {
+            let Some(meta) = self.store.head(&key).await? else {
+                return Err(missing(&record.id));
+            };
+            let condition = PutCondition::IfMatch(meta.e_tag);
+            match self.store.put(&key, body.clone(), condition).await {
+                Err(Error::NotFound(_)) => return Err(missing(&record.id)),
+                // Changed since we looked; check it still exists and try again
+                Err(Error::Concurrency(_)) if attempts + 1 < MAX_UPDATE_ATTEMPTS => attempts += 1,
+                result => return result,
+            }
+        }
+    }
+
+    async fn update_if_version(&self, record: &Record, expected_version: u64) -> Result<Record> {
+        let key = self.key(&record.id);
+        let updated = next_version(record, expected_version);
+        let body = serde_json::to_vec(&updated)?;
+
+        let mut attempts = 0;
+        loop {
+            let object = self.store.get(&key).await?.ok_or_else(|| missing(&record.id))?;
+            let stored: Record = serde_json::from_slice(&object.body)?;
+            check_version(&stored, expected_version)?;
+
+            // The entity tag makes the version check and the write atomic
+            let condition = PutCondition::IfMatch(object.meta.e_tag);
+            match self.store.put(&key, body.clone(), condition).await {
+                Ok(()) => return Ok(updated),
+                Err(Error::NotFound(_)) => return Err(missing(&record.id)),
+                // Changed since we read it; compare against the new version
+                Err(Error::Concurrency(_)) if attempts + 1 < MAX_UPDATE_ATTEMPTS => attempts += 1,
+                // Still changing under us, which is a version conflict
+                Err(Error::Concurrency(msg)) => return Err(Error::conflict(msg)),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Delete a record
+    ///
+    /// Exact when the store reports whether the object existed, as
+    /// [`FsObjectStore`] does. On S3 the result is best-effort: two
+    /// concurrent deletes of the same record can both return `true`.
+    async fn delete(&self, id: &Uuid) -> Result<bool> {
+        let key = self.key(id);
+        if self.store.head(&key).await?.is_none() {
+            return Ok(false);
+        }
+        self.store.delete(&key).await
+    }
+
+    async fn list(&self) -> Result<Vec<Uuid>> {
+        Ok(self.record_objects().await?.into_iter().map(|(id, _)| id).collect())
+    }
+
+    async fn count(&self) -> Result<usize> {
+        Ok(self.record_objects().await?.len())
+    }
+
+    async fn clear(&self) -> Result<()> {
+        for (_, meta) in self.record_objects().await? {
+            self.store.delete(&meta.key).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Reject keys an S3-style store would not accept or that escape a directory
+pub(crate) fn validate_key(key: &str) -> Result<()> {
+    let valid = !key.is_empty()
+        && key
+            .split('/')
+            .all(|segment| !segment.is_empty() && segment != "." && segment != "..");
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::storage(format!("Invalid object key '{}'", key)))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::storage::tests::check_storage_contract;
+
+    /// Exercise conditional puts, prefix listing and deletes on a store
+    pub(crate) async fn check_object_store(store: &dyn ObjectStore) {
+        store.put("a/1.json", b"one".to_vec(), PutCondition::None).await.unwrap();
+        store.put("a/2.json", b"two".to_vec(), PutCondition::IfAbsent).await.unwrap();
+        store.put("ab/3.json", b"three".to_vec(), PutCondition::None).await.unwrap();
+
+        let object = store.get("a/1.json").await.unwrap().unwrap();
+        assert_eq!(object.body, b"one");
+        assert_eq!(object.meta.size, 3);
+        assert_eq!(store.head("a/1.json").await.unwrap().unwrap().e_tag, object.meta.e_tag);
+        assert!(store.get("a/missing").await.unwrap().is_none());
+        assert!(store.head("a/missing").await.unwrap().is_none());
+
+        let keys = |objects: Vec<ObjectMeta>| -> Vec<String> {
+            objects.into_iter().map(|meta| meta.key).collect()
+        };
+        assert_eq!(keys(store.list("a/").await.unwrap()), vec!["a/1.json", "a/2.json"]);
+        assert_eq!(
+            keys(store.list("a").await.unwrap()),
+            vec!["a/1.json", "a/2.json", "ab/3.json"]
+        );
+        assert!(store.list("b/").await.unwrap().is_empty());
+
+        // Create refuses to overwrite
+        let err = store.put("a/2.json", b"again".to_vec(), PutCondition::IfAbsent).await;
+        assert!(matches!(err, Err(Error::Concurrency(_))));
+        assert_eq!(store.get("a/2.json").await.unwrap().unwrap().body, b"two");
+
+        // Update succeeds with the current tag and fails with a stale one
+        let stale = PutCondition::IfMatch(object.meta.e_tag.clone());
+        store.put("a/1.json", b"uno".to_vec(), stale.clone()).await.unwrap();
+        let updated = store.head("a/1.json").await.unwrap().unwrap();
+        assert_ne!(updated.e_tag, object.meta.e_tag);
+        let err = store.put("a/1.json", b"eins".to_vec(), stale.clone()).await;
+        assert!(matches!(err, Err(Error::Concurrency(_))));
+        assert_eq!(store.get("a/1.json").await.unwrap().unwrap().body, b"uno");
+        let err = store.put("a/missing", b"x".to_vec(), stale).await.unwrap_err();
+        assert!(matches!(err, Error::Concurrency(_) | Error::NotFound(_)), "{}", err);
+        assert!(store.head("a/missing").await.unwrap().is_none());
+
+        assert!(store.delete("a/1.json").await.unwrap());
+        store.delete("a/1.json").await.unwrap();
+        assert_eq!(keys(store.list("a/").await.unwrap()), vec!["a/2.json"]);
+    }
+
+    #[tokio::test]
+    async fn test_object_store_storage_contract() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = ObjectStoreStorage::local(dir.path(), "records").await.unwrap();
+        check_storage_contract(&storage).await;
+    }
+
+    #[tokio::test]
+    async fn test_prefixes_are_isolated() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn ObjectStore> = Arc::new(FsObjectStore::open(dir.path()).await.unwrap());
+        let orders = ObjectStoreStorage::new(store.clone(), "/tenants/orders/");
+        let customers = ObjectStoreStorage::new(store.clone(), "tenants/orders-archive");
+        assert_eq!(orders.prefix(), "tenants/orders/");
+
+        let order = Record::new("o1", 1);
+        orders.store(&order).await.unwrap();
+        customers.store(&Record::new("c1", 2)).await.unwrap();
+        // Objects that are not records are ignored
+        store.put("tenants/orders/README", b"notes".to_vec(), PutCondition::None).await.unwrap();
+
+        assert_eq!(orders.list().await.unwrap(), vec![order.id]);
+        assert_eq!(customers.count().await.unwrap(), 1);
+        orders.clear().await.unwrap();
+        assert_eq!(orders.count().await.unwrap(), 0);
+        assert_eq!(customers.count().await.unwrap(), 1);
+        assert!(store.head("tenants/orders/README").await.unwrap().is_some());
+        let key = format!("tenants/orders/{}.json", order.id);
+        assert!(store.head(&key).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_create_and_update_are_conditional() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = ObjectStoreStorage::local(dir.path(), "").await.unwrap();
+        let mut record = Record::new("k", 1);
+
+        storage.create(&record).await.unwrap();
+        let err = storage.create(&record).await.unwrap_err();
+        assert!(err.to_string().contains("already exists"), "{}", err);
+
+        record.update_value(2);
+        storage.update(&record).await.unwrap();
+        assert_eq!(storage.get(&record.id).await.unwrap().unwrap().value, 2);
+
+        assert!(storage.delete(&record.id).await.unwrap());
+        let err = storage.update(&record).await.unwrap_err();
+        assert!(matches!(err, Error::NotFound(_)));
+        assert!(storage.get(&record.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_deletes_report_one_deletion() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = ObjectStoreStorage::local(dir.path(), "records").await.unwrap();
+        for _ in 0..10 {
+            let record = Record::new("k", 0);
+            storage.store(&record).await.unwrap();
+
+            let deletes = (0..4).map(|_| {
+                let storage = storage.clone();
+                tokio::spawn(async move { storage.delete(&record.id).await })
+            });
+            let results = futures::future::join_all(deletes).await;
+            let deleted = results.into_iter().filter(|r| *r.as_ref().unwrap().as_ref().unwrap());
+            assert_eq!(deleted.count(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_updates_all_land() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = ObjectStoreStorage::local(dir.path(), "records").await.unwrap();
+        let record = Record::new("k", 0);
+        storage.store(&record).await.unwrap();
+
+        let updates = (1..=4).map(|i| {
+            let storage = storage.clone();
+            let mut record = record.clone();
+            record.update_value(i);
+            tokio::spawn(async move { storage.update(&record).await })
+        });
+        for result in futures::future::join_all(updates).await {
+            result.unwrap().unwrap();
+        }
+        let value = storage.get(&record.id).await.unwrap().unwrap().value;
+        assert!((1..=4).any(|i| value == i), "{}", value);
+    }
+
+    /// Store whose conditional puts always lose the race
+    #[derive(Debug)]
+    struct RacingStore(FsObjectStore);
+
+    #[async_trait]
+    impl ObjectStore for RacingStore {
+        async fn put(&self, key: &str, body: Vec<u8>, condition: PutCondition) -> Result<()> {
+            match condition {
+                PutCondition::IfMatch(_) => Err(Error::concurrency("changed")),
+                condition => self.0.put(key, body, condition).await,
+            }
+        }
+
+        async fn get(&self, key: &str) -> Result<Option<Object>> {
+            self.0.get(key).await
+        }
+
+        async fn head(&self, key: &str) -> Result<Option<ObjectMeta>> {
+            self.0.head(key).await
+        }
+
+        async fn delete(&self, key: &str) -> Result<bool> {
+            self.0.delete(key).await
+        }
+
+        async fn list(&self, prefix: &str) -> Result<Vec<ObjectMeta>> {
+            self.0.list(prefix).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lost_update_races_are_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RacingStore(FsObjectStore::open(dir.path()).await.unwrap());
+        let storage = ObjectStoreStorage::new(Arc::new(store), "records");
+        let record = Record::new("k", 0);
+        storage.store(&record).await.unwrap();
+
+        let err = storage.update_if_version(&record, record.metadata.version).await.unwrap_err();
+        assert!(matches!(err, Error::Conflict(_)), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("a/b/c.json").is_ok());
+        for key in ["", "/a", "a/", "a//b", "a/../b", "./a"] {
+            assert!(validate_key(key).is_err(), "{}", key);
+        }
+    }
+}
@@ -0,0 +1,286 @@
+//! Object store emulator backed by a local directory
+
+use super::{validate_key, Object, ObjectMeta, ObjectStore, PutCondition};
+use crate::{Error, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    fs::Metadata,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::Mutex,
+};
+use uuid::Uuid;
+
+/// Directory under the root where objects are written before being renamed into place
+const STAGING_DIR: &str = ".staging";
+
+/// Object store emulator keeping each object as a file under a root directory
+///
+/// An object's key is its path relative to the root, so the contents can be
+/// inspected with ordinary tools. Objects are written to a staging file and
+/// renamed into place, so readers never see partial content. Entity tags are
+/// derived from file metadata (the inode on Unix, modification time and
+/// size), so listing objects never reads their content. Every write creates a
+/// new file, so on Unix each write gets a new tag even if the content is
+/// unchanged.
+///
+/// Conditional puts are atomic among users of the same instance; share it
+/// through an `Arc` rather than opening the directory twice. Unlike S3, a
+/// key cannot be both an object and a prefix of other keys (`a` and `a/b`),
+/// and keys may not start with `.staging/`.
+#[derive(Debug)]
+pub struct FsObjectStore {
+    root: PathBuf,
+    writes: Mutex<()>,
+}
+
+impl FsObjectStore {
+    /// Open or create an emulated store in the given directory
+    pub async fn open(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        let staging = root.join(STAGING_DIR);
+        // Leftovers from interrupted writes were never visible
+        if tokio::fs::try_exists(&staging).await? {
+            tokio::fs::remove_dir_all(&staging).await?;
+        }
+        tokio::fs::create_dir_all(&staging).await?;
+        Ok(Self {
+            root,
+            writes: Mutex::new(()),
+        })
+    }
+
+    /// Directory holding the objects
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        validate_key(key)?;
+        if key.split('/').next() == Some(STAGING_DIR) {
+            return Err(Error::storage(format!("Object key '{}' is reserved", key)));
+        }
+        Ok(self.root.join(key))
+    }
+
+    /// Object key of a path under the root
+    fn key_for(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let segments: Option<Vec<&str>> = relative.iter().map(|s| s.to_str()).collect();
+        Some(segments?.join("/"))
+    }
+
+    /// Write `body` to `path` through a staging file
+    async fn write_object(&self, path: &Path, body: &[u8]) -> Result<()> {
+        let staged = self.root.join(STAGING_DIR).join(Uuid::new_v4().to_string());
+        let mut file = tokio::fs::File::create(&staged).await?;
+        file.write_all(body).await?;
+        file.sync_all().await?;
+        drop(file);
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        if let Err(e) = tokio::fs::rename(&staged, path).await {
+            let _ = tokio::fs::remove_file(&staged).await;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ObjectStore for FsObjectStore {
+    async fn put(&self, key: &str, body: Vec<u8>, condition: PutCondition) -> Result<()> {
+        let path = self.path(key)?;
+        let _guard = self.writes.lock().await;
+
+        match &condition {
+            PutCondition::None => {}
+            PutCondition::IfAbsent => {
+                if tokio::fs::try_exists(&path).await? {
+                    return Err(Error::concurrency(format!("Object '{}' already exists", key)));
+                }
+            }
+            PutCondition::IfMatch(e_tag) => match stat_object(key, &path).await? {
+                None => return Err(Error::not_found(format!("Object '{}' not found", key))),
+                Some(current) if current.e_tag != *e_tag => {
+                    return Err(Error::concurrency(format!(
+                        "Object '{}' was modified concurrently",
+                        key
+                    )));
+                }
+                Some(_) => {}
+            },
+        }
+        self.write_object(&path, &body).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Object>> {
+        read_object(key, &self.path(key)?).await
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ObjectMeta>> {
+        stat_object(key, &self.path(key)?).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool> {
+        let path = self.path(key)?;
+        let _guard = self.writes.lock().await;
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectMeta>> {
+        // Only the directory holding the prefix's last segment can contain matches
+        let start = match prefix.rfind('/') {
+            Some(end) => self.root.join(&prefix[..end]),
+            None => self.root.clone(),
+        };
+
+        let mut objects = Vec::new();
+        let mut pending = vec![start];
+        while let Some(dir) = pending.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                let Some(key) = self.key_for(&path) else {
+                    continue;
+                };
+                if entry.file_type().await?.is_dir() {
+                    if key != STAGING_DIR && (key.starts_with(prefix) || prefix.starts_with(&key)) {
+                        pending.push(path);
+                    }
+                } else if key.starts_with(prefix) {
+                    // Deleted since the directory was read
+                    if let Some(meta) = stat_object(&key, &path).await? {
+                        objects.push(meta);
+                    }
+                }
+            }
+        }
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+}
+
+async fn read_object(key: &str, path: &Path) -> Result<Option<Object>> {
+    // Read the body and metadata through one handle so they belong to the same write
+    let read = async {
+        let mut file = tokio::fs::File::open(path).await?;
+        let metadata = file.metadata().await?;
+        let mut body = Vec::with_capacity(metadata.len() as usize);
+        file.read_to_end(&mut body).await?;
+        Ok::<_, std::io::Error>((body, metadata))
+    };
+    let (body, metadata) = match read.await {
+        Ok(read) => read,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(Some(Object {
+        meta: object_meta(key, &metadata)?,
+        body,
+    }))
+}
+
+/// Object metadata without reading the content
+async fn stat_object(key: &str, path: &Path) -> Result<Option<ObjectMeta>> {
+    match tokio::fs::metadata(path).await {
+        Ok(metadata) => object_meta(key, &metadata).map(Some),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn object_meta(key: &str, metadata: &Metadata) -> Result<ObjectMeta> {
+    let modified = metadata.modified()?;
+    Ok(ObjectMeta {
+        key: key.to_string(),
+        size: metadata.len(),
+        e_tag: e_tag(metadata, modified),
+        last_modified: DateTime::<Utc>::from(modified),
+    })
+}
+
+/// Entity tag for the file currently holding an object
+fn e_tag(metadata: &Metadata, modified: SystemTime) -> String {
+    let nanos = modified
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|since| since.as_nanos())
+        .unwrap_or_default();
+    #[cfg(unix)]
+    let file_id = std::os::unix::fs::MetadataExt::ino(metadata);
+    #[cfg(not(unix))]
+    let file_id = 0u64;
+    format!("\"{:x}-{:x}-{:x}\"", file_id, nanos, metadata.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::object::tests::check_object_store;
+
+    #[tokio::test]
+    async fn test_fs_object_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FsObjectStore::open(dir.path()).await.unwrap();
+        check_object_store(&store).await;
+
+        // Objects are plain files under the root
+        let content = std::fs::read(dir.path().join("a/2.json")).unwrap();
+        assert_eq!(content, b"two");
+        let err = store.put(".staging/x", Vec::new(), PutCondition::None).await;
+        assert!(matches!(err, Err(Error::Storage(_))));
+        assert!(store.get("../outside").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_objects_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FsObjectStore::open(dir.path()).await.unwrap();
+        store.put("x/y.json", b"{}".to_vec(), PutCondition::None).await.unwrap();
+        let e_tag = store.head("x/y.json").await.unwrap().unwrap().e_tag;
+        std::fs::write(dir.path().join(STAGING_DIR).join("partial"), b"half").unwrap();
+        drop(store);
+
+        let store = FsObjectStore::open(dir.path()).await.unwrap();
+        assert!(!dir.path().join(STAGING_DIR).join("partial").exists());
+        let listed = store.list("").await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].key, "x/y.json");
+        assert_eq!(listed[0].e_tag, e_tag);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_every_write_gets_a_new_e_tag() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FsObjectStore::open(dir.path()).await.unwrap();
+        store.put("k.json", b"same".to_vec(), PutCondition::None).await.unwrap();
+        let first = store.head("k.json").await.unwrap().unwrap();
+
+        // Same content and length, so only the file itself tells the writes apart
+        store.put("k.json", b"same".to_vec(), PutCondition::None).await.unwrap();
+        let second = store.head("k.json").await.unwrap().unwrap();
+        assert_ne!(first.e_tag, second.e_tag);
+        assert_eq!(store.list("").await.unwrap()[0].e_tag, second.e_tag);
+        assert_eq!(store.get("k.json").await.unwrap().unwrap().meta.e_tag, second.e_tag);
+
+        let stale = PutCondition::IfMatch(first.e_tag);
+        let err = store.put("k.json", b"new".to_vec(), stale).await;
+        assert!(matches!(err, Err(Error::Concurrency(_))));
+    }
+}
@@ -0,0 +1,270 @@
+//! Object store adapter for S3 and other `object_store` backends
+
+use super::{validate_key, Object, ObjectMeta, ObjectStore, PutCondition};
+use crate::{Error, Result};
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use object_store::{
+    aws::AmazonS3Builder, path::Path, ObjectStore as RemoteStore, PutMode, PutOptions,
+    UpdateVersion,
+};
+use serde::Deserialize;
+use std::{fmt, sync::Arc};
+
+/// Connection settings for an S3 bucket
+///
+/// Settings left unset fall back to the standard `AWS_*` environment
+/// variables, so credentials can come from the environment. Set `endpoint`
+/// (and usually `allow_http`) for S3-compatible services such as MinIO.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct S3Config {
+    /// Bucket name
+    pub bucket: String,
+
+    /// Region of the bucket
+    pub region: Option<String>,
+
+    /// Endpoint URL for S3-compatible services
+    pub endpoint: Option<String>,
+
+    /// Access key ID
+    pub access_key_id: Option<String>,
+
+    /// Secret access key
+    pub secret_access_key: Option<String>,
+
+    /// Allow plain HTTP endpoints
+    pub allow_http: bool,
+}
+
+impl S3Config {
+    /// Settings for the given bucket
+    pub fn new(bucket: impl Into<String>) -> Self {
+        Self {
+            bucket: bucket.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Set the region
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    /// Set the endpoint URL
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Set static credentials
+    pub fn credentials(
+        mut self,
+        access_key_id: impl Into<String>,
+        secret_access_key: impl Into<String>,
+    ) -> Self {
+        self.access_key_id = Some(access_key_id.into());
+        self.secret_access_key = Some(secret_access_key.into());
+        self
+    }
+
+    /// Allow plain HTTP endpoints
+    pub fn allow_http(mut self, allow: bool) -> Self {
+        self.allow_http = allow;
+        self
+    }
+}
+
+impl fmt::Debug for S3Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Config")
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("endpoint", &self.endpoint)
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &self.secret_access_key.as_ref().map(|_| "<redacted>"))
+            .field("allow_http", &self.allow_http)
+            .finish()
+    }
+}
+
+/// [`ObjectStore`] backed by the `object_store` crate
+///
+/// Use [`s3`](Self::s3) for S3 and S3-compatible services, or
+/// [`new`](Self::new) to wrap any other `object_store` backend. Conditional
+/// puts need backend support; for S3 they use `If-None-Match` and `If-Match`.
+#[derive(Debug, Clone)]
+pub struct CloudObjectStore {
+    inner: Arc<dyn RemoteStore>,
+}
+
+impl CloudObjectStore {
+    /// Wrap an `object_store` backend
+    pub fn new(inner: Arc<dyn RemoteStore>) -> Self {
+        Self { inner }
+    }
+
+    /// Connect to an S3 bucket
+    pub fn s3(config: &S3Config) -> Result<Self> {
+        if config.bucket.is_empty() {
+            return Err(Error::config("S3 bucket name must not be empty"));
+        }
+
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(&config.bucket)
+            .with_allow_http(config.allow_http);
+        if let Some(region) = &config.region {
+            builder = builder.with_region(region);
+        }
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        if let Some(id) = &config.access_key_id {
+            builder = builder.with_access_key_id(id);
+        }
+        if let Some(secret) = &config.secret_access_key {
+            builder = builder.with_secret_access_key(secret);
+        }
+
+        let store = builder
+            .build()
+            .map_err(|e| Error::config(format!("Invalid S3 configuration: {}", e)))?;
+        Ok(Self::new(Arc::new(store)))
+    }
+}
+
+#[async_trait]
+impl ObjectStore for CloudObjectStore {
+    async fn put(&self, key: &str, body: Vec<u8>, condition: PutCondition) -> Result<()> {
+        let mode = match condition {
+            PutCondition::None => PutMode::Overwrite,
+            PutCondition::IfAbsent => PutMode::Create,
+            PutCondition::IfMatch(e_tag) => PutMode::Update(UpdateVersion {
+                e_tag: Some(e_tag),
+                version: None,
+            }),
+        };
+        let options = PutOptions {
+            mode,
+            ..PutOptions::default()
+        };
+        self.inner
+            .put_opts(&path(key)?, body.into(), options)
+            .await
+            .map_err(|e| map_error(key, e))?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Object>> {
+        let result = match self.inner.get(&path(key)?).await {
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            result => result.map_err(|e| map_error(key, e))?,
+        };
+        let meta = convert_meta(&result.meta);
+        let body = result.bytes().await.map_err(|e| map_error(key, e))?;
+        Ok(Some(Object {
+            meta,
+            body: body.to_vec(),
+        }))
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ObjectMeta>> {
+        match self.inner.head(&path(key)?).await {
+            Ok(meta) => Ok(Some(convert_meta(&meta))),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(map_error(key, e)),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool> {
+        // S3 deletes succeed whether or not the object existed
+        match self.inner.delete(&path(key)?).await {
+            Ok(()) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(e) => Err(map_error(key, e)),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectMeta>> {
+        // Backends list whole path segments, so list the enclosing directory and filter
+        let dir = match prefix.rfind('/') {
+            Some(end) => Some(path(&prefix[..end])?),
+            None => None,
+        };
+        let mut objects: Vec<ObjectMeta> = self
+            .inner
+            .list(dir.as_ref())
+            .map_err(|e| map_error(prefix, e))
+            .try_filter_map(|meta| async move {
+                let meta = convert_meta(&meta);
+                Ok(meta.key.starts_with(prefix).then_some(meta))
+            })
+            .try_collect()
+            .await?;
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+}
+
+fn path(key: &str) -> Result<Path> {
+    validate_key(key)?;
+    Path::parse(key).map_err(|e| Error::storage(format!("Invalid object key '{}': {}", key, e)))
+}
+
+fn convert_meta(meta: &object_store::ObjectMeta) -> ObjectMeta {
+    ObjectMeta {
+        key: meta.location.to_string(),
+        size: meta.size,
+        e_tag: meta.e_tag.clone().unwrap_or_default(),
+        last_modified: meta.last_modified,
+    }
+}
+
+fn map_error(key: &str, error: object_store::Error) -> Error {
+    match error {
+        object_store::Error::NotFound { .. } => {
+            Error::not_found(format!("Object '{}' not found", key))
+        }
+        object_store::Error::AlreadyExists { .. } => {
+            Error::concurrency(format!("Object '{}' already exists", key))
+        }
+        object_store::Error::Precondition { .. } => {
+            Error::concurrency(format!("Object '{}' was modified concurrently", key))
+        }
+        e => Error::storage(format!("Object store request for '{}' failed: {}", key, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{
+        object::{tests::check_object_store, ObjectStoreStorage},
+        tests::check_storage_contract,
+    };
+    use object_store::memory::InMemory;
+
+    #[tokio::test]
+    async fn test_cloud_object_store() {
+        check_object_store(&CloudObjectStore::new(Arc::new(InMemory::new()))).await;
+
+        let store = Arc::new(CloudObjectStore::new(Arc::new(InMemory::new())));
+        check_storage_contract(&ObjectStoreStorage::new(store, "records")).await;
+    }
+
+    #[test]
+    fn test_s3_config() {
+        let config = S3Config::new("bucket")
+            .region("eu-west-1")
+            .endpoint("http://localhost:9000")
+            .allow_http(true)
+            .credentials("id", "secret");
+        assert!(!format!("{:?}", config).contains("\"secret\""));
+        assert!(CloudObjectStore::s3(&config).is_ok());
+
+        let err = CloudObjectStore::s3(&S3Config::default()).unwrap_err();
+        assert!(matches!(err, Error::Config(_)));
+    }
+}
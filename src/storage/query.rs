@@ -0,0 +1,547 @@
+//! Queries over stored records
+//!
+//! A [`Query`] filters records by key prefix, tags, [`RecordStatus`],
+//! creation and update time and predicates on fields of the value, then
+//! sorts them and returns one page at a time. Pages are resumed with the
+//! opaque [`Cursor`] of the previous page, which points just past its last
+//! record, so records added or removed between pages do not shift results.
+
+use crate::{
+    path::FieldPath,
+    record::{Record, RecordStatus},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::{cmp::Ordering, collections::BTreeMap, fmt, str::FromStr};
+use uuid::Uuid;
+
+/// Half-open time range; unset bounds are unbounded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeRange {
+    /// Earliest time included
+    pub start: Option<DateTime<Utc>>,
+
+    /// First time excluded
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    /// Check whether a time falls in the range
+    pub fn contains(&self, time: &DateTime<Utc>) -> bool {
+        self.start.map_or(true, |start| *time >= start) && self.end.map_or(true, |end| *time < end)
+    }
+}
+
+/// Condition on the values a [`FieldPath`] resolves to
+///
+/// Except for [`Ne`](Self::Ne), a predicate holds if any value at the path
+/// satisfies it, so wildcard paths match when one element does. Ordering
+/// comparisons only hold between numbers or between strings.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    /// Equal to the value; numbers compare numerically
+    Eq(Value),
+
+    /// No value at the path equals the value, including when there is none
+    Ne(Value),
+
+    /// Greater than the value
+    Gt(Value),
+
+    /// Greater than or equal to the value
+    Gte(Value),
+
+    /// Less than the value
+    Lt(Value),
+
+    /// Less than or equal to the value
+    Lte(Value),
+
+    /// Equal to one of the values
+    In(Vec<Value>),
+
+    /// String starting with the prefix
+    StartsWith(String),
+
+    /// The path resolves to a value, which may be `null`
+    Exists,
+}
+
+impl Predicate {
+    /// Check the predicate against the values found at a path
+    fn holds(&self, values: &[&Value]) -> bool {
+        let any = |f: &dyn Fn(&Value) -> bool| values.iter().any(|value| f(value));
+        match self {
+            Predicate::Eq(expected) => any(&|value| equal(value, expected)),
+            Predicate::Ne(expected) => !any(&|value| equal(value, expected)),
+            Predicate::Gt(bound) => any(&|value| ordered(value, bound) == Some(Ordering::Greater)),
+            Predicate::Gte(bound) => any(&|value| {
+                matches!(ordered(value, bound), Some(Ordering::Greater | Ordering::Equal))
+            }),
+            Predicate::Lt(bound) => any(&|value| ordered(value, bound) == Some(Ordering::Less)),
+            Predicate::Lte(bound) => any(&|value| {
+                matches!(ordered(value, bound), Some(Ordering::Less | Ordering::Equal))
+            }),
+            Predicate::In(candidates) => {
+                any(&|value| candidates.iter().any(|candidate| equal(value, candidate)))
+            }
+            Predicate::StartsWith(prefix) => {
+                any(&|value| value.as_str().is_some_and(|s| s.starts_with(prefix.as_str())))
+            }
+            Predicate::Exists => !values.is_empty(),
+        }
+    }
+}
+
+/// A [`Predicate`] applied at a field path
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldFilter {
+    /// Path into `Record.value`
+    pub path: FieldPath,
+
+    /// Condition on the values at the path
+    pub predicate: Predicate,
+}
+
+/// What query results are sorted by
+///
+/// Ties are broken by record ID, so the order is always total.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum SortKey {
+    /// Record ID
+    Id,
+
+    /// Record key
+    Key,
+
+    /// Creation time
+    #[default]
+    CreatedAt,
+
+    /// Last update time
+    UpdatedAt,
+
+    /// Record version
+    Version,
+
+    /// First value at a field path; records without one sort first
+    Field(FieldPath),
+}
+
+impl SortKey {
+    fn value(&self, record: &Record) -> Value {
+        match self {
+            SortKey::Id => Value::Null,
+            SortKey::Key => Value::from(record.key.as_str()),
+            SortKey::CreatedAt => Value::from(record.metadata.created_at.timestamp_micros()),
+            SortKey::UpdatedAt => Value::from(record.metadata.updated_at.timestamp_micros()),
+            SortKey::Version => Value::from(record.metadata.version),
+            SortKey::Field(path) => path.get(&record.value).cloned().unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// Sort direction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Smallest first
+    #[default]
+    Ascending,
+
+    /// Largest first
+    Descending,
+}
+
+/// Position just past the last record of a page
+///
+/// Cursors are opaque strings when displayed and parse back with
+/// [`FromStr`], so they can be handed to clients. A cursor only makes sense
+/// for the sort it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    value: Value,
+    id: Uuid,
+}
+
+impl Cursor {
+    fn after(record: &Record, sort: &SortKey) -> Self {
+        Self {
+            value: sort.value(record),
+            id: record.id,
+        }
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_json::json!([self.value, self.id]))
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::config(format!("Invalid query cursor '{}'", s));
+        let (value, id): (Value, Uuid) = serde_json::from_str(s).map_err(|_| invalid())?;
+        Ok(Self { value, id })
+    }
+}
+
+/// One page of query results
+#[derive(Debug, Clone)]
+pub struct QueryPage {
+    /// Matching records in sort order
+    pub records: Vec<Record>,
+
+    /// Cursor for the next page, if more records match
+    pub next_cursor: Option<Cursor>,
+}
+
+/// Filters, sort order and page bounds for [`Storage::query`](super::Storage::query)
+///
+/// All filters must hold for a record to match; an empty query matches
+/// every record.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    /// Prefix the record key must start with
+    pub key_prefix: Option<String>,
+
+    /// Tags the record must carry, with the required value or `None` for any value
+    pub tags: BTreeMap<String, Option<String>>,
+
+    /// Statuses the record may have; empty allows any
+    pub statuses: Vec<RecordStatus>,
+
+    /// Range `created_at` must fall in
+    pub created: TimeRange,
+
+    /// Range `updated_at` must fall in
+    pub updated: TimeRange,
+
+    /// Conditions on fields of the record value
+    pub fields: Vec<FieldFilter>,
+
+    /// Sort key
+    pub sort: SortKey,
+
+    /// Sort direction
+    pub order: SortOrder,
+
+    /// Maximum number of records per page
+    pub limit: Option<usize>,
+
+    /// Resume after this position
+    pub cursor: Option<Cursor>,
+}
+
+impl Query {
+    /// Create a query matching every record
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match keys starting with `prefix`
+    pub fn key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.key_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Only match records with the tag set to `value`
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), Some(value.into()));
+        self
+    }
+
+    /// Only match records carrying the tag, with any value
+    pub fn has_tag(mut self, key: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), None);
+        self
+    }
+
+    /// Also allow records with this status
+    ///
+    /// Without any status every status matches.
+    pub fn status(mut self, status: RecordStatus) -> Self {
+        self.statuses.push(status);
+        self
+    }
+
+    /// Only match records created at or after `time`
+    pub fn created_after(mut self, time: DateTime<Utc>) -> Self {
+        self.created.start = Some(time);
+        self
+    }
+
+    /// Only match records created before `time`
+    pub fn created_before(mut self, time: DateTime<Utc>) -> Self {
+        self.created.end = Some(time);
+        self
+    }
+
+    /// Only match records updated at or after `time`
+    pub fn updated_after(mut self, time: DateTime<Utc>) -> Self {
+        self.updated.start = Some(time);
+        self
+    }
+
+    /// Only match records updated before `time`
+    pub fn updated_before(mut self, time: DateTime<Utc>) -> Self {
+        self.updated.end = Some(time);
+        self
+    }
+
+    /// Only match records whose value satisfies `predicate` at `path`
+    pub fn field(mut self, path: impl Into<FieldPath>, predicate: Predicate) -> Self {
+        self.fields.push(FieldFilter {
+            path: path.into(),
+            predicate,
+        });
+        self
+    }
+
+    /// Sort by the given key and direction
+    pub fn sort_by(mut self, sort: SortKey, order: SortOrder) -> Self {
+        self.sort = sort;
+        self.order = order;
+        self
+    }
+
+    /// Return at most `limit` records per page
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Continue from the cursor of a previous page
+    pub fn after(mut self, cursor: Cursor) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+
+    /// Check whether a record passes every filter
+    pub fn matches(&self, record: &Record) -> bool {
+        let metadata = &record.metadata;
+        self.key_prefix
+            .as_ref()
+            .map_or(true, |prefix| record.key.starts_with(prefix.as_str()))
+            && self.tags.iter().all(|(key, expected)| match (record.get_tag(key), expected) {
+                (Some(actual), Some(expected)) => actual == expected,
+                (found, None) => found.is_some(),
+                (None, Some(_)) => false,
+            })
+            && (self.statuses.is_empty() || self.statuses.contains(&metadata.status))
+            && self.created.contains(&metadata.created_at)
+            && self.updated.contains(&metadata.updated_at)
+            && self
+                .fields
+                .iter()
+                .all(|filter| filter.predicate.holds(&filter.path.find(&record.value)))
+    }
+
+    /// Turn matching records into a page: sort, skip to the cursor and limit
+    ///
+    /// For backends implementing [`Storage::query`](super::Storage::query);
+    /// `matches` must already have passed [`matches`](Self::matches).
+    pub fn page(&self, matches: Vec<Record>) -> QueryPage {
+        let mut keyed: Vec<(Value, Record)> = matches
+            .into_iter()
+            .map(|record| (self.sort.value(&record), record))
+            .collect();
+        keyed.sort_by(|(a, x), (b, y)| self.compare((a, &x.id), (b, &y.id)));
+
+        let start = match &self.cursor {
+            Some(cursor) => keyed.partition_point(|(value, record)| {
+                self.compare((value, &record.id), (&cursor.value, &cursor.id)) != Ordering::Greater
+            }),
+            None => 0,
+        };
+        let mut remaining = keyed.into_iter().skip(start);
+        let records: Vec<Record> = match self.limit {
+            Some(limit) => remaining.by_ref().take(limit).map(|(_, record)| record).collect(),
+            None => remaining.by_ref().map(|(_, record)| record).collect(),
+        };
+
+        let next_cursor = match (records.last(), remaining.next()) {
+            (Some(last), Some(_)) => Some(Cursor::after(last, &self.sort)),
+            _ => None,
+        };
+        QueryPage {
+            records,
+            next_cursor,
+        }
+    }
+
+    /// Compare two (sort value, ID) positions in result order
+    fn compare(&self, (a, a_id): (&Value, &Uuid), (b, b_id): (&Value, &Uuid)) -> Ordering {
+        let ordering = total_order(a, b).then_with(|| a_id.cmp(b_id));
+        match self.order {
+            SortOrder::Ascending => ordering,
+            SortOrder::Descending => ordering.reverse(),
+        }
+    }
+}
+
+/// Order of two values of a comparable type, or `None` if the types differ
+fn ordered(value: &Value, bound: &Value) -> Option<Ordering> {
+    match (value, bound) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+fn equal(value: &Value, expected: &Value) -> bool {
+    match (value, expected) {
+        (Value::Number(_), Value::Number(_)) => ordered(value, expected) == Some(Ordering::Equal),
+        _ => value == expected,
+    }
+}
+
+/// Total order over JSON values for sorting: null, booleans, numbers,
+/// strings, then arrays and objects by their JSON text
+fn total_order(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Number(_) => 2,
+            Value::String(_) => 3,
+            Value::Array(_) => 4,
+            Value::Object(_) => 5,
+        }
+    }
+
+    match (a, b) {
+        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+        (Value::Number(_), Value::Number(_)) | (Value::String(_), Value::String(_)) => {
+            ordered(a, b).unwrap_or(Ordering::Equal)
+        }
+        (Value::Array(_), Value::Array(_)) | (Value::Object(_), Value::Object(_)) => {
+            a.to_string().cmp(&b.to_string())
+        }
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{InMemoryStorage, Storage};
+    use chrono::Duration;
+    use serde_json::json;
+
+    fn order(key: &str, total: f64, region: &str) -> Record {
+        let mut record = Record::new(
+            key,
+            json!({"total": total, "items": [{"sku": format!("{}-a", key)}, {"sku": "shared"}]}),
+        );
+        record.add_tag("region", region);
+        record
+    }
+
+    #[test]
+    fn test_filters() {
+        let mut record = order("order-1", 25.0, "eu");
+        record.mark_completed();
+        let matches = |query: Query| query.matches(&record);
+
+        assert!(matches(Query::new()));
+        assert!(matches(Query::new().key_prefix("order-")));
+        assert!(!matches(Query::new().key_prefix("customer-")));
+        assert!(matches(Query::new().tag("region", "eu").has_tag("region")));
+        assert!(!matches(Query::new().tag("region", "us")));
+        assert!(!matches(Query::new().has_tag("priority")));
+        assert!(matches(Query::new().status(RecordStatus::Failed).status(RecordStatus::Completed)));
+        assert!(!matches(Query::new().status(RecordStatus::Pending)));
+
+        let created = record.metadata.created_at;
+        let second_later = created + Duration::seconds(1);
+        assert!(matches(Query::new().created_after(created).created_before(second_later)));
+        assert!(!matches(Query::new().created_before(created)));
+        assert!(!matches(Query::new().updated_after(Utc::now() + Duration::seconds(1))));
+    }
+
+    #[test]
+    fn test_field_predicates() {
+        let record = order("order-1", 25.0, "eu");
+        let holds = |path: &str, predicate: Predicate| {
+            Query::new().field(path, predicate).matches(&record)
+        };
+
+        assert!(holds("total", Predicate::Eq(json!(25))));
+        assert!(holds("total", Predicate::Gt(json!(20))));
+        assert!(holds("total", Predicate::Lte(json!(25.0))));
+        assert!(!holds("total", Predicate::Lt(json!(25))));
+        // Types that do not compare never match
+        assert!(!holds("total", Predicate::Gt(json!("20"))));
+        assert!(holds("total", Predicate::In(vec![json!(1), json!(25)])));
+        assert!(holds("items[*].sku", Predicate::Eq(json!("shared"))));
+        assert!(holds("items[*].sku", Predicate::StartsWith("order-1".into())));
+        assert!(!holds("items[*].sku", Predicate::Ne(json!("shared"))));
+        assert!(holds("items[*].sku", Predicate::Ne(json!("other"))));
+        assert!(holds("missing", Predicate::Ne(json!(1))));
+        assert!(holds("items[0]", Predicate::Exists));
+        assert!(!holds("items[5]", Predicate::Exists));
+    }
+
+    #[tokio::test]
+    async fn test_sorting_and_pagination() {
+        let storage = InMemoryStorage::new();
+        let totals = [30.0, 10.0, 20.0, 10.0, 50.0];
+        for (i, total) in totals.iter().enumerate() {
+            storage.store(&order(&format!("order-{}", i), *total, "eu")).await.unwrap();
+        }
+        storage.store(&order("order-us", 5.0, "us")).await.unwrap();
+
+        let query = Query::new()
+            .tag("region", "eu")
+            .sort_by(SortKey::Field("total".into()), SortOrder::Descending)
+            .limit(2);
+        let mut pages = Vec::new();
+        let mut cursor = None;
+        loop {
+            let mut page_query = query.clone();
+            if let Some(cursor) = cursor.take() {
+                // Cursors survive a round trip through a string
+                page_query = page_query.after(Cursor::from_str(&format!("{}", cursor)).unwrap());
+            }
+            let page = storage.query(&page_query).await.unwrap();
+            let totals: Vec<_> = page.records.iter().map(|r| r.value["total"].as_f64()).collect();
+            pages.push(totals.into_iter().map(Option::unwrap).collect::<Vec<_>>());
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(pages, vec![vec![50.0, 30.0], vec![20.0, 10.0], vec![10.0]]);
+
+        let by_key = Query::new().sort_by(SortKey::Key, SortOrder::Ascending);
+        let page = storage.query(&by_key.key_prefix("order-").limit(10)).await.unwrap();
+        let keys: Vec<_> = page.records.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(keys, ["order-0", "order-1", "order-2", "order-3", "order-4", "order-us"]);
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cursor_skips_removed_records() {
+        let storage = InMemoryStorage::new();
+        let records: Vec<_> = (0..4).map(|i| order(&format!("k{}", i), i as f64, "eu")).collect();
+        for record in &records {
+            storage.store(record).await.unwrap();
+        }
+
+        let query = Query::new().sort_by(SortKey::Key, SortOrder::Ascending).limit(2);
+        let first = storage.query(&query).await.unwrap();
+        assert_eq!(first.records.len(), 2);
+
+        // The last record of the page is deleted before the next page is fetched
+        storage.delete(&records[1].id).await.unwrap();
+        let second = storage.query(&query.after(first.next_cursor.unwrap())).await.unwrap();
+        let keys: Vec<_> = second.records.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(keys, ["k2", "k3"]);
+
+        assert!(Cursor::from_str("not a cursor").is_err());
+    }
+}
@@ -0,0 +1,129 @@
+//! Streaming processing support
+//!
+//! Records are pulled from any [`Stream`] only as fast as results are
+//! consumed: processing runs with bounded concurrency and results flow through
+//! a bounded channel, so a slow consumer applies backpressure all the way back
+//! to the source instead of buffering without limit.
+
+use crate::{config::ProcessorConfig, processor::TransformOutcome, Error, Result};
+use futures::{Stream, StreamExt};
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::sync::mpsc;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+/// Options controlling stream processing
+#[derive(Debug, Clone, Copy)]
+pub struct StreamOptions {
+    /// Capacity of the bounded result channel
+    pub buffer_size: usize,
+
+    /// Maximum number of records processed concurrently
+    pub concurrency: usize,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self::from(&ProcessorConfig::default())
+    }
+}
+
+impl From<&ProcessorConfig> for StreamOptions {
+    fn from(config: &ProcessorConfig) -> Self {
+        Self {
+            buffer_size: config.buffer_size,
+            concurrency: config.max_workers,
+        }
+    }
+}
+
+/// Result of running one streamed record through a pipeline
+#[derive(Debug)]
+pub struct PipelineResult {
+    /// ID of the input record
+    pub record_id: Uuid,
+
+    /// Outcome of the pipeline for this record
+    pub outcome: Result<TransformOutcome>,
+}
+
+/// Stream of processing results fed by a background task
+///
+/// Dropping the stream stops the background task once its in-flight records
+/// finish.
+#[derive(Debug)]
+pub struct ResultStream<T> {
+    rx: mpsc::Receiver<T>,
+}
+
+impl<T> ResultStream<T> {
+    pub(crate) fn new(rx: mpsc::Receiver<T>) -> Self {
+        Self { rx }
+    }
+}
+
+impl<T> Stream for ResultStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// Drive `items` through `process` and emit results in input order
+///
+/// Each item is processed on its own task. Before an item is spawned,
+/// `panicked` captures what is needed to report it; if the task panics, the
+/// closure it returned turns the error into the item's result, so the output
+/// has one result per input.
+pub(crate) fn spawn_ordered<S, I, F, Fut, T, P, C>(
+    items: S,
+    options: StreamOptions,
+    process: F,
+    panicked: P,
+) -> ResultStream<T>
+where
+    S: Stream<Item = I> + Send + 'static,
+    I: Send + 'static,
+    F: Fn(I) -> Fut + Send + 'static,
+    Fut: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+    P: Fn(&I) -> C + Send + 'static,
+    C: FnOnce(Error) -> T + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(options.buffer_size.max(1));
+    let concurrency = options.concurrency.max(1);
+
+    tokio::spawn(async move {
+        let mut results = Box::pin(
+            items
+                .map(move |item| {
+                    let panicked = panicked(&item);
+                    let task = tokio::spawn(process(item));
+                    async move {
+                        task.await.unwrap_or_else(|e| {
+                            error!(error = %e, "Task panicked processing streamed record");
+                            panicked(Error::processing(format!(
+                                "Task processing streamed record failed: {}",
+                                e
+                            )))
+                        })
+                    }
+                })
+                .buffered(concurrency),
+        );
+
+        while let Some(result) = results.next().await {
+            if tx.send(result).await.is_err() {
+                debug!("Result stream dropped, stopping stream processing");
+                break;
+            }
+        }
+    });
+
+    ResultStream::new(rx)
+}
@@ -1,11 +1,28 @@
 //! Data transformation module
 
-use crate::{processor::Transform, record::Record, Result};
+use crate::{
+    path::FieldPath,
+    processor::{Transform, TransformOutcome},
+    record::Record,
+    Result,
+};
 use async_trait::async_trait;
 use serde_json::Value;
+use std::fmt;
+
+#[cfg(feature = "encryption")]
+mod encrypt;
+
+#[cfg(feature = "encryption")]
+#[cfg_attr(docsrs, doc(cfg(feature = "encryption")))]
+pub use encrypt::{DecryptTransform, EncryptTransform};
 
 /// Transform that filters records based on a predicate
-#[derive(Debug)]
+///
+/// When applied through [`Transform::apply`] (as `Processor` and `Pipeline`
+/// do), records failing the predicate are dropped and reported as filtered.
+/// Calling [`Transform::transform`] directly returns an error instead, since
+/// a plain `Record` result cannot express a dropped record.
 pub struct FilterTransform<F>
 where
     F: Fn(&Record) -> bool + Send + Sync,
@@ -27,6 +44,17 @@ where
     }
 }
 
+impl<F> fmt::Debug for FilterTransform<F>
+where
+    F: Fn(&Record) -> bool + Send + Sync,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FilterTransform")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
 #[async_trait]
 impl<F> Transform for FilterTransform<F>
 where
@@ -40,13 +68,20 @@ where
         }
     }
 
+    async fn apply(&self, record: Record) -> Result<TransformOutcome> {
+        if (self.predicate)(&record) {
+            Ok(TransformOutcome::Keep(record))
+        } else {
+            Ok(TransformOutcome::Drop)
+        }
+    }
+
     fn name(&self) -> &str {
         &self.name
     }
 }
 
 /// Transform that maps record values
-#[derive(Debug)]
 pub struct MapTransform<F>
 where
     F: Fn(Value) -> Value + Send + Sync,
@@ -68,6 +103,17 @@ where
     }
 }
 
+impl<F> fmt::Debug for MapTransform<F>
+where
+    F: Fn(Value) -> Value + Send + Sync,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapTransform")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
 #[async_trait]
 impl<F> Transform for MapTransform<F>
 where
@@ -83,17 +129,79 @@ where
     }
 }
 
+/// Transform that splits one record into several
+pub struct SplitTransform<F>
+where
+    F: Fn(Record) -> Vec<Record> + Send + Sync,
+{
+    name: String,
+    splitter: F,
+}
+
+impl<F> SplitTransform<F>
+where
+    F: Fn(Record) -> Vec<Record> + Send + Sync,
+{
+    /// Create a new split transform
+    pub fn new(name: impl Into<String>, splitter: F) -> Self {
+        Self {
+            name: name.into(),
+            splitter,
+        }
+    }
+}
+
+impl<F> fmt::Debug for SplitTransform<F>
+where
+    F: Fn(Record) -> Vec<Record> + Send + Sync,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SplitTransform")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl<F> Transform for SplitTransform<F>
+where
+    F: Fn(Record) -> Vec<Record> + Send + Sync,
+{
+    async fn transform(&self, record: Record) -> Result<Record> {
+        let mut records = (self.splitter)(record);
+        if records.len() == 1 {
+            Ok(records.remove(0))
+        } else {
+            Err(crate::Error::processing(format!(
+                "Split produced {} records; use apply to split",
+                records.len()
+            )))
+        }
+    }
+
+    async fn apply(&self, record: Record) -> Result<TransformOutcome> {
+        Ok(TransformOutcome::Split((self.splitter)(record)))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 /// Transform that enriches records with additional data
+///
+/// The field is a [`FieldPath`]; missing intermediate objects are created,
+/// and a wildcard such as `items[*].currency` sets the field on every element.
 #[derive(Debug)]
 pub struct EnrichTransform {
     name: String,
-    field: String,
+    field: FieldPath,
     value: Value,
 }
 
 impl EnrichTransform {
     /// Create a new enrich transform
-    pub fn new(name: impl Into<String>, field: impl Into<String>, value: Value) -> Self {
+    pub fn new(name: impl Into<String>, field: impl Into<FieldPath>, value: Value) -> Self {
         Self {
             name: name.into(),
             field: field.into(),
@@ -105,9 +213,7 @@ impl EnrichTransform {
 #[async_trait]
 impl Transform for EnrichTransform {
     async fn transform(&self, mut record: Record) -> Result<Record> {
-        if let Some(obj) = record.value.as_object_mut() {
-            obj.insert(self.field.clone(), self.value.clone());
-        }
+        self.field.set(&mut record.value, self.value.clone());
         Ok(record)
     }
 
@@ -117,18 +223,23 @@ impl Transform for EnrichTransform {
 }
 
 /// Transform that normalizes string fields
+///
+/// Each field is a [`FieldPath`], so nested and wildcard paths are supported.
 #[derive(Debug)]
 pub struct NormalizeTransform {
     name: String,
-    fields: Vec<String>,
+    fields: Vec<FieldPath>,
 }
 
 impl NormalizeTransform {
     /// Create a new normalize transform
-    pub fn new(name: impl Into<String>, fields: Vec<String>) -> Self {
+    pub fn new<P: Into<FieldPath>>(
+        name: impl Into<String>,
+        fields: impl IntoIterator<Item = P>,
+    ) -> Self {
         Self {
             name: name.into(),
-            fields,
+            fields: fields.into_iter().map(Into::into).collect(),
         }
     }
 }
@@ -136,12 +247,12 @@ impl NormalizeTransform {
 #[async_trait]
 impl Transform for NormalizeTransform {
     async fn transform(&self, mut record: Record) -> Result<Record> {
-        if let Some(obj) = record.value.as_object_mut() {
-            for field in &self.fields {
-                if let Some(Value::String(s)) = obj.get_mut(field) {
+        for field in &self.fields {
+            field.for_each_mut(&mut record.value, |value| {
+                if let Value::String(s) = value {
                     *s = s.trim().to_lowercase();
                 }
-            }
+            });
         }
         Ok(record)
     }
@@ -167,6 +278,24 @@ mod tests {
 
         let drop_record = Record::new("drop_this", "value");
         assert!(transform.transform(drop_record).await.is_err());
+
+        let drop_record = Record::new("drop_this", "value");
+        assert!(transform.apply(drop_record).await.unwrap().is_dropped());
+    }
+
+    #[tokio::test]
+    async fn test_split_transform() {
+        let transform = SplitTransform::new("split_items", |record: Record| {
+            record.value["items"]
+                .as_array()
+                .map(|items| items.iter().map(|i| Record::new(&record.key, i)).collect())
+                .unwrap_or_default()
+        });
+
+        let record = Record::new("order", json!({"items": [1, 2, 3]}));
+        let records = transform.apply(record).await.unwrap().into_records();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].value, json!(3));
     }
 
     #[tokio::test]
@@ -209,4 +338,27 @@ mod tests {
         let result = transform.transform(record).await.unwrap();
         assert_eq!(result.value["name"], json!("john doe"));
     }
+
+    #[tokio::test]
+    async fn test_transforms_accept_nested_paths() {
+        let record = Record::new(
+            "order",
+            json!({"customer": {"email": " A@X.COM "}, "items": [{"sku": "AB "}, {"sku": "Cd"}]}),
+        );
+
+        let normalize = NormalizeTransform::new("normalize", ["customer.email", "items[*].sku"]);
+        let enrich = EnrichTransform::new("enrich", "items[*].currency", json!("EUR"));
+        let audit = EnrichTransform::new("audit", "meta.source", json!("crm"));
+
+        let mut record = normalize.transform(record).await.unwrap();
+        for transform in [&enrich, &audit] {
+            record = transform.transform(record).await.unwrap();
+        }
+
+        assert_eq!(record.value["customer"]["email"], json!("a@x.com"));
+        assert_eq!(record.value["items"][0]["sku"], json!("ab"));
+        assert_eq!(record.value["items"][1]["sku"], json!("cd"));
+        assert_eq!(record.value["items"][1]["currency"], json!("EUR"));
+        assert_eq!(record.value["meta"]["source"], json!("crm"));
+    }
 }
@@ -0,0 +1,388 @@
+//! Transforms encrypting and decrypting record fields
+
+use crate::{
+    encryption::{
+        contains_envelope, decrypt_value, encrypt_value, envelope_key_id, EncryptionKey,
+        KeyProvider, ENCRYPTED_TAG,
+    },
+    path::FieldPath,
+    processor::Transform,
+    record::Record,
+    Error, Result,
+};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::{collections::HashMap, sync::Arc};
+
+/// Transform that encrypts fields with the key provider's current key
+///
+/// Each field is a [`FieldPath`]; every value it resolves to is replaced by
+/// an envelope (see [`encryption`](crate::encryption)) bound to the value's
+/// concrete path. Envelopes that decrypt at their path are left alone, so
+/// applying the transform twice is harmless; anything else, including
+/// plaintext that merely looks like an envelope, is encrypted. Records with
+/// newly encrypted fields get an [`ENCRYPTED_TAG`] tag naming the key.
+#[derive(Debug)]
+pub struct EncryptTransform {
+    name: String,
+    fields: Vec<FieldPath>,
+    keys: Arc<dyn KeyProvider>,
+    bind_to_key: bool,
+}
+
+impl EncryptTransform {
+    /// Create a transform encrypting the given fields
+    pub fn new<P: Into<FieldPath>>(
+        name: impl Into<String>,
+        fields: impl IntoIterator<Item = P>,
+        keys: Arc<dyn KeyProvider>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            fields: fields.into_iter().map(Into::into).collect(),
+            keys,
+            bind_to_key: false,
+        }
+    }
+
+    /// Also bind envelopes to the record key, so they only decrypt in a
+    /// record with the same key
+    ///
+    /// The [`DecryptTransform`] must be configured the same way.
+    pub fn bind_to_key(mut self, bind: bool) -> Self {
+        self.bind_to_key = bind;
+        self
+    }
+}
+
+#[async_trait]
+impl Transform for EncryptTransform {
+    async fn transform(&self, mut record: Record) -> Result<Record> {
+        let key = self.keys.current_key().await?;
+        let known = envelope_keys(self.keys.as_ref(), &self.fields, &record).await?;
+        let context = self.bind_to_key.then(|| record.key.clone());
+        let context = context.as_deref();
+        let mut encrypted = 0;
+        let mut result = Ok(());
+
+        for field in &self.fields {
+            field.for_each_path_mut(&mut record.value, |path, value| {
+                if result.is_err() || is_envelope_for(value, &known, path, context) {
+                    return;
+                }
+                match encrypt_value(value, &key, path, context) {
+                    Ok(envelope) => {
+                        *value = envelope;
+                        encrypted += 1;
+                    }
+                    Err(e) => result = Err(e),
+                }
+            });
+        }
+        result?;
+
+        if encrypted > 0 {
+            record.add_tag(ENCRYPTED_TAG, key.id());
+        }
+        Ok(record)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Transform that decrypts fields encrypted by [`EncryptTransform`]
+///
+/// Each envelope is decrypted with the key named inside it, so records
+/// encrypted under retired keys decrypt as long as the provider still has
+/// them. Envelopes only decrypt at the concrete path they were encrypted at;
+/// a field such as `cards[*].number` reaches the same paths as the
+/// `cards[0].number` and `cards[1].number` it covers. Values that are not
+/// envelopes are left alone. The [`ENCRYPTED_TAG`] tag is removed once no
+/// encrypted values remain.
+#[derive(Debug)]
+pub struct DecryptTransform {
+    name: String,
+    fields: Vec<FieldPath>,
+    keys: Arc<dyn KeyProvider>,
+    bind_to_key: bool,
+}
+
+impl DecryptTransform {
+    /// Create a transform decrypting the given fields
+    pub fn new<P: Into<FieldPath>>(
+        name: impl Into<String>,
+        fields: impl IntoIterator<Item = P>,
+        keys: Arc<dyn KeyProvider>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            fields: fields.into_iter().map(Into::into).collect(),
+            keys,
+            bind_to_key: false,
+        }
+    }
+
+    /// Decrypt envelopes bound to the record key by
+    /// [`EncryptTransform::bind_to_key`]
+    pub fn bind_to_key(mut self, bind: bool) -> Self {
+        self.bind_to_key = bind;
+        self
+    }
+}
+
+#[async_trait]
+impl Transform for DecryptTransform {
+    async fn transform(&self, mut record: Record) -> Result<Record> {
+        let mut keys = HashMap::new();
+        for (id, key) in envelope_keys(self.keys.as_ref(), &self.fields, &record).await? {
+            let key = key.ok_or_else(|| {
+                Error::config(format!("Unknown encryption key '{}'", id))
+            })?;
+            keys.insert(id, key);
+        }
+        let context = self.bind_to_key.then(|| record.key.clone());
+        let context = context.as_deref();
+        let mut result = Ok(());
+
+        for field in &self.fields {
+            field.for_each_path_mut(&mut record.value, |path, value| {
+                if result.is_err() {
+                    return;
+                }
+                let Some(key) = envelope_key_id(value).and_then(|id| keys.get(id)) else {
+                    return;
+                };
+                let decrypted = value
+                    .as_str()
+                    .map(|envelope| decrypt_value(envelope, key, path, context));
+                match decrypted {
+                    Some(Ok(decrypted)) => *value = decrypted,
+                    Some(Err(e)) => result = Err(e),
+                    None => {}
+                }
+            });
+        }
+        result.map_err(|e| {
+            Error::processing(format!("Failed to decrypt record {}: {}", record.id, e))
+        })?;
+
+        if !contains_envelope(&record.value) {
+            record.remove_tag(ENCRYPTED_TAG);
+        }
+        Ok(record)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Look up every key the envelopes at `fields` refer to
+///
+/// Keys the provider does not know map to `None`.
+async fn envelope_keys(
+    provider: &dyn KeyProvider,
+    fields: &[FieldPath],
+    record: &Record,
+) -> Result<HashMap<String, Option<EncryptionKey>>> {
+    let mut keys = HashMap::new();
+    for field in fields {
+        for value in field.find(&record.value) {
+            let Some(id) = envelope_key_id(value) else {
+                continue;
+            };
+            if !keys.contains_key(id) {
+                keys.insert(id.to_string(), provider.key(id).await?);
+            }
+        }
+    }
+    Ok(keys)
+}
+
+/// Check whether `value` is an envelope that decrypts at `path` in `context`
+fn is_envelope_for(
+    value: &Value,
+    keys: &HashMap<String, Option<EncryptionKey>>,
+    path: &str,
+    context: Option<&str>,
+) -> bool {
+    let Some(Some(key)) = envelope_key_id(value).and_then(|id| keys.get(id)) else {
+        return false;
+    };
+    value
+        .as_str()
+        .is_some_and(|envelope| decrypt_value(envelope, key, path, context).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::StaticKeyProvider;
+    use serde_json::json;
+
+    fn customer() -> Record {
+        Record::new(
+            "c1",
+            json!({
+                "name": "Ann",
+                "ssn": "123-45-6789",
+                "cards": [{"number": "4111111111111111"}, {"number": "5500000000000004"}]
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_and_decrypt_fields() {
+        let keys: Arc<dyn KeyProvider> =
+            Arc::new(StaticKeyProvider::new(EncryptionKey::generate("k1").unwrap()));
+        let fields = ["ssn", "cards[*].number", "missing"];
+        let encrypt = EncryptTransform::new("encrypt_pii", fields, keys.clone());
+        let decrypt = DecryptTransform::new("decrypt_pii", fields, keys);
+        let original = customer();
+
+        let encrypted = encrypt.transform(original.clone()).await.unwrap();
+        assert_eq!(encrypted.get_tag(ENCRYPTED_TAG).map(String::as_str), Some("k1"));
+        assert_eq!(encrypted.value["name"], json!("Ann"));
+        assert_eq!(envelope_key_id(&encrypted.value["ssn"]), Some("k1"));
+        assert_eq!(envelope_key_id(&encrypted.value["cards"][1]["number"]), Some("k1"));
+
+        // Already encrypted values are not encrypted again
+        let again = encrypt.transform(encrypted.clone()).await.unwrap();
+        assert_eq!(again.value, encrypted.value);
+
+        let decrypted = decrypt.transform(encrypted).await.unwrap();
+        assert_eq!(decrypted.value, original.value);
+        assert!(!decrypted.has_tag(ENCRYPTED_TAG));
+    }
+
+    #[tokio::test]
+    async fn test_rotation_and_unknown_keys() {
+        let old = EncryptionKey::generate("2024-01").unwrap();
+        let new = EncryptionKey::generate("2024-06").unwrap();
+
+        let before: Arc<dyn KeyProvider> = Arc::new(StaticKeyProvider::new(old.clone()));
+        let encrypted = EncryptTransform::new("encrypt", ["ssn"], before)
+            .transform(customer())
+            .await
+            .unwrap();
+
+        // After rotation new values use the new key and old ones still decrypt
+        let rotated: Arc<dyn KeyProvider> =
+            Arc::new(StaticKeyProvider::new(new.clone()).with_key(old));
+        let mixed = EncryptTransform::new("encrypt", ["name"], rotated.clone())
+            .transform(encrypted.clone())
+            .await
+            .unwrap();
+        assert_eq!(envelope_key_id(&mixed.value["ssn"]), Some("2024-01"));
+        assert_eq!(envelope_key_id(&mixed.value["name"]), Some("2024-06"));
+
+        // Decrypting only some of the encrypted fields keeps the tag
+        let partial = DecryptTransform::new("decrypt", ["name"], rotated.clone())
+            .transform(mixed.clone())
+            .await
+            .unwrap();
+        assert_eq!(partial.value["name"], json!("Ann"));
+        assert!(partial.has_tag(ENCRYPTED_TAG));
+
+        let decrypted = DecryptTransform::new("decrypt", ["name", "ssn"], rotated)
+            .transform(mixed)
+            .await
+            .unwrap();
+        assert_eq!(decrypted.value, customer().value);
+
+        let forgotten: Arc<dyn KeyProvider> = Arc::new(StaticKeyProvider::new(new));
+        let err = DecryptTransform::new("decrypt", ["ssn"], forgotten)
+            .transform(encrypted.clone())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Unknown encryption key '2024-01'"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_lookalike_plaintext_is_encrypted() {
+        let keys: Arc<dyn KeyProvider> =
+            Arc::new(StaticKeyProvider::new(EncryptionKey::generate("k1").unwrap()));
+        let encrypt = EncryptTransform::new("encrypt", ["ssn", "name"], keys.clone());
+        let decrypt = DecryptTransform::new("decrypt", ["ssn", "name"], keys);
+
+        let mut record = customer();
+        record.value["ssn"] = json!("enc:v1:k1:AAAA");
+        record.value["name"] = json!("enc:v1:unknown:AAAA");
+        let encrypted = encrypt.transform(record.clone()).await.unwrap();
+        assert_ne!(encrypted.value["ssn"], json!("enc:v1:k1:AAAA"));
+        assert_ne!(encrypted.value["name"], json!("enc:v1:unknown:AAAA"));
+        assert!(encrypted.has_tag(ENCRYPTED_TAG));
+
+        let decrypted = decrypt.transform(encrypted).await.unwrap();
+        assert_eq!(decrypted.value, record.value);
+    }
+
+    #[tokio::test]
+    async fn test_envelopes_do_not_move_between_fields_or_keys() {
+        let keys: Arc<dyn KeyProvider> =
+            Arc::new(StaticKeyProvider::new(EncryptionKey::generate("k1").unwrap()));
+        let encrypted = EncryptTransform::new("encrypt", ["ssn", "cards[*].number"], keys.clone())
+            .transform(customer())
+            .await
+            .unwrap();
+
+        // A wildcard and an index reach the same concrete path
+        let first = DecryptTransform::new("decrypt", ["cards[0].number"], keys.clone())
+            .transform(encrypted.clone())
+            .await
+            .unwrap();
+        assert_eq!(first.value["cards"][0], customer().value["cards"][0]);
+
+        let mut swapped = encrypted.clone();
+        swapped.value["cards"][0]["number"] = encrypted.value["cards"][1]["number"].clone();
+        let decrypt = DecryptTransform::new("decrypt", ["cards[*].number"], keys.clone());
+        assert!(decrypt.transform(swapped).await.is_err());
+
+        // Bound to the record key, an envelope copied into another record is
+        // treated as plaintext
+        let bound = EncryptTransform::new("encrypt", ["ssn"], keys.clone())
+            .bind_to_key(true)
+            .transform(customer())
+            .await
+            .unwrap();
+        let decrypt = DecryptTransform::new("decrypt", ["ssn"], keys.clone()).bind_to_key(true);
+        let mut other = Record::new("c2", customer().value);
+        other.value["ssn"] = bound.value["ssn"].clone();
+        assert!(decrypt.transform(other.clone()).await.is_err());
+        let reencrypted = EncryptTransform::new("encrypt", ["ssn"], keys)
+            .bind_to_key(true)
+            .transform(other)
+            .await
+            .unwrap();
+        assert_ne!(reencrypted.value["ssn"], bound.value["ssn"]);
+        assert_eq!(decrypt.transform(bound).await.unwrap().value, customer().value);
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_through_json_lines_files() {
+        use crate::{
+            sink::{JsonLinesSink, Sink},
+            source::{JsonLinesSource, Source},
+        };
+
+        let keys: Arc<dyn KeyProvider> =
+            Arc::new(StaticKeyProvider::new(EncryptionKey::generate("k1").unwrap()));
+        let fields = ["ssn", "cards[*].number"];
+        let encrypt = EncryptTransform::new("encrypt", fields, keys.clone());
+        let decrypt = DecryptTransform::new("decrypt", fields, keys);
+
+        let dir = tempfile::tempdir().unwrap();
+        let sink = JsonLinesSink::new(dir.path(), "customers");
+        sink.write(&encrypt.transform(customer()).await.unwrap()).await.unwrap();
+        sink.close().await.unwrap();
+
+        // The source builds a new record with a new ID
+        let mut source = JsonLinesSource::new(dir.path());
+        let read = source.next_record().await.unwrap().unwrap();
+        assert_eq!(envelope_key_id(&read.value["ssn"]), Some("k1"));
+        let decrypted = decrypt.transform(read).await.unwrap();
+        assert_eq!(decrypted.value, customer().value);
+    }
+}
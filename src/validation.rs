@@ -1,15 +1,42 @@
 //! Data validation module
 
-use crate::{error::ValidationError, record::Record, Result};
+use crate::{
+    error::{Severity, ValidationError},
+    metrics::MetricsRecorder,
+    path::FieldPath,
+    record::Record,
+    Error, Result,
+};
 use async_trait::async_trait;
 use std::sync::Arc;
+use tracing::debug;
+
+mod schema;
+
+pub use crate::error::ValidationReport;
+pub use schema::JsonSchemaRule;
 
 /// Trait for implementing validation rules
 #[async_trait]
-pub trait ValidationRule: Send + Sync {
+pub trait ValidationRule: Send + Sync + std::fmt::Debug {
     /// Validate a record
     async fn validate(&self, record: &Record) -> Result<()>;
     
+    /// Check a record and return every failure the rule finds
+    ///
+    /// The default implementation reports the error returned by
+    /// [`validate`](Self::validate). Rules that can find several failures at
+    /// once should override it. Errors other than validation failures are
+    /// returned as `Err`.
+    async fn check(&self, record: &Record) -> Result<Vec<ValidationError>> {
+        match self.validate(record).await {
+            Ok(()) => Ok(Vec::new()),
+            Err(Error::Validation(error)) => Ok(vec![error]),
+            Err(Error::ValidationFailed(report)) => Ok(report.into_issues()),
+            Err(e) => Err(e),
+        }
+    }
+    
     /// Name of the validation rule
     fn name(&self) -> &str;
     
@@ -17,64 +44,174 @@ pub trait ValidationRule: Send + Sync {
     fn description(&self) -> &str;
 }
 
+/// Rule registered with a [`Validator`]
+#[derive(Debug)]
+struct RegisteredRule {
+    rule: Arc<dyn ValidationRule>,
+    severity: Option<Severity>,
+}
+
+impl RegisteredRule {
+    /// Run the rule, applying the severity override to its failures
+    async fn check(&self, record: &Record) -> Result<Vec<ValidationError>> {
+        let mut issues = self.rule.check(record).await?;
+        if let Some(severity) = self.severity {
+            issues.iter_mut().for_each(|issue| issue.severity = severity);
+        }
+        Ok(issues)
+    }
+}
+
 /// Validator that applies multiple rules
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Validator {
-    rules: Vec<Arc<dyn ValidationRule>>,
+    rules: Vec<RegisteredRule>,
+    metrics: MetricsRecorder,
+}
+
+impl Default for Validator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Validator {
     /// Create a new validator
     pub fn new() -> Self {
-        Self { rules: Vec::new() }
+        Self {
+            rules: Vec::new(),
+            metrics: MetricsRecorder::default(),
+        }
+    }
+
+    /// Set the metrics recorder that receives per-rule results
+    pub fn set_metrics(&mut self, metrics: MetricsRecorder) {
+        self.metrics = metrics;
     }
 
     /// Add a validation rule
     pub fn add_rule(&mut self, rule: Arc<dyn ValidationRule>) {
-        self.rules.push(rule);
+        self.rules.push(RegisteredRule { rule, severity: None });
+    }
+
+    /// Add a validation rule whose failures are all reported with `severity`
+    ///
+    /// Use [`Severity::Warning`] for rules that should be reported without
+    /// rejecting the record.
+    pub fn add_rule_with_severity(&mut self, rule: Arc<dyn ValidationRule>, severity: Severity) {
+        self.rules.push(RegisteredRule {
+            rule,
+            severity: Some(severity),
+        });
     }
 
     /// Validate a record against all rules
+    ///
+    /// Stops at the first rule that reports an error. Warnings are logged and
+    /// do not fail the record.
     pub async fn validate(&self, record: &Record) -> Result<()> {
-        for rule in &self.rules {
-            rule.validate(record).await?;
+        self.validate_with(record, &self.metrics).await
+    }
+
+    /// Like [`validate`](Self::validate), reporting per-rule results to `metrics`
+    pub(crate) async fn validate_with(
+        &self,
+        record: &Record,
+        metrics: &MetricsRecorder,
+    ) -> Result<()> {
+        for entry in &self.rules {
+            let issues = entry.check(record).await?;
+            let (warnings, mut errors): (Vec<_>, Vec<_>) =
+                issues.into_iter().partition(ValidationError::is_warning);
+            metrics.record_validation(entry.rule.name(), errors.is_empty());
+
+            for warning in warnings {
+                debug!(key = %record.key, warning = %warning, "Validation warning");
+            }
+            if !errors.is_empty() {
+                return Err(errors.swap_remove(0).into());
+            }
         }
         Ok(())
     }
 
+    /// Validate a record against every rule and report all failures
+    ///
+    /// Returns `Err` only if a rule fails with something other than a
+    /// validation failure; use [`ValidationReport::is_valid`] to check the
+    /// outcome.
+    pub async fn validate_all(&self, record: &Record) -> Result<ValidationReport> {
+        self.validate_all_with(record, &self.metrics).await
+    }
+
+    /// Like [`validate_all`](Self::validate_all), reporting per-rule results to `metrics`
+    pub(crate) async fn validate_all_with(
+        &self,
+        record: &Record,
+        metrics: &MetricsRecorder,
+    ) -> Result<ValidationReport> {
+        let mut report = ValidationReport::new();
+        for entry in &self.rules {
+            let issues = entry.check(record).await?;
+            metrics.record_validation(entry.rule.name(), issues.iter().all(|i| i.is_warning()));
+            issues.into_iter().for_each(|issue| report.push(issue));
+        }
+        Ok(report)
+    }
+
     /// Get number of registered rules
     pub fn rule_count(&self) -> usize {
         self.rules.len()
     }
 }
 
+/// Turn the failures a rule found into the result of [`ValidationRule::validate`]
+fn issues_into_result(mut issues: Vec<ValidationError>) -> Result<()> {
+    match issues.len() {
+        0 => Ok(()),
+        1 => Err(issues.remove(0).into()),
+        _ => Err(Error::ValidationFailed(ValidationReport::from_issues(issues))),
+    }
+}
+
 /// Validation rule for required fields
+///
+/// The field is a [`FieldPath`], so nested fields such as `customer.email`
+/// and every element of an array with `items[*].sku` can be required.
 #[derive(Debug)]
 pub struct RequiredFieldRule {
-    field: String,
+    field: FieldPath,
 }
 
 impl RequiredFieldRule {
     /// Create a new required field rule
-    pub fn new(field: impl Into<String>) -> Self {
+    pub fn new(field: impl Into<FieldPath>) -> Self {
         Self {
             field: field.into(),
         }
     }
+
+    fn issues(&self, record: &Record) -> Vec<ValidationError> {
+        self.field
+            .resolve(&record.value)
+            .into_iter()
+            .filter(|m| m.value.is_none())
+            .map(|m| {
+                let message = format!("Field '{}' is required", m.path);
+                ValidationError::new(m.path, "required", message)
+            })
+            .collect()
+    }
 }
 
 #[async_trait]
 impl ValidationRule for RequiredFieldRule {
     async fn validate(&self, record: &Record) -> Result<()> {
-        if record.value.get(&self.field).is_none() {
-            return Err(ValidationError {
-                field: self.field.clone(),
-                rule: "required".to_string(),
-                message: format!("Field '{}' is required", self.field),
-            }
-            .into());
-        }
-        Ok(())
+        issues_into_result(self.issues(record))
+    }
+
+    async fn check(&self, record: &Record) -> Result<Vec<ValidationError>> {
+        Ok(self.issues(record))
     }
 
     fn name(&self) -> &str {
@@ -89,34 +226,38 @@ impl ValidationRule for RequiredFieldRule {
 /// Validation rule for non-empty strings
 #[derive(Debug)]
 pub struct NonEmptyStringRule {
-    field: String,
+    field: FieldPath,
 }
 
 impl NonEmptyStringRule {
     /// Create a new non-empty string rule
-    pub fn new(field: impl Into<String>) -> Self {
+    pub fn new(field: impl Into<FieldPath>) -> Self {
         Self {
             field: field.into(),
         }
     }
+
+    fn issues(&self, record: &Record) -> Vec<ValidationError> {
+        self.field
+            .resolve(&record.value)
+            .into_iter()
+            .filter(|m| m.value.and_then(|v| v.as_str()) == Some(""))
+            .map(|m| {
+                let message = format!("Field '{}' cannot be empty", m.path);
+                ValidationError::new(m.path, "non_empty", message)
+            })
+            .collect()
+    }
 }
 
 #[async_trait]
 impl ValidationRule for NonEmptyStringRule {
     async fn validate(&self, record: &Record) -> Result<()> {
-        if let Some(value) = record.value.get(&self.field) {
-            if let Some(s) = value.as_str() {
-                if s.is_empty() {
-                    return Err(ValidationError {
-                        field: self.field.clone(),
-                        rule: "non_empty".to_string(),
-                        message: format!("Field '{}' cannot be empty", self.field),
-                    }
-                    .into());
-                }
-            }
-        }
-        Ok(())
+        issues_into_result(self.issues(record))
+    }
+
+    async fn check(&self, record: &Record) -> Result<Vec<ValidationError>> {
+        Ok(self.issues(record))
     }
 
     fn name(&self) -> &str {
@@ -131,14 +272,14 @@ impl ValidationRule for NonEmptyStringRule {
 /// Validation rule for numeric ranges
 #[derive(Debug)]
 pub struct NumericRangeRule {
-    field: String,
+    field: FieldPath,
     min: Option<f64>,
     max: Option<f64>,
 }
 
 impl NumericRangeRule {
     /// Create a new numeric range rule
-    pub fn new(field: impl Into<String>) -> Self {
+    pub fn new(field: impl Into<FieldPath>) -> Self {
         Self {
             field: field.into(),
             min: None,
@@ -157,42 +298,33 @@ impl NumericRangeRule {
         self.max = Some(max);
         self
     }
+
+    fn issues(&self, record: &Record) -> Vec<ValidationError> {
+        let mut issues = Vec::new();
+        for m in self.field.resolve(&record.value) {
+            let Some(num) = m.value.and_then(|v| v.as_f64()) else {
+                continue;
+            };
+            if let Some(min) = self.min.filter(|min| num < *min) {
+                let message = format!("Field '{}' must be at least {}", m.path, min);
+                issues.push(ValidationError::new(m.path, "min_value", message));
+            } else if let Some(max) = self.max.filter(|max| num > *max) {
+                let message = format!("Field '{}' must be at most {}", m.path, max);
+                issues.push(ValidationError::new(m.path, "max_value", message));
+            }
+        }
+        issues
+    }
 }
 
 #[async_trait]
 impl ValidationRule for NumericRangeRule {
     async fn validate(&self, record: &Record) -> Result<()> {
-        if let Some(value) = record.value.get(&self.field) {
-            if let Some(num) = value.as_f64() {
-                if let Some(min) = self.min {
-                    if num < min {
-                        return Err(ValidationError {
-                            field: self.field.clone(),
-                            rule: "min_value".to_string(),
-                            message: format!(
-                                "Field '{}' must be at least {}",
-                                self.field, min
-                            ),
-                        }
-                        .into());
-                    }
-                }
-                if let Some(max) = self.max {
-                    if num > max {
-                        return Err(ValidationError {
-                            field: self.field.clone(),
-                            rule: "max_value".to_string(),
-                            message: format!(
-                                "Field '{}' must be at most {}",
-                                self.field, max
-                            ),
-                        }
-                        .into());
-                    }
-                }
-            }
-        }
-        Ok(())
+        issues_into_result(self.issues(record))
+    }
+
+    async fn check(&self, record: &Record) -> Result<Vec<ValidationError>> {
+        Ok(self.issues(record))
     }
 
     fn name(&self) -> &str {
@@ -242,6 +374,44 @@ mod tests {
         assert!(rule.validate(&invalid_record).await.is_err());
     }
 
+    #[tokio::test]
+    async fn test_rules_accept_nested_paths() {
+        let record = Record::new(
+            "order",
+            json!({
+                "customer": {"email": "a@example.com", "name": ""},
+                "items": [{"sku": "a", "price": 5}, {"price": -1}, {"sku": "c", "price": 500}]
+            }),
+        );
+
+        assert!(RequiredFieldRule::new("customer.email").validate(&record).await.is_ok());
+        assert!(RequiredFieldRule::new("customer.phone").validate(&record).await.is_err());
+
+        let missing = RequiredFieldRule::new("items[*].sku").check(&record).await.unwrap();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].field, "items[1].sku");
+
+        let empty = NonEmptyStringRule::new("customer.name").check(&record).await.unwrap();
+        assert_eq!(empty[0].field, "customer.name");
+
+        let rule = NumericRangeRule::new("items[*].price").min(0.0).max(100.0);
+        let out_of_range: Vec<_> = rule
+            .check(&record)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.field, e.rule))
+            .collect();
+        assert_eq!(
+            out_of_range,
+            [
+                ("items[1].price".to_string(), "min_value".to_string()),
+                ("items[2].price".to_string(), "max_value".to_string()),
+            ]
+        );
+        assert!(matches!(rule.validate(&record).await, Err(Error::ValidationFailed(_))));
+    }
+
     #[tokio::test]
     async fn test_validator() {
         let mut validator = Validator::new();
@@ -254,4 +424,48 @@ mod tests {
         let invalid_record = Record::new("test", json!({}));
         assert!(validator.validate(&invalid_record).await.is_err());
     }
+
+    #[tokio::test]
+    async fn test_validate_all_collects_every_failure() {
+        let mut validator = Validator::new();
+        validator.add_rule(Arc::new(RequiredFieldRule::new("name")));
+        validator.add_rule(Arc::new(NumericRangeRule::new("age").min(0.0)));
+        validator.add_rule_with_severity(
+            Arc::new(RequiredFieldRule::new("email")),
+            Severity::Warning,
+        );
+
+        let record = Record::new("test", json!({"age": -1}));
+        let report = validator.validate_all(&record).await.unwrap();
+        assert_eq!(report.issues().len(), 3);
+        assert!(!report.is_valid());
+
+        let rules: Vec<_> = report.errors().map(|e| e.rule.as_str()).collect();
+        assert_eq!(rules, ["required", "min_value"]);
+        let warning = report.warnings().next().unwrap();
+        assert_eq!(warning.field, "email");
+
+        match report.into_result() {
+            Err(Error::ValidationFailed(report)) => assert_eq!(report.errors().count(), 2),
+            other => panic!("expected validation failure, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_warnings_pass_validation() {
+        let mut validator = Validator::new();
+        validator.add_rule(Arc::new(RequiredFieldRule::new("name")));
+        validator.add_rule_with_severity(
+            Arc::new(NonEmptyStringRule::new("nickname")),
+            Severity::Warning,
+        );
+
+        let record = Record::new("test", json!({"name": "John", "nickname": ""}));
+        assert!(validator.validate(&record).await.is_ok());
+
+        let report = validator.validate_all(&record).await.unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.warnings().count(), 1);
+        assert!(report.to_string().starts_with("0 error(s), 1 warning(s): [warning]"));
+    }
 }
@@ -0,0 +1,641 @@
+//! JSON Schema validation rule
+
+use super::ValidationRule;
+use crate::{error::ValidationError, record::Record, Error, Result};
+use async_trait::async_trait;
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::{collections::HashMap, path::Path};
+
+/// Deepest chain of `$ref` lookups followed before giving up
+const MAX_REF_DEPTH: usize = 64;
+
+/// Validation rule that checks `Record.value` against a JSON Schema
+///
+/// Supports a subset of draft 2020-12: `type`, `enum`, `const`, `required`,
+/// `properties`, `additionalProperties`, `items`, `prefixItems`, `minItems`,
+/// `maxItems`, `minLength`, `maxLength`, `pattern`, `minimum`, `maximum`,
+/// `exclusiveMinimum`, `exclusiveMaximum` and local `$ref`s such as
+/// `#/$defs/address`. Other keywords are ignored.
+///
+/// Each violation is reported with the JSON pointer of the offending value
+/// as its field, e.g. `/items/0/price`, and the violated keyword as its rule.
+#[derive(Debug)]
+pub struct JsonSchemaRule {
+    root: Node,
+    refs: HashMap<String, Node>,
+}
+
+impl JsonSchemaRule {
+    /// Compile a schema
+    ///
+    /// Fails with a configuration error naming the schema location of the
+    /// first invalid keyword.
+    pub fn new(schema: Value) -> Result<Self> {
+        let mut compiler = Compiler {
+            document: &schema,
+            refs: HashMap::new(),
+            pending: Vec::new(),
+        };
+        let root = compiler.compile(&schema, "")?;
+
+        while let Some(pointer) = compiler.pending.pop() {
+            if compiler.refs.contains_key(&pointer) {
+                continue;
+            }
+            let target = schema.pointer(&pointer).ok_or_else(|| {
+                Error::config(format!("Schema $ref '#{}' does not resolve", pointer))
+            })?;
+            let node = compiler.compile(target, &pointer)?;
+            compiler.refs.insert(pointer, node);
+        }
+
+        Ok(Self {
+            root,
+            refs: compiler.refs,
+        })
+    }
+
+    /// Read and compile a schema from a JSON file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::new(serde_json::from_str(&contents)?)
+    }
+
+    /// Validate a value and return every violation
+    pub fn violations(&self, value: &Value) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        self.check_node(&self.root, value, "", 0, &mut errors);
+        errors
+    }
+
+    fn check_node(
+        &self,
+        node: &Node,
+        value: &Value,
+        path: &str,
+        depth: usize,
+        errors: &mut Vec<ValidationError>,
+    ) {
+        let schema = match node {
+            Node::Bool(true) => return,
+            Node::Bool(false) => {
+                errors.push(violation(path, "false", "No value is allowed here"));
+                return;
+            }
+            Node::Schema(schema) => schema,
+        };
+
+        // `depth` counts `$ref` hops at the current value and resets for children
+        if let Some(reference) = &schema.reference {
+            if depth >= MAX_REF_DEPTH {
+                let message = format!("Schema $ref chain deeper than {}", MAX_REF_DEPTH);
+                errors.push(violation(path, "$ref", message));
+            } else if let Some(target) = self.refs.get(reference) {
+                self.check_node(target, value, path, depth + 1, errors);
+            }
+        }
+
+        if let Some(types) = &schema.types {
+            if !types.iter().any(|t| t.matches(value)) {
+                let expected: Vec<_> = types.iter().map(|t| t.name()).collect();
+                let message = format!(
+                    "Expected {}, found {}",
+                    expected.join(" or "),
+                    type_name(value)
+                );
+                errors.push(violation(path, "type", message));
+                // Remaining keywords assume the declared type
+                return;
+            }
+        }
+
+        if let Some(allowed) = &schema.enum_values {
+            if !allowed.iter().any(|candidate| json_equal(candidate, value)) {
+                errors.push(violation(path, "enum", format!("{} is not an allowed value", value)));
+            }
+        }
+        if let Some(expected) = &schema.const_value {
+            if !json_equal(expected, value) {
+                errors.push(violation(path, "const", format!("Expected {}", expected)));
+            }
+        }
+
+        match value {
+            Value::Object(object) => self.check_object(schema, object, path, errors),
+            Value::Array(items) => self.check_array(schema, items, path, errors),
+            Value::String(s) => check_string(schema, s, path, errors),
+            Value::Number(n) => check_number(schema, n.as_f64().unwrap_or(f64::NAN), path, errors),
+            _ => {}
+        }
+    }
+
+    fn check_object(
+        &self,
+        schema: &SchemaNode,
+        object: &Map<String, Value>,
+        path: &str,
+        errors: &mut Vec<ValidationError>,
+    ) {
+        for name in &schema.required {
+            if !object.contains_key(name) {
+                let message = format!("Property '{}' is required", name);
+                errors.push(violation(&child_path(path, name), "required", message));
+            }
+        }
+
+        for (name, value) in object {
+            let child = child_path(path, name);
+            match schema.properties.get(name) {
+                Some(node) => self.check_node(node, value, &child, 0, errors),
+                None => match &schema.additional_properties {
+                    Some(Node::Bool(false)) => {
+                        let message = format!("Property '{}' is not allowed", name);
+                        errors.push(violation(&child, "additionalProperties", message));
+                    }
+                    Some(node) => self.check_node(node, value, &child, 0, errors),
+                    None => {}
+                },
+            }
+        }
+    }
+
+    fn check_array(
+        &self,
+        schema: &SchemaNode,
+        items: &[Value],
+        path: &str,
+        errors: &mut Vec<ValidationError>,
+    ) {
+        if let Some(min) = schema.min_items {
+            if items.len() < min {
+                let message = format!("Expected at least {} items, found {}", min, items.len());
+                errors.push(violation(path, "minItems", message));
+            }
+        }
+        if let Some(max) = schema.max_items {
+            if items.len() > max {
+                let message = format!("Expected at most {} items, found {}", max, items.len());
+                errors.push(violation(path, "maxItems", message));
+            }
+        }
+
+        for (i, item) in items.iter().enumerate() {
+            let node = schema.prefix_items.get(i).or(schema.items.as_ref());
+            if let Some(node) = node {
+                self.check_node(node, item, &child_path(path, &i.to_string()), 0, errors);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ValidationRule for JsonSchemaRule {
+    async fn validate(&self, record: &Record) -> Result<()> {
+        super::issues_into_result(self.violations(&record.value))
+    }
+
+    async fn check(&self, record: &Record) -> Result<Vec<ValidationError>> {
+        Ok(self.violations(&record.value))
+    }
+
+    fn name(&self) -> &str {
+        "json_schema"
+    }
+
+    fn description(&self) -> &str {
+        "Validates the record value against a JSON Schema"
+    }
+}
+
+/// Compiled schema or boolean schema
+#[derive(Debug)]
+enum Node {
+    Bool(bool),
+    Schema(Box<SchemaNode>),
+}
+
+#[derive(Debug, Default)]
+struct SchemaNode {
+    reference: Option<String>,
+    types: Option<Vec<JsonType>>,
+    enum_values: Option<Vec<Value>>,
+    const_value: Option<Value>,
+    required: Vec<String>,
+    properties: HashMap<String, Node>,
+    additional_properties: Option<Node>,
+    items: Option<Node>,
+    prefix_items: Vec<Node>,
+    min_items: Option<usize>,
+    max_items: Option<usize>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    pattern: Option<Regex>,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    exclusive_minimum: Option<f64>,
+    exclusive_maximum: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JsonType {
+    Null,
+    Boolean,
+    Object,
+    Array,
+    Number,
+    Integer,
+    String,
+}
+
+impl JsonType {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "null" => JsonType::Null,
+            "boolean" => JsonType::Boolean,
+            "object" => JsonType::Object,
+            "array" => JsonType::Array,
+            "number" => JsonType::Number,
+            "integer" => JsonType::Integer,
+            "string" => JsonType::String,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            JsonType::Null => "null",
+            JsonType::Boolean => "boolean",
+            JsonType::Object => "object",
+            JsonType::Array => "array",
+            JsonType::Number => "number",
+            JsonType::Integer => "integer",
+            JsonType::String => "string",
+        }
+    }
+
+    fn matches(self, value: &Value) -> bool {
+        match (self, value) {
+            (JsonType::Null, Value::Null)
+            | (JsonType::Boolean, Value::Bool(_))
+            | (JsonType::Object, Value::Object(_))
+            | (JsonType::Array, Value::Array(_))
+            | (JsonType::Number, Value::Number(_))
+            | (JsonType::String, Value::String(_)) => true,
+            (JsonType::Integer, Value::Number(n)) => {
+                n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Compiles schema documents into [`Node`]s, collecting `$ref` targets
+struct Compiler<'a> {
+    document: &'a Value,
+    refs: HashMap<String, Node>,
+    pending: Vec<String>,
+}
+
+impl Compiler<'_> {
+    fn compile(&mut self, schema: &Value, location: &str) -> Result<Node> {
+        let object = match schema {
+            Value::Bool(b) => return Ok(Node::Bool(*b)),
+            Value::Object(object) => object,
+            _ => return Err(schema_error(location, "a schema must be an object or boolean")),
+        };
+
+        let mut node = SchemaNode::default();
+        for (keyword, value) in object {
+            let at = child_path(location, keyword);
+            match keyword.as_str() {
+                "$ref" => node.reference = Some(self.reference(value, &at)?),
+                "type" => node.types = Some(parse_types(value, &at)?),
+                "enum" => match value {
+                    Value::Array(values) => node.enum_values = Some(values.clone()),
+                    _ => return Err(schema_error(&at, "expected an array")),
+                },
+                "const" => node.const_value = Some(value.clone()),
+                "required" => node.required = parse_strings(value, &at)?,
+                "properties" => {
+                    let properties = value
+                        .as_object()
+                        .ok_or_else(|| schema_error(&at, "expected an object"))?;
+                    for (name, property) in properties {
+                        let compiled = self.compile(property, &child_path(&at, name))?;
+                        node.properties.insert(name.clone(), compiled);
+                    }
+                }
+                "additionalProperties" => {
+                    node.additional_properties = Some(self.compile(value, &at)?)
+                }
+                "items" => node.items = Some(self.compile(value, &at)?),
+                "prefixItems" => {
+                    let items = value
+                        .as_array()
+                        .ok_or_else(|| schema_error(&at, "expected an array"))?;
+                    for (i, item) in items.iter().enumerate() {
+                        let compiled = self.compile(item, &child_path(&at, &i.to_string()))?;
+                        node.prefix_items.push(compiled);
+                    }
+                }
+                "minItems" => node.min_items = Some(parse_count(value, &at)?),
+                "maxItems" => node.max_items = Some(parse_count(value, &at)?),
+                "minLength" => node.min_length = Some(parse_count(value, &at)?),
+                "maxLength" => node.max_length = Some(parse_count(value, &at)?),
+                "pattern" => {
+                    let pattern = value
+                        .as_str()
+                        .ok_or_else(|| schema_error(&at, "expected a string"))?;
+                    let regex = Regex::new(pattern)
+                        .map_err(|e| schema_error(&at, &format!("invalid pattern: {}", e)))?;
+                    node.pattern = Some(regex);
+                }
+                "minimum" => node.minimum = Some(parse_number(value, &at)?),
+                "maximum" => node.maximum = Some(parse_number(value, &at)?),
+                "exclusiveMinimum" => node.exclusive_minimum = Some(parse_number(value, &at)?),
+                "exclusiveMaximum" => node.exclusive_maximum = Some(parse_number(value, &at)?),
+                _ => {}
+            }
+        }
+
+        Ok(Node::Schema(Box::new(node)))
+    }
+
+    /// Turn a local `$ref` into a JSON pointer and queue it for compilation
+    fn reference(&mut self, value: &Value, at: &str) -> Result<String> {
+        let reference = value
+            .as_str()
+            .ok_or_else(|| schema_error(at, "expected a string"))?;
+        let pointer = reference
+            .strip_prefix('#')
+            .ok_or_else(|| schema_error(at, "only local references ('#...') are supported"))?;
+        if self.document.pointer(pointer).is_none() {
+            return Err(schema_error(at, &format!("'{}' does not resolve", reference)));
+        }
+
+        self.pending.push(pointer.to_string());
+        Ok(pointer.to_string())
+    }
+}
+
+fn parse_types(value: &Value, at: &str) -> Result<Vec<JsonType>> {
+    let names = match value {
+        Value::String(name) => vec![name.clone()],
+        _ => parse_strings(value, at)?,
+    };
+    names
+        .iter()
+        .map(|name| {
+            JsonType::parse(name)
+                .ok_or_else(|| schema_error(at, &format!("unknown type '{}'", name)))
+        })
+        .collect()
+}
+
+fn parse_strings(value: &Value, at: &str) -> Result<Vec<String>> {
+    value
+        .as_array()
+        .and_then(|values| {
+            values
+                .iter()
+                .map(|v| v.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or_else(|| schema_error(at, "expected an array of strings"))
+}
+
+fn parse_count(value: &Value, at: &str) -> Result<usize> {
+    value
+        .as_u64()
+        .map(|n| n as usize)
+        .ok_or_else(|| schema_error(at, "expected a non-negative integer"))
+}
+
+fn parse_number(value: &Value, at: &str) -> Result<f64> {
+    value
+        .as_f64()
+        .ok_or_else(|| schema_error(at, "expected a number"))
+}
+
+fn check_string(schema: &SchemaNode, s: &str, path: &str, errors: &mut Vec<ValidationError>) {
+    let length = s.chars().count();
+    if let Some(min) = schema.min_length {
+        if length < min {
+            let message = format!("Expected at least {} characters, found {}", min, length);
+            errors.push(violation(path, "minLength", message));
+        }
+    }
+    if let Some(max) = schema.max_length {
+        if length > max {
+            let message = format!("Expected at most {} characters, found {}", max, length);
+            errors.push(violation(path, "maxLength", message));
+        }
+    }
+    if let Some(pattern) = &schema.pattern {
+        if !pattern.is_match(s) {
+            let message = format!("'{}' does not match pattern '{}'", s, pattern.as_str());
+            errors.push(violation(path, "pattern", message));
+        }
+    }
+}
+
+fn check_number(schema: &SchemaNode, n: f64, path: &str, errors: &mut Vec<ValidationError>) {
+    if let Some(min) = schema.minimum {
+        if n < min {
+            errors.push(violation(path, "minimum", format!("{} is less than {}", n, min)));
+        }
+    }
+    if let Some(max) = schema.maximum {
+        if n > max {
+            errors.push(violation(path, "maximum", format!("{} is greater than {}", n, max)));
+        }
+    }
+    if let Some(min) = schema.exclusive_minimum {
+        if n <= min {
+            let message = format!("{} must be greater than {}", n, min);
+            errors.push(violation(path, "exclusiveMinimum", message));
+        }
+    }
+    if let Some(max) = schema.exclusive_maximum {
+        if n >= max {
+            let message = format!("{} must be less than {}", n, max);
+            errors.push(violation(path, "exclusiveMaximum", message));
+        }
+    }
+}
+
+/// JSON equality that treats `1` and `1.0` as the same number
+fn json_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        (Value::Array(x), Value::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(x, y)| json_equal(x, y))
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len()
+                && x.iter().all(|(k, v)| y.get(k).is_some_and(|w| json_equal(v, w)))
+        }
+        _ => a == b,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+    }
+}
+
+/// Append a reference token to a JSON pointer, escaping `~` and `/`
+fn child_path(path: &str, token: &str) -> String {
+    format!("{}/{}", path, token.replace('~', "~0").replace('/', "~1"))
+}
+
+fn violation(path: &str, keyword: &str, message: impl Into<String>) -> ValidationError {
+    ValidationError::new(path, keyword, message)
+}
+
+fn schema_error(location: &str, message: &str) -> Error {
+    Error::config(format!("Invalid JSON Schema at '#{}': {}", location, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn order_schema() -> Value {
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "type": "object",
+            "required": ["id", "customer", "items"],
+            "properties": {
+                "id": {"type": "string", "pattern": "^ord-[0-9]+$"},
+                "status": {"enum": ["new", "shipped"]},
+                "customer": {"$ref": "#/$defs/customer"},
+                "items": {
+                    "type": "array",
+                    "minItems": 1,
+                    "items": {
+                        "type": "object",
+                        "required": ["sku"],
+                        "properties": {
+                            "sku": {"type": "string", "minLength": 3},
+                            "price": {"type": "number", "minimum": 0}
+                        }
+                    }
+                }
+            },
+            "$defs": {
+                "customer": {
+                    "type": "object",
+                    "required": ["email"],
+                    "properties": {"email": {"type": "string"}},
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    fn fields(errors: &[ValidationError]) -> Vec<(&str, &str)> {
+        errors.iter().map(|e| (e.field.as_str(), e.rule.as_str())).collect()
+    }
+
+    #[tokio::test]
+    async fn test_valid_record_passes() {
+        let rule = JsonSchemaRule::new(order_schema()).unwrap();
+        let record = Record::new(
+            "order",
+            json!({
+                "id": "ord-1",
+                "status": "new",
+                "customer": {"email": "a@example.com"},
+                "items": [{"sku": "abc", "price": 9.5}]
+            }),
+        );
+
+        assert!(rule.validate(&record).await.is_ok());
+        assert!(rule.check(&record).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reports_every_violation_with_pointer() {
+        let rule = JsonSchemaRule::new(order_schema()).unwrap();
+        let record = Record::new(
+            "order",
+            json!({
+                "id": "order-1",
+                "status": "lost",
+                "customer": {"name": "x"},
+                "items": [{"sku": "ab", "price": -1}, {"price": "free"}]
+            }),
+        );
+
+        let mut errors = rule.check(&record).await.unwrap();
+        errors.sort_by(|a, b| a.field.cmp(&b.field));
+        assert_eq!(
+            fields(&errors),
+            [
+                ("/customer/email", "required"),
+                ("/customer/name", "additionalProperties"),
+                ("/id", "pattern"),
+                ("/items/0/price", "minimum"),
+                ("/items/0/sku", "minLength"),
+                ("/items/1/price", "type"),
+                ("/items/1/sku", "required"),
+                ("/status", "enum"),
+            ]
+        );
+
+        match rule.validate(&record).await {
+            Err(Error::ValidationFailed(report)) => assert_eq!(report.issues().len(), 8),
+            other => panic!("expected validation failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_types_and_recursive_refs() {
+        let schema = json!({
+            "$ref": "#/$defs/node",
+            "$defs": {
+                "node": {
+                    "type": "object",
+                    "properties": {
+                        "value": {"type": ["integer", "null"], "exclusiveMaximum": 10},
+                        "children": {"type": "array", "items": {"$ref": "#/$defs/node"}}
+                    }
+                }
+            }
+        });
+        let rule = JsonSchemaRule::new(schema).unwrap();
+
+        let tree = json!({"value": 1, "children": [{"value": 2.0}, {"value": null}]});
+        assert!(rule.violations(&tree).is_empty());
+
+        let tree = json!({"value": 1, "children": [{"children": [{"value": 1.5}, {"value": 10}]}]});
+        assert_eq!(
+            fields(&rule.violations(&tree)),
+            [
+                ("/children/0/children/0/value", "type"),
+                ("/children/0/children/1/value", "exclusiveMaximum"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_schema_is_rejected() {
+        let err = JsonSchemaRule::new(json!({"properties": {"a": {"type": "text"}}})).unwrap_err();
+        assert!(err.to_string().contains("#/properties/a/type"));
+
+        let err = JsonSchemaRule::new(json!({"$ref": "#/$defs/missing"})).unwrap_err();
+        assert!(err.to_string().contains("does not resolve"));
+
+        assert!(JsonSchemaRule::new(json!({"pattern": "("})).is_err());
+        assert!(JsonSchemaRule::new(json!({"$ref": "other.json"})).is_err());
+    }
+}
@@ -4,7 +4,7 @@ use enterprise_data_processor::{
     pipeline::PipelineBuilder,
     processor::Processor,
     record::Record,
-    storage::InMemoryStorage,
+    storage::{InMemoryStorage, Storage},
     transform::{EnrichTransform, NormalizeTransform},
     validation::{NonEmptyStringRule, RequiredFieldRule, Validator},
     ProcessorConfig,
@@ -99,7 +99,7 @@ async fn test_pipeline_with_validation_and_transforms() {
         }),
     );
 
-    let result = pipeline.execute(record).await.unwrap();
+    let result = pipeline.execute(record).await.unwrap().into_record().unwrap();
 
     // Verify transformations
     assert_eq!(result.value["name"], json!("john doe"));
@@ -180,6 +180,7 @@ async fn test_record_lifecycle() {
 }
 
 #[tokio::test]
+#[allow(unused_variables)]
 async fn test_storage_operations() {
     let storage = InMemoryStorage::new();
     
@@ -279,3 +280,59 @@ async fn test_processor_statistics() {
     processor.clear_records();
     assert_eq!(processor.total_records(), 0);
 }
+
+#[tokio::test]
+async fn test_filtered_records_are_not_failures() {
+    use enterprise_data_processor::{record::RecordStatus, transform::FilterTransform};
+
+    let processor = Processor::new(ProcessorConfig::default()).unwrap();
+    processor
+        .register_transform(Arc::new(FilterTransform::new("adults_only", |r: &Record| {
+            r.value["age"].as_u64().unwrap_or(0) >= 18
+        })))
+        .unwrap();
+
+    let records = vec![
+        Record::new("adult", json!({"age": 30})),
+        Record::new("minor", json!({"age": 12})),
+    ];
+
+    let results = processor.process_batch(records).await.unwrap();
+    assert!(results.iter().all(|r| r.success));
+
+    let filtered: Vec<_> = results.iter().filter(|r| r.filtered).collect();
+    assert_eq!(filtered.len(), 1);
+    assert_eq!(filtered[0].record.key, "minor");
+    assert!(filtered[0].outputs.is_empty());
+    assert_eq!(filtered[0].record.metadata.status, RecordStatus::Filtered);
+    assert!(filtered[0].error.is_none());
+}
+
+#[tokio::test]
+async fn test_process_json_lines_source() {
+    use enterprise_data_processor::source::{JsonLinesSource, KeyExtractor};
+    use futures::StreamExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("events.ndjson");
+    std::fs::write(
+        &path,
+        "{\"id\": \"e1\", \"name\": \" Alice \"}\nbroken\n{\"id\": \"e2\", \"name\": \"Bob\"}\n",
+    )
+    .unwrap();
+
+    let processor = Processor::new(ProcessorConfig::default()).unwrap();
+    let normalize = NormalizeTransform::new("normalize", vec!["name".to_string()]);
+    processor.register_transform(Arc::new(normalize)).unwrap();
+
+    let source = JsonLinesSource::new(&path).key(KeyExtractor::Field("id".to_string()));
+    let results: Vec<_> = processor.process_source(source).collect().await;
+
+    assert_eq!(results.len(), 3);
+    assert!(results[1].is_err());
+
+    let first = results[0].as_ref().unwrap();
+    assert!(first.success);
+    assert_eq!(first.outputs[0].key, "e1");
+    assert_eq!(first.outputs[0].value["name"], json!("alice"));
+}
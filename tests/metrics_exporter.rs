@@ -0,0 +1,44 @@
+//! Scrapes the Prometheus exporter installed by `try_init_with_config`
+//!
+//! Kept in its own test binary because the exporter installs a process-wide
+//! metrics recorder.
+
+use enterprise_data_processor::{
+    config::MetricsExporterConfig, try_init_with_config, Processor, ProcessorConfig, Record,
+};
+use tokio::net::TcpStream;
+
+mod common;
+
+use common::{free_address, scrape};
+
+#[tokio::test]
+async fn test_init_serves_prometheus_metrics() {
+    let exporter_config = MetricsExporterConfig::new(free_address());
+    let config = ProcessorConfig::builder()
+        .metrics_exporter(exporter_config.clone())
+        .build();
+
+    let exporter = try_init_with_config(config.clone()).unwrap().unwrap();
+    assert!(exporter.local_addr().ip().is_loopback());
+
+    let processor = Processor::new(config).unwrap();
+    for i in 0..3 {
+        processor.process(Record::new(format!("key_{}", i), i)).await.unwrap();
+    }
+
+    let response = scrape(exporter.local_addr()).await;
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("records_processed_success 3"));
+    assert!(response.contains("# TYPE record_processing_duration_ms histogram"));
+    assert!(response.contains("record_processing_duration_ms_bucket{le=\"2.5\"}"));
+
+    // Only one global recorder can be installed per process
+    let again = MetricsExporterConfig::new(free_address());
+    let again = ProcessorConfig::builder().metrics_exporter(again).build();
+    assert!(try_init_with_config(again).is_err());
+
+    let address = exporter.local_addr();
+    exporter.shutdown();
+    assert!(TcpStream::connect(address).await.is_err());
+}
@@ -0,0 +1,30 @@
+//! Scrapes the Prometheus exporter started by plain `init_with_config`
+//!
+//! Kept in its own test binary because the exporter installs a process-wide
+//! metrics recorder.
+
+use enterprise_data_processor::{
+    config::MetricsExporterConfig, init_with_config, Processor, ProcessorConfig, Record,
+};
+
+mod common;
+
+use common::{free_address, scrape};
+
+#[tokio::test]
+async fn test_init_keeps_serving_after_returning() {
+    let address = free_address();
+    let config = ProcessorConfig::builder()
+        .metrics_exporter(MetricsExporterConfig::new(address))
+        .build();
+
+    // The exporter handle is dropped inside `init_with_config`
+    init_with_config(config.clone());
+
+    let processor = Processor::new(config).unwrap();
+    processor.process(Record::new("key", 1)).await.unwrap();
+
+    let response = scrape(address).await;
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("records_processed_success 1"));
+}
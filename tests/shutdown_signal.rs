@@ -0,0 +1,44 @@
+//! Shuts a processor down on SIGTERM
+//!
+//! Kept in its own test binary because it signals the whole test process.
+
+#![cfg(unix)]
+
+use enterprise_data_processor::{Processor, ProcessorConfig, Record};
+use std::{process::Command, time::Duration};
+use tokio::signal::unix::{signal, SignalKind};
+
+#[tokio::test]
+async fn test_sigterm_shuts_processor_down() {
+    // Install the handler up front so SIGTERM can never terminate the test binary
+    let _guard = signal(SignalKind::terminate()).unwrap();
+
+    let processor = Processor::new(ProcessorConfig::default()).unwrap();
+    assert!(processor.process(Record::new("before", 1)).await.unwrap().success);
+
+    let mut shutdown = tokio::spawn({
+        let processor = processor.clone();
+        async move { processor.shutdown_on_signal(Duration::from_secs(1)).await }
+    });
+
+    // Keep signalling until the task is listening and has shut down
+    let report = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let status = Command::new("kill")
+                .args(["-TERM", &std::process::id().to_string()])
+                .status()
+                .unwrap();
+            assert!(status.success());
+            let poll = tokio::time::timeout(Duration::from_millis(50), &mut shutdown);
+            if let Ok(report) = poll.await {
+                return report.unwrap().unwrap();
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    assert!(report.is_clean());
+    assert!(processor.is_shutting_down());
+    assert!(processor.process(Record::new("after", 2)).await.is_err());
+}